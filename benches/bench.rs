@@ -11,7 +11,7 @@ use random_hand::{
     generate_random_non_simple_pure_hand, generate_random_pure_hand,
 };
 use xiangting::{
-    PlayerCount, calculate_necessary_tiles, calculate_replacement_number,
+    PlayerCount, analyze, calculate_necessary_tiles, calculate_replacement_number,
     calculate_unnecessary_tiles,
 };
 
@@ -147,6 +147,22 @@ fn unnecessary_tiles_proposed(c: &mut Criterion) {
     group.finish();
 }
 
+fn analyze_proposed(c: &mut Criterion) {
+    let mut rng = create_rng();
+    let hands: Vec<_> = (0..NUM_HAND)
+        .map(|_| generate_random_pure_hand(&mut rng))
+        .collect();
+
+    let mut group = c.benchmark_group("xiangting");
+    group.sample_size(SAMPLE_SIZE);
+    group.nresamples(NUM_RESAMPLE);
+    group.bench_function("Analyze Proposed", |b| {
+        let mut hand = hands.iter();
+        b.iter(|| analyze(hand.next().unwrap(), &PlayerCount::Four).unwrap())
+    });
+    group.finish();
+}
+
 criterion_group!(
     benches_number,
     xiangting_normal,
@@ -160,5 +176,6 @@ criterion_group!(
     necessary_tiles_proposed,
     unnecessary_tiles_baseline,
     unnecessary_tiles_proposed,
+    analyze_proposed,
 );
 criterion_main!(benches_number, benches_tiles);
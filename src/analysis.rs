@@ -0,0 +1,593 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use super::constrained;
+use super::qiduizi;
+use super::shisanyao;
+use super::standard;
+use crate::bingpai::{Bingpai, Bingpai3p, BingpaiError};
+use crate::config::{Config, PlayerCount, WinningForms};
+use crate::hand::{Hand, Meld};
+use crate::tile::{TileCounts, TileFlags};
+use core::cmp::Ordering;
+
+/// The result of the calculation for a hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Analysis {
+    /// The replacement number (= xiàngtīng number + 1).
+    pub replacement_number: u8,
+    /// The necessary tiles (有効牌).
+    pub necessary_tiles: TileFlags,
+    /// The unnecessary tiles (不要牌).
+    pub unnecessary_tiles: TileFlags,
+}
+
+impl Analysis {
+    pub(crate) const UNREACHABLE: Self = Self {
+        replacement_number: u8::MAX,
+        necessary_tiles: 0,
+        unnecessary_tiles: 0,
+    };
+
+    #[inline]
+    fn from_standard(
+        (replacement_number, necessary_tiles, unnecessary_tiles): (u8, TileFlags, TileFlags),
+    ) -> Self {
+        Self {
+            replacement_number,
+            necessary_tiles,
+            unnecessary_tiles,
+        }
+    }
+
+    fn new(
+        (replacement_number, necessary_tiles): (u8, TileFlags),
+        (r, unnecessary_tiles): (u8, TileFlags),
+    ) -> Self {
+        debug_assert_eq!(replacement_number, r);
+        Self {
+            replacement_number,
+            necessary_tiles,
+            unnecessary_tiles,
+        }
+    }
+
+    /// Returns `None` if the form cannot be completed with the hand.
+    fn new_if_reachable(necessary: (u8, TileFlags), unnecessary: (u8, TileFlags)) -> Option<Self> {
+        (necessary.0 != u8::MAX).then(|| Self::new(necessary, unnecessary))
+    }
+
+    pub(crate) fn update_min(&mut self, rhs: Self) {
+        match rhs.replacement_number.cmp(&self.replacement_number) {
+            Ordering::Less => *self = rhs,
+            Ordering::Equal => {
+                self.necessary_tiles |= rhs.necessary_tiles;
+                self.unnecessary_tiles |= rhs.unnecessary_tiles;
+            }
+            Ordering::Greater => (),
+        }
+    }
+}
+
+/// Keeps the smaller replacement number of `lhs` and `rhs`,
+/// and merges the tiles if the replacement numbers are equal.
+pub(crate) fn update_min(lhs: &mut (u8, TileFlags), (r, tiles): (u8, TileFlags)) {
+    match r.cmp(&lhs.0) {
+        Ordering::Less => *lhs = (r, tiles),
+        Ordering::Equal => lhs.1 |= tiles,
+        Ordering::Greater => (),
+    }
+}
+
+/// Calculates the replacement number (= xiàngtīng number + 1), the necessary tiles
+/// and the unnecessary tiles for a given hand at once.
+///
+/// This is equivalent to calling [`calculate_necessary_tiles`](crate::calculate_necessary_tiles)
+/// and [`calculate_unnecessary_tiles`](crate::calculate_unnecessary_tiles),
+/// but validates and decomposes the hand only once.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `player_count` - A reference to the number of players.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{PlayerCount, analyze};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 123m456p789s11222z + 3z
+/// let hand: [u8; 34] = [
+///     1, 1, 1, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 1, 1, 1, // s
+///     2, 2, 1, 0, 0, 0, 0, // z
+/// ];
+///
+/// let analysis = analyze(&hand, &PlayerCount::Four)?;
+/// assert_eq!(analysis.replacement_number, 1u8);
+/// assert_eq!(analysis.necessary_tiles, 0b0000011_000000000_000000000_000000000); // 12z
+/// assert_eq!(analysis.unnecessary_tiles, 0b0000100_000000000_000000000_000000000); // 3z
+/// # Ok(())
+/// # }
+/// ```
+#[inline]
+pub fn analyze(bingpai: &impl Hand, player_count: &PlayerCount) -> Result<Analysis, BingpaiError> {
+    analyze_with_config(bingpai, &player_count.clone().into())
+}
+
+/// Calculates the replacement number (= xiàngtīng number + 1), the necessary tiles
+/// and the unnecessary tiles for a given hand at once under the given rules.
+///
+/// Only the winning forms selected in `config` are considered.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid or no selected winning form can be completed with the hand.
+pub fn analyze_with_config(bingpai: &impl Hand, config: &Config) -> Result<Analysis, BingpaiError> {
+    let analysis = if !config.uses_tables() || bingpai.num_wildcards() > 0 {
+        let num_wildcards = bingpai.num_wildcards();
+        constrained::analyze(bingpai.bingpai(), bingpai.melds(), num_wildcards, config)
+    } else {
+        match config.player_count {
+            PlayerCount::Four => analyze_4p(bingpai.bingpai(), bingpai.melds(), config),
+            PlayerCount::Three => analyze_3p(bingpai.bingpai(), bingpai.melds(), config),
+        }
+    }?;
+
+    match analysis.replacement_number {
+        u8::MAX => Err(BingpaiError::NoReachableForm),
+        _ => Ok(analysis),
+    }
+}
+
+fn analyze_4p(
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    config: &Config,
+) -> Result<Analysis, BingpaiError> {
+    let bingpai = Bingpai::with_melds(tile_counts, melds)?;
+
+    let mut analysis = Analysis::UNREACHABLE;
+
+    if config.forms.contains(WinningForms::STANDARD) {
+        analysis.update_min(Analysis::from_standard(standard::analyze(&bingpai)));
+    }
+
+    if config.forms.contains(WinningForms::QIDUIZI) {
+        analysis.update_min(Analysis::new(
+            qiduizi::calculate_necessary_tiles(&bingpai, config.qiduizi_quads_as_two_pairs),
+            qiduizi::calculate_unnecessary_tiles(&bingpai, config.qiduizi_quads_as_two_pairs),
+        ));
+    }
+
+    if config.forms.contains(WinningForms::SHISANYAO) {
+        analysis.update_min(Analysis::new(
+            shisanyao::calculate_necessary_tiles(&bingpai),
+            shisanyao::calculate_unnecessary_tiles(&bingpai),
+        ));
+    }
+
+    Ok(analysis)
+}
+
+fn analyze_3p(
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    config: &Config,
+) -> Result<Analysis, BingpaiError> {
+    let bingpai_3p = Bingpai3p::with_melds(tile_counts, melds)?;
+
+    let mut analysis = Analysis::UNREACHABLE;
+
+    if config.forms.contains(WinningForms::STANDARD) {
+        analysis.update_min(Analysis::from_standard(standard::analyze_3p(&bingpai_3p)));
+    }
+
+    if config.forms.contains(WinningForms::QIDUIZI) {
+        analysis.update_min(Analysis::new(
+            qiduizi::calculate_necessary_tiles_3p(&bingpai_3p, config.qiduizi_quads_as_two_pairs),
+            qiduizi::calculate_unnecessary_tiles_3p(&bingpai_3p, config.qiduizi_quads_as_two_pairs),
+        ));
+    }
+
+    let bingpai = bingpai_3p.into();
+
+    if config.forms.contains(WinningForms::SHISANYAO) {
+        analysis.update_min(Analysis::new(
+            shisanyao::calculate_necessary_tiles(&bingpai),
+            shisanyao::calculate_unnecessary_tiles(&bingpai),
+        ));
+    }
+
+    Ok(analysis)
+}
+
+/// The results of the calculation for each winning form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormBreakdown {
+    /// The result for the standard form (4 melds and a pair, 一般形).
+    pub standard: Analysis,
+    /// The result for seven pairs (七対子).
+    ///
+    /// This is `None` if the hand has melds or fewer than 13 tiles.
+    pub qiduizi: Option<Analysis>,
+    /// The result for thirteen orphans (国士無双).
+    ///
+    /// This is `None` if the hand has melds or fewer than 13 tiles.
+    pub shisanyao: Option<Analysis>,
+}
+
+/// Calculates the replacement number, the necessary tiles and the unnecessary tiles
+/// for each winning form separately.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `player_count` - A reference to the number of players.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{PlayerCount, calculate_form_breakdown};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 1188m288p55s1177z
+/// let hand: [u8; 34] = [
+///     2, 0, 0, 0, 0, 0, 0, 2, 0, // m
+///     0, 1, 0, 0, 0, 0, 0, 2, 0, // p
+///     0, 0, 0, 0, 2, 0, 0, 0, 0, // s
+///     2, 0, 0, 0, 0, 0, 2, // z
+/// ];
+///
+/// let breakdown = calculate_form_breakdown(&hand, &PlayerCount::Four)?;
+/// assert_eq!(breakdown.standard.replacement_number, 4u8);
+///
+/// let qiduizi = breakdown.qiduizi.unwrap();
+/// assert_eq!(qiduizi.replacement_number, 1u8);
+/// assert_eq!(qiduizi.necessary_tiles, 0b0000000_000000000_000000010_000000000); // 2p
+/// assert_eq!(qiduizi.unnecessary_tiles, 0);
+///
+/// let shisanyao = breakdown.shisanyao.unwrap();
+/// assert_eq!(shisanyao.replacement_number, 10u8);
+/// # Ok(())
+/// # }
+/// ```
+#[inline]
+pub fn calculate_form_breakdown(
+    bingpai: &impl Hand,
+    player_count: &PlayerCount,
+) -> Result<FormBreakdown, BingpaiError> {
+    calculate_form_breakdown_with_config(bingpai, &player_count.clone().into())
+}
+
+/// Calculates the replacement number, the necessary tiles and the unnecessary tiles
+/// for each winning form separately under the given rules.
+///
+/// The winning forms selected in `config` are not used.
+/// Each form of [`FormBreakdown`] is calculated under the other rules.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid or the standard form cannot be completed with the hand.
+pub fn calculate_form_breakdown_with_config(
+    bingpai: &impl Hand,
+    config: &Config,
+) -> Result<FormBreakdown, BingpaiError> {
+    let config = &Config {
+        forms: WinningForms::STANDARD | WinningForms::QIDUIZI | WinningForms::SHISANYAO,
+        ..config.clone()
+    };
+    if !config.uses_tables() || bingpai.num_wildcards() > 0 {
+        let num_wildcards = bingpai.num_wildcards();
+        let [standard, qiduizi, shisanyao, ..] =
+            constrained::analyze_forms(bingpai.bingpai(), bingpai.melds(), num_wildcards, config)?
+                .map(|result| result.filter(|a| a.replacement_number != u8::MAX));
+        return Ok(FormBreakdown {
+            standard: standard.ok_or(BingpaiError::NoReachableForm)?,
+            qiduizi,
+            shisanyao,
+        });
+    }
+
+    match config.player_count {
+        PlayerCount::Four => {
+            calculate_form_breakdown_4p(bingpai.bingpai(), bingpai.melds(), config)
+        }
+        PlayerCount::Three => {
+            calculate_form_breakdown_3p(bingpai.bingpai(), bingpai.melds(), config)
+        }
+    }
+}
+
+fn calculate_form_breakdown_4p(
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    config: &Config,
+) -> Result<FormBreakdown, BingpaiError> {
+    let bingpai = Bingpai::with_melds(tile_counts, melds)?;
+
+    Ok(FormBreakdown {
+        standard: Analysis::from_standard(standard::analyze(&bingpai)),
+        qiduizi: Analysis::new_if_reachable(
+            qiduizi::calculate_necessary_tiles(&bingpai, config.qiduizi_quads_as_two_pairs),
+            qiduizi::calculate_unnecessary_tiles(&bingpai, config.qiduizi_quads_as_two_pairs),
+        ),
+        shisanyao: Analysis::new_if_reachable(
+            shisanyao::calculate_necessary_tiles(&bingpai),
+            shisanyao::calculate_unnecessary_tiles(&bingpai),
+        ),
+    })
+}
+
+fn calculate_form_breakdown_3p(
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    config: &Config,
+) -> Result<FormBreakdown, BingpaiError> {
+    let bingpai_3p = Bingpai3p::with_melds(tile_counts, melds)?;
+
+    let standard = Analysis::from_standard(standard::analyze_3p(&bingpai_3p));
+    let qiduizi = Analysis::new_if_reachable(
+        qiduizi::calculate_necessary_tiles_3p(&bingpai_3p, config.qiduizi_quads_as_two_pairs),
+        qiduizi::calculate_unnecessary_tiles_3p(&bingpai_3p, config.qiduizi_quads_as_two_pairs),
+    );
+
+    let bingpai = bingpai_3p.into();
+
+    let shisanyao = Analysis::new_if_reachable(
+        shisanyao::calculate_necessary_tiles(&bingpai),
+        shisanyao::calculate_unnecessary_tiles(&bingpai),
+    );
+
+    Ok(FormBreakdown {
+        standard,
+        qiduizi,
+        shisanyao,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hand::{FullHand, HandWithWildcards};
+    use crate::necessary_tiles::{
+        calculate_necessary_tiles, calculate_necessary_tiles_with_config,
+    };
+    use crate::test_utils::FromTileCode;
+    use crate::unnecessary_tiles::{
+        calculate_unnecessary_tiles, calculate_unnecessary_tiles_with_config,
+    };
+
+    #[test]
+    fn calculate_form_breakdown_each_form() {
+        let bingpai = TileCounts::from_code("19m19p19s1234567z");
+        let breakdown = calculate_form_breakdown(&bingpai, &PlayerCount::Four).unwrap();
+
+        assert_eq!(breakdown.standard.replacement_number, 9);
+        assert_eq!(breakdown.qiduizi.unwrap().replacement_number, 7);
+        let shisanyao = breakdown.shisanyao.unwrap();
+        assert_eq!(shisanyao.replacement_number, 1);
+        assert_eq!(
+            shisanyao.necessary_tiles,
+            TileFlags::from_code("19m19p19s1234567z")
+        );
+        assert_eq!(shisanyao.unnecessary_tiles, 0);
+    }
+
+    #[test]
+    fn calculate_form_breakdown_with_melds() {
+        let bingpai = TileCounts::from_code("123m456p1122z");
+        let melds = [Meld::Pon(33)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let breakdown = calculate_form_breakdown(&hand, &PlayerCount::Four).unwrap();
+
+        assert_eq!(breakdown.standard.replacement_number, 1);
+        assert_eq!(
+            breakdown.standard.necessary_tiles,
+            TileFlags::from_code("12z")
+        );
+        assert_eq!(breakdown.standard.unnecessary_tiles, 0);
+        assert!(breakdown.qiduizi.is_none());
+        assert!(breakdown.shisanyao.is_none());
+    }
+
+    #[test]
+    fn calculate_form_breakdown_min_matches_combined() {
+        let bingpai = TileCounts::from_code("1188m288p55s1177z");
+        let breakdown = calculate_form_breakdown(&bingpai, &PlayerCount::Four).unwrap();
+        let (r, n) = calculate_necessary_tiles(&bingpai, &PlayerCount::Four).unwrap();
+        let (_, u) = calculate_unnecessary_tiles(&bingpai, &PlayerCount::Four).unwrap();
+
+        let qiduizi = breakdown.qiduizi.unwrap();
+        assert!(breakdown.standard.replacement_number > r);
+        assert_eq!(qiduizi.replacement_number, r);
+        assert_eq!(qiduizi.necessary_tiles, n);
+        assert_eq!(qiduizi.unnecessary_tiles, u);
+    }
+
+    #[test]
+    fn calculate_form_breakdown_3_player() {
+        let bingpai = TileCounts::from_code("1199m288p55s1177z");
+        let breakdown = calculate_form_breakdown(&bingpai, &PlayerCount::Three).unwrap();
+
+        assert_eq!(breakdown.standard.replacement_number, 4);
+        let qiduizi = breakdown.qiduizi.unwrap();
+        assert_eq!(qiduizi.replacement_number, 1);
+        assert_eq!(qiduizi.necessary_tiles, TileFlags::from_code("2p"));
+        assert!(breakdown.shisanyao.is_some());
+    }
+
+    #[test]
+    fn calculate_form_breakdown_3_player_err_bingpai_2m() {
+        let bingpai = TileCounts::from_code("2m");
+        let breakdown = calculate_form_breakdown(&bingpai, &PlayerCount::Three);
+        assert!(matches!(
+            breakdown,
+            Err(BingpaiError::InvalidTileForThreePlayer(1))
+        ));
+    }
+
+    #[test]
+    fn calculate_form_breakdown_with_config_matches_tables() {
+        for code in [
+            "19m19p19s1234567z",
+            "1188m288p55s1177z",
+            "199m146779p12s246z",
+        ] {
+            let bingpai = TileCounts::from_code(code);
+            let expected = calculate_form_breakdown(&bingpai, &PlayerCount::Four).unwrap();
+            // The hands have no quads, so the rule does not change the results.
+            let config = Config {
+                forms: WinningForms::DUIDUIHE,
+                qiduizi_quads_as_two_pairs: true,
+                ..Default::default()
+            };
+            let breakdown = calculate_form_breakdown_with_config(&bingpai, &config).unwrap();
+            assert_eq!(breakdown, expected, "{code}");
+        }
+    }
+
+    #[test]
+    fn calculate_form_breakdown_with_config_allowed_tiles() {
+        // One suit and honors (混一色)
+        let config = Config {
+            allowed_tiles: TileFlags::from_code("123456789m1234567z"),
+            ..Default::default()
+        };
+        let bingpai = TileCounts::from_code("19m19p19s1234567z");
+        let breakdown = calculate_form_breakdown_with_config(&bingpai, &config).unwrap();
+        assert!(breakdown.shisanyao.is_none());
+        let standard = analyze_with_config(
+            &bingpai,
+            &Config {
+                forms: WinningForms::STANDARD,
+                ..config.clone()
+            },
+        )
+        .unwrap();
+        assert_eq!(breakdown.standard, standard);
+        assert_eq!(
+            breakdown.standard.necessary_tiles & !config.allowed_tiles,
+            0
+        );
+    }
+
+    #[test]
+    fn calculate_form_breakdown_with_config_wildcards() {
+        let bingpai = TileCounts::from_code("1188m288p55s117z");
+        let config = Config {
+            wildcard_tile: Some(33),
+            ..Default::default()
+        };
+        let hand = HandWithWildcards {
+            hand: &bingpai,
+            num_wildcards: 1,
+        };
+        let breakdown = calculate_form_breakdown_with_config(&hand, &config).unwrap();
+        // The wildcard stands for 7z.
+        let qiduizi = breakdown.qiduizi.unwrap();
+        assert_eq!(qiduizi.replacement_number, 1);
+        assert_eq!(breakdown.shisanyao.unwrap().replacement_number, 9);
+    }
+
+    #[test]
+    fn calculate_form_breakdown_with_config_err_no_reachable_standard() {
+        let bingpai = TileCounts::from_code("1188m288p55s1177z");
+        let config = Config {
+            allowed_tiles: TileFlags::from_code("1m"),
+            ..Default::default()
+        };
+        let breakdown = calculate_form_breakdown_with_config(&bingpai, &config);
+        assert!(matches!(breakdown, Err(BingpaiError::NoReachableForm)));
+    }
+
+    #[test]
+    fn analyze_matches_separate_calculations() {
+        for code in [
+            "123m456p789s1122z",
+            "1188m288p55s1177z",
+            "19m19p19s1234567z",
+            "119m19p19s1234567z",
+            "199m146779p12s246z",
+            "1111247777m1112z",
+        ] {
+            let bingpai = TileCounts::from_code(code);
+            for player_count in [PlayerCount::Four, PlayerCount::Three] {
+                let Ok(analysis) = analyze(&bingpai, &player_count) else {
+                    continue;
+                };
+                let (r, n) = calculate_necessary_tiles(&bingpai, &player_count).unwrap();
+                let (_, u) = calculate_unnecessary_tiles(&bingpai, &player_count).unwrap();
+                assert_eq!(analysis.replacement_number, r, "{code}");
+                assert_eq!(analysis.necessary_tiles, n, "{code}");
+                assert_eq!(analysis.unnecessary_tiles, u, "{code}");
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_with_config_allowed_tiles_matches_separate_calculations() {
+        // One suit and honors (混一色)
+        let config = Config {
+            allowed_tiles: TileFlags::from_code("123456789m1234567z"),
+            ..Default::default()
+        };
+        for code in [
+            "1123456m1p9s1122z",
+            "1188m288p55s1177z",
+            "19m19p19s1234567z",
+        ] {
+            let bingpai = TileCounts::from_code(code);
+            let analysis = analyze_with_config(&bingpai, &config).unwrap();
+            let (r, n) = calculate_necessary_tiles_with_config(&bingpai, &config).unwrap();
+            let (_, u) = calculate_unnecessary_tiles_with_config(&bingpai, &config).unwrap();
+            assert_eq!(analysis.replacement_number, r, "{code}");
+            assert_eq!(analysis.necessary_tiles, n, "{code}");
+            assert_eq!(analysis.unnecessary_tiles, u, "{code}");
+            assert_eq!(n & !config.allowed_tiles, 0, "{code}");
+        }
+    }
+
+    #[test]
+    fn analyze_with_config_err_no_forms() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let config = Config {
+            forms: WinningForms::empty(),
+            ..Default::default()
+        };
+        let analysis = analyze_with_config(&bingpai, &config);
+        assert!(matches!(analysis, Err(BingpaiError::NoReachableForm)));
+    }
+}
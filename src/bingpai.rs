@@ -2,11 +2,14 @@
 // SPDX-License-Identifier: MIT
 // This file is part of https://github.com/Apricot-S/xiangting
 
+use crate::decomposition::Block;
+use crate::hand::Meld;
 use crate::tile::{Tile, TileCounts};
 use thiserror::Error;
 
 const MAX_TILE_COPIES: u8 = 4;
-const MAX_NUM_BINGPAI: u8 = 14;
+pub(crate) const MAX_NUM_FULU_MIANZI: u8 = 4;
+const NUM_TILES_PER_MELD: u8 = 3;
 
 pub(crate) struct Bingpai<'a> {
     tile_counts: &'a TileCounts,
@@ -20,6 +23,7 @@ pub(crate) struct Bingpai3p<'a> {
 
 /// Errors that occur when an invalid pure hand (純手牌) is provided.
 #[derive(Debug, Error)]
+#[non_exhaustive]
 pub enum BingpaiError {
     /// A specific tile count in the pure hand exceeds 4.
     #[error("tile {tile} count must be 4 or less but was {count}")]
@@ -29,8 +33,9 @@ pub enum BingpaiError {
         /// The actual number of copies found in the pure hand.
         count: u8,
     },
-    /// Total tile count in the pure hand exceeds 14.
-    #[error("total tile count must be 14 or less but was {0}")]
+    /// Total tile count in the hand exceeds 14 (17 in 16-tile mahjong),
+    /// where each meld counts as 3 tiles and each wildcard counts as 1 tile.
+    #[error("total tile count must be 14 or less (17 in 16-tile mahjong) but was {0}")]
     TooManyTiles(u8),
     /// Total tile count in the pure hand is not of the form 3n+1 or 3n+2.
     #[error("total tile count must be a multiple of 3 plus 1 or 2 but was {0}")]
@@ -38,15 +43,103 @@ pub enum BingpaiError {
     /// The pure hand contains tiles that are not used in three-player mahjong (2m-8m).
     #[error("tile {0} cannot be used in three-player mahjong")]
     InvalidTileForThreePlayer(Tile),
+    /// The number of red fives of a suit exceeds 1.
+    #[error("red five {tile} count must be 1 or less but was {count}")]
+    TooManyRedFives {
+        /// The five of the suit that has too many red fives.
+        tile: Tile,
+        /// The actual number of red fives.
+        count: u8,
+    },
+    /// The number of melds exceeds 4 (5 in 16-tile mahjong).
+    ///
+    /// The count saturates at 255.
+    #[error("meld count must be 4 or less (5 in 16-tile mahjong) but was {0}")]
+    TooManyMelds(u8),
+    /// A meld does not represent real tiles (e.g., a sequence of honors).
+    #[error("meld {0:?} is invalid")]
+    InvalidMeld(Meld),
+    /// A specific tile count across the pure hand and the melds exceeds 4.
+    #[error("tile {tile} count including melds must be 4 or less but was {count}")]
+    TooManyCopiesWithMelds {
+        /// The tile that appears too many times.
+        tile: Tile,
+        /// The actual number of copies found in the pure hand and the melds.
+        count: u8,
+    },
+    /// A meld cannot be made in three-player mahjong (sequences, or tiles from 2m to 8m).
+    #[error("meld {0:?} cannot be made in three-player mahjong")]
+    InvalidMeldForThreePlayer(Meld),
+    /// None of the selected winning forms can be completed with the hand
+    /// (e.g., only seven pairs is selected for a hand with melds).
+    #[error("no selected winning form can be completed with the hand")]
+    NoReachableForm,
+    /// Total tile count in the pure hand is not of the form 3n+2 when a tile is to be discarded.
+    #[error("total tile count must be a multiple of 3 plus 2 to discard a tile but was {0}")]
+    InvalidTileCountForDiscard(u8),
+    /// Total tile count in the pure hand is not of the form 3n+1 when a tile is to be drawn.
+    #[error("total tile count must be a multiple of 3 plus 1 to draw a tile but was {0}")]
+    InvalidTileCountForDraw(u8),
+    /// The hand is not tenpai (聴牌), i.e., the replacement number is not 1.
+    #[error("hand must be tenpai but the replacement number was {0}")]
+    NotTenpai(u8),
+    /// A specific tile count across the hand and the visible tiles exceeds 4.
+    #[error("tile {tile} count including visible tiles must be 4 or less but was {count}")]
+    TooManyVisibleCopies {
+        /// The tile that appears too many times.
+        tile: Tile,
+        /// The actual number of copies found in the hand and the visible tiles.
+        count: u8,
+    },
+    /// A required block is not a triplet, a sequence or a pair, or does not represent real tiles.
+    #[error("required block {0:?} must be a triplet, a sequence or a pair of real tiles")]
+    InvalidRequiredBlock(Block),
+    /// The pure hand contains tiles that are not used in Sichuan mahjong (honors).
+    #[error("tile {0} cannot be used in Sichuan mahjong")]
+    InvalidTileForSichuan(Tile),
+    /// A meld cannot be made in Sichuan mahjong (honors, or tiles of the void suit).
+    #[error("meld {0:?} cannot be made in Sichuan mahjong")]
+    InvalidMeldForSichuan(Meld),
+    /// The tile designated as the wildcard (百搭) does not exist.
+    #[error("tile {0} cannot be the wildcard")]
+    InvalidWildcardTile(Tile),
+    /// Wildcards are given to a calculation other than the replacement number,
+    /// the necessary tiles and the unnecessary tiles.
+    #[error("wildcards are not supported in this calculation")]
+    UnsupportedWildcards,
 }
 
 pub(crate) trait TileCountsExt {
-    fn count(&self) -> Result<u8, BingpaiError>;
-    fn count_3p(&self) -> Result<u8, BingpaiError>;
+    fn count(
+        &self,
+        melds: &[Meld],
+        num_wildcards: u8,
+        max_num_mianzi: u8,
+    ) -> Result<u8, BingpaiError>;
+    fn count_3p(
+        &self,
+        melds: &[Meld],
+        num_wildcards: u8,
+        max_num_mianzi: u8,
+    ) -> Result<u8, BingpaiError>;
 }
 
 impl TileCountsExt for TileCounts {
-    fn count(&self) -> Result<u8, BingpaiError> {
+    fn count(
+        &self,
+        melds: &[Meld],
+        num_wildcards: u8,
+        max_num_mianzi: u8,
+    ) -> Result<u8, BingpaiError> {
+        if melds.len() > usize::from(max_num_mianzi) {
+            let num_melds = u8::try_from(melds.len()).unwrap_or(u8::MAX);
+            return Err(BingpaiError::TooManyMelds(num_melds));
+        }
+
+        if let Some(&m) = melds.iter().find(|m| !m.is_valid()) {
+            return Err(BingpaiError::InvalidMeld(m));
+        }
+
         self.iter()
             .enumerate()
             .find(|(_, c)| **c > MAX_TILE_COPIES)
@@ -56,25 +149,73 @@ impl TileCountsExt for TileCounts {
             })
             .map_or(Ok(()), Err)?;
 
-        let num_bingpai: u8 = self.iter().sum();
+        let mut all_tiles = *self;
+        melds.iter().for_each(|m| m.add_to(&mut all_tiles));
+        all_tiles
+            .iter()
+            .enumerate()
+            .find(|(_, c)| **c > MAX_TILE_COPIES)
+            .map(|(i, &c)| BingpaiError::TooManyCopiesWithMelds {
+                tile: i as Tile,
+                count: c,
+            })
+            .map_or(Ok(()), Err)?;
+
+        let num_bingpai = self.iter().sum::<u8>().saturating_add(num_wildcards);
+        let num_tiles = num_bingpai.saturating_add(NUM_TILES_PER_MELD * melds.len() as u8);
+        if num_tiles > NUM_TILES_PER_MELD * max_num_mianzi + 2 {
+            return Err(BingpaiError::TooManyTiles(num_tiles));
+        }
+
         match num_bingpai {
-            n if n > MAX_NUM_BINGPAI => Err(BingpaiError::TooManyTiles(n)),
             n if n % 3 == 0 => Err(BingpaiError::InvalidTileCount(n)),
             n => Ok(n),
         }
     }
 
-    fn count_3p(&self) -> Result<u8, BingpaiError> {
+    fn count_3p(
+        &self,
+        melds: &[Meld],
+        num_wildcards: u8,
+        max_num_mianzi: u8,
+    ) -> Result<u8, BingpaiError> {
         if let Some(i) = self[1..8].iter().position(|&t| t > 0) {
             return Err(BingpaiError::InvalidTileForThreePlayer((i + 1) as u8));
         }
-        self.count()
+
+        if let Some(&m) = melds
+            .iter()
+            .find(|m| matches!(m, Meld::Chi(_)) || (1..8).contains(&m.tile()))
+        {
+            return Err(BingpaiError::InvalidMeldForThreePlayer(m));
+        }
+
+        self.count(melds, num_wildcards, max_num_mianzi)
     }
 }
 
 impl<'a> Bingpai<'a> {
+    #[cfg(test)]
     pub(crate) fn new(tile_counts: &'a TileCounts) -> Result<Self, BingpaiError> {
-        let num_bingpai = tile_counts.count()?;
+        Self::with_melds(tile_counts, &[])
+    }
+
+    pub(crate) fn with_melds(
+        tile_counts: &'a TileCounts,
+        melds: &[Meld],
+    ) -> Result<Self, BingpaiError> {
+        Self::with_wildcards(tile_counts, melds, 0, MAX_NUM_FULU_MIANZI)
+    }
+
+    pub(crate) fn with_wildcards(
+        tile_counts: &'a TileCounts,
+        melds: &[Meld],
+        num_wildcards: u8,
+        max_num_mianzi: u8,
+    ) -> Result<Self, BingpaiError> {
+        let num_bingpai = tile_counts.count(melds, num_wildcards, max_num_mianzi)?;
+        // Since each meld counts as 3 tiles, this never exceeds `max_num_mianzi - melds.len()`.
+        // Thus, seven pairs and thirteen orphans are ruled out for a hand with melds.
         let num_required_bingpai_mianzi = num_bingpai / 3;
 
         Ok(Self {
@@ -83,6 +224,17 @@ impl<'a> Bingpai<'a> {
         })
     }
 
+    /// Creates a pure hand from the tiles of a validated hand, e.g., without the wildcards.
+    pub(crate) fn from_validated(
+        tile_counts: &'a TileCounts,
+        num_required_bingpai_mianzi: u8,
+    ) -> Self {
+        Self {
+            tile_counts,
+            num_required_bingpai_mianzi,
+        }
+    }
+
     #[inline(always)]
     #[must_use]
     pub(crate) fn tile_counts(&self) -> &'a TileCounts {
@@ -97,8 +249,27 @@ impl<'a> Bingpai<'a> {
 }
 
 impl<'a> Bingpai3p<'a> {
+    #[cfg(test)]
     pub(crate) fn new(tile_counts: &'a TileCounts) -> Result<Self, BingpaiError> {
-        let num_bingpai = tile_counts.count_3p()?;
+        Self::with_melds(tile_counts, &[])
+    }
+
+    pub(crate) fn with_melds(
+        tile_counts: &'a TileCounts,
+        melds: &[Meld],
+    ) -> Result<Self, BingpaiError> {
+        Self::with_wildcards(tile_counts, melds, 0, MAX_NUM_FULU_MIANZI)
+    }
+
+    pub(crate) fn with_wildcards(
+        tile_counts: &'a TileCounts,
+        melds: &[Meld],
+        num_wildcards: u8,
+        max_num_mianzi: u8,
+    ) -> Result<Self, BingpaiError> {
+        let num_bingpai = tile_counts.count_3p(melds, num_wildcards, max_num_mianzi)?;
+        // Since each meld counts as 3 tiles, this never exceeds `max_num_mianzi - melds.len()`.
+        // Thus, seven pairs and thirteen orphans are ruled out for a hand with melds.
         let num_required_bingpai_mianzi = num_bingpai / 3;
 
         Ok(Self {
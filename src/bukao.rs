@@ -0,0 +1,370 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use crate::analysis::Analysis;
+use crate::config::WinningForms;
+use crate::constrained::{self, BlockRules};
+use crate::tile::{HONOR_TILES, NUM_TILE_INDEX, TileCounts, TileFlags};
+
+const NUM_BUKAO_TILES: u32 = 14;
+const NUM_QIXING_SHUPAI: u32 = 7;
+
+// 147, 258 and 369 of a suit.
+const KNITTED_147: TileFlags = 0b001001001;
+const KNITTED_258: TileFlags = KNITTED_147 << 1;
+const KNITTED_369: TileFlags = KNITTED_147 << 2;
+
+/// The knitted straights (组合龙), i.e., 147, 258 and 369 of different suits.
+pub(crate) const KNITTED_STRAIGHTS: [TileFlags; 6] = [
+    KNITTED_147 | (KNITTED_258 << 9) | (KNITTED_369 << 18),
+    KNITTED_147 | (KNITTED_369 << 9) | (KNITTED_258 << 18),
+    KNITTED_258 | (KNITTED_147 << 9) | (KNITTED_369 << 18),
+    KNITTED_258 | (KNITTED_369 << 9) | (KNITTED_147 << 18),
+    KNITTED_369 | (KNITTED_147 << 9) | (KNITTED_258 << 18),
+    KNITTED_369 | (KNITTED_258 << 9) | (KNITTED_147 << 18),
+];
+
+#[inline]
+fn held_tiles(tile_counts: &TileCounts) -> TileFlags {
+    tile_counts
+        .iter()
+        .enumerate()
+        .filter(|&(_, &c)| c > 0)
+        .fold(0, |flags, (i, _)| flags | (1 << i))
+}
+
+#[inline]
+fn duplicated_tiles(tile_counts: &TileCounts) -> TileFlags {
+    tile_counts
+        .iter()
+        .enumerate()
+        .filter(|&(_, &c)| c > 1)
+        .fold(0, |flags, (i, _)| flags | (1 << i))
+}
+
+/// Returns the tiles that can be in a winning hand of a knitted form
+/// (全不靠 or 七星不靠) with `knitted`.
+#[inline]
+pub(crate) fn bukao_tiles(knitted: TileFlags, allowed_tiles: TileFlags) -> TileFlags {
+    (knitted | HONOR_TILES) & allowed_tiles
+}
+
+/// Calculates the result of lesser honors and knitted tiles (全不靠) with the knitted straight `knitted`.
+///
+/// Any 14 of the honors and the tiles of `knitted` can be chosen,
+/// so all the held ones are chosen.
+fn analyze_quanbukao_with(
+    tile_counts: &TileCounts,
+    knitted: TileFlags,
+    allowed_tiles: TileFlags,
+) -> Analysis {
+    let candidates = bukao_tiles(knitted, allowed_tiles);
+    if candidates.count_ones() < NUM_BUKAO_TILES {
+        return Analysis::UNREACHABLE;
+    }
+
+    let held = held_tiles(tile_counts);
+    let num_held = (held & candidates).count_ones();
+    Analysis {
+        replacement_number: NUM_BUKAO_TILES.saturating_sub(num_held) as u8,
+        necessary_tiles: if num_held < NUM_BUKAO_TILES {
+            candidates & !held
+        } else {
+            0
+        },
+        unnecessary_tiles: (held & !candidates) | duplicated_tiles(tile_counts),
+    }
+}
+
+/// Calculates the result of greater honors and knitted tiles (七星不靠) with the knitted straight `knitted`.
+///
+/// All the honors and any 7 of the tiles of `knitted` are chosen.
+fn analyze_qixingbukao_with(
+    tile_counts: &TileCounts,
+    knitted: TileFlags,
+    allowed_tiles: TileFlags,
+) -> Analysis {
+    let knitted = knitted & allowed_tiles;
+    if HONOR_TILES & !allowed_tiles != 0 || knitted.count_ones() < NUM_QIXING_SHUPAI {
+        return Analysis::UNREACHABLE;
+    }
+
+    let held = held_tiles(tile_counts);
+    let num_honors = (held & HONOR_TILES).count_ones();
+    let num_knitted = (held & knitted).count_ones();
+    let mut necessary_tiles = HONOR_TILES & !held;
+    if num_knitted < NUM_QIXING_SHUPAI {
+        necessary_tiles |= knitted & !held;
+    }
+    let mut unnecessary_tiles = (held & !(HONOR_TILES | knitted)) | duplicated_tiles(tile_counts);
+    if num_knitted > NUM_QIXING_SHUPAI {
+        unnecessary_tiles |= held & knitted;
+    }
+
+    Analysis {
+        replacement_number: (HONOR_TILES.count_ones() - num_honors
+            + NUM_QIXING_SHUPAI.saturating_sub(num_knitted)) as u8,
+        necessary_tiles,
+        unnecessary_tiles,
+    }
+}
+
+/// Calculates the result of a knitted straight (组合龙) `knitted` with a meld and a pair.
+fn analyze_zuhelong_with(
+    tile_counts: &TileCounts,
+    num_required_mianzi: u8,
+    knitted: TileFlags,
+    allowed_tiles: TileFlags,
+) -> Analysis {
+    if knitted & !allowed_tiles != 0 {
+        return Analysis::UNREACHABLE;
+    }
+
+    let mut base = [0; NUM_TILE_INDEX];
+    for (i, count) in base.iter_mut().enumerate() {
+        *count = ((knitted >> i) & 1) as u8;
+    }
+    let rules = BlockRules::new(allowed_tiles);
+    constrained::analyze_standard_with_base(tile_counts, &base, num_required_mianzi - 3, 1, &rules)
+}
+
+/// Calculates the result of a knitted form with the knitted straight `knitted`.
+pub(crate) fn analyze_with(
+    form: WinningForms,
+    tile_counts: &TileCounts,
+    num_required_mianzi: u8,
+    knitted: TileFlags,
+    allowed_tiles: TileFlags,
+) -> Analysis {
+    match form {
+        // The knitted straight takes the places of 3 melds.
+        WinningForms::ZUHELONG if num_required_mianzi >= 3 => {
+            analyze_zuhelong_with(tile_counts, num_required_mianzi, knitted, allowed_tiles)
+        }
+        // The other knitted forms cannot have melds (副露).
+        WinningForms::QUANBUKAO if num_required_mianzi == 4 => {
+            analyze_quanbukao_with(tile_counts, knitted, allowed_tiles)
+        }
+        WinningForms::QIXINGBUKAO if num_required_mianzi == 4 => {
+            analyze_qixingbukao_with(tile_counts, knitted, allowed_tiles)
+        }
+        _ => Analysis::UNREACHABLE,
+    }
+}
+
+/// Calculates the result of a knitted form
+/// (全不靠, 七星不靠 or 组合龙) with the best knitted straight.
+pub(crate) fn analyze(
+    form: WinningForms,
+    tile_counts: &TileCounts,
+    num_required_mianzi: u8,
+    allowed_tiles: TileFlags,
+) -> Analysis {
+    let mut analysis = Analysis::UNREACHABLE;
+    for knitted in KNITTED_STRAIGHTS {
+        analysis.update_min(analyze_with(
+            form,
+            tile_counts,
+            num_required_mianzi,
+            knitted,
+            allowed_tiles,
+        ));
+    }
+    analysis
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::SplitMix64;
+    use crate::test_utils::{FromTileCode, random_hand};
+    use crate::tile::ALL_TILES;
+
+    fn update_with_target(analysis: &mut Analysis, tile_counts: &TileCounts, target: &TileCounts) {
+        let mut result = Analysis {
+            replacement_number: 0,
+            necessary_tiles: 0,
+            unnecessary_tiles: 0,
+        };
+        for (i, (&w, &h)) in target.iter().zip(tile_counts).enumerate() {
+            result.replacement_number += w.saturating_sub(h);
+            if w > h {
+                result.necessary_tiles |= 1 << i;
+            }
+            if h > w {
+                result.unnecessary_tiles |= 1 << i;
+            }
+        }
+        analysis.update_min(result);
+    }
+
+    fn brute_force(form: WinningForms, tile_counts: &TileCounts) -> Analysis {
+        let mut analysis = Analysis::UNREACHABLE;
+        for knitted in KNITTED_STRAIGHTS {
+            let mut base = [0; NUM_TILE_INDEX];
+            for (i, count) in base.iter_mut().enumerate() {
+                *count = ((knitted >> i) & 1) as u8;
+            }
+            match form {
+                WinningForms::QUANBUKAO => {
+                    let candidates: TileCounts =
+                        core::array::from_fn(|i| base[i] + ((HONOR_TILES >> i) & 1) as u8);
+                    // Exclude 2 of the 16 tiles.
+                    for a in 0..NUM_TILE_INDEX {
+                        for b in a + 1..NUM_TILE_INDEX {
+                            if candidates[a] == 0 || candidates[b] == 0 {
+                                continue;
+                            }
+                            let mut target = candidates;
+                            target[a] = 0;
+                            target[b] = 0;
+                            update_with_target(&mut analysis, tile_counts, &target);
+                        }
+                    }
+                }
+                WinningForms::QIXINGBUKAO => {
+                    // Exclude 2 of the 9 knitted tiles.
+                    for a in 0..27 {
+                        for b in a + 1..27 {
+                            if base[a] == 0 || base[b] == 0 {
+                                continue;
+                            }
+                            let mut target: TileCounts =
+                                core::array::from_fn(|i| base[i] + ((HONOR_TILES >> i) & 1) as u8);
+                            target[a] = 0;
+                            target[b] = 0;
+                            update_with_target(&mut analysis, tile_counts, &target);
+                        }
+                    }
+                }
+                _ => {
+                    for meld in 0..NUM_TILE_INDEX + 21 {
+                        for pair in 0..NUM_TILE_INDEX {
+                            let mut target = base;
+                            match meld {
+                                0..NUM_TILE_INDEX => target[meld] += 3,
+                                _ => {
+                                    let s = meld - NUM_TILE_INDEX;
+                                    let start = s / 7 * 9 + s % 7;
+                                    for o in 0..3 {
+                                        target[start + o] += 1;
+                                    }
+                                }
+                            }
+                            target[pair] += 2;
+                            if target.iter().all(|&n| n <= 4) {
+                                update_with_target(&mut analysis, tile_counts, &target);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        analysis
+    }
+
+    #[test]
+    fn analyze_matches_brute_force() {
+        let mut rng = SplitMix64(1);
+        // Also draw only the knitted tiles and the honors to get close to the forms.
+        let knitted_tiles = KNITTED_STRAIGHTS[0] | HONOR_TILES;
+        for form in [
+            WinningForms::QUANBUKAO,
+            WinningForms::QIXINGBUKAO,
+            WinningForms::ZUHELONG,
+        ] {
+            for (num_tiles, allowed_tiles) in [13, 14]
+                .into_iter()
+                .flat_map(|n| [(n, ALL_TILES), (n, knitted_tiles)])
+            {
+                for _ in 0..100 {
+                    let tile_counts = random_hand(&mut rng, num_tiles, allowed_tiles);
+                    let analysis = analyze(form, &tile_counts, 4, ALL_TILES);
+                    assert_eq!(
+                        analysis,
+                        brute_force(form, &tile_counts),
+                        "{form:?} {tile_counts:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_quanbukao() {
+        let tile_counts = TileCounts::from_code("147m258p369s12345z");
+        let analysis = analyze(WinningForms::QUANBUKAO, &tile_counts, 4, ALL_TILES);
+        assert_eq!(analysis.replacement_number, 0);
+
+        let tile_counts = TileCounts::from_code("147m258p369s1234z");
+        let analysis = analyze(WinningForms::QUANBUKAO, &tile_counts, 4, ALL_TILES);
+        assert_eq!(analysis.replacement_number, 1);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("567z"));
+        assert_eq!(analysis.unnecessary_tiles, 0);
+
+        // Seven pairs cannot be lesser honors and knitted tiles.
+        let tile_counts = TileCounts::from_code("1144m2255p3366s1z");
+        let analysis = analyze(WinningForms::QUANBUKAO, &tile_counts, 4, ALL_TILES);
+        assert_eq!(analysis.replacement_number, 7);
+    }
+
+    #[test]
+    fn analyze_qixingbukao() {
+        let tile_counts = TileCounts::from_code("147m25p36s1234567z");
+        let analysis = analyze(WinningForms::QIXINGBUKAO, &tile_counts, 4, ALL_TILES);
+        assert_eq!(analysis.replacement_number, 0);
+
+        let tile_counts = TileCounts::from_code("147m258p369s12345z");
+        let analysis = analyze(WinningForms::QIXINGBUKAO, &tile_counts, 4, ALL_TILES);
+        assert_eq!(analysis.replacement_number, 2);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("67z"));
+        assert_eq!(
+            analysis.unnecessary_tiles,
+            TileFlags::from_code("147m258p369s")
+        );
+    }
+
+    #[test]
+    fn analyze_zuhelong() {
+        let tile_counts = TileCounts::from_code("147m258p369s123s11z");
+        let analysis = analyze(WinningForms::ZUHELONG, &tile_counts, 4, ALL_TILES);
+        assert_eq!(analysis.replacement_number, 0);
+
+        let tile_counts = TileCounts::from_code("147m258p36s123s11z");
+        let analysis = analyze(WinningForms::ZUHELONG, &tile_counts, 4, ALL_TILES);
+        assert_eq!(analysis.replacement_number, 1);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("9s"));
+
+        // With a meld (副露)
+        let tile_counts = TileCounts::from_code("147m258p369s1z");
+        let analysis = analyze(WinningForms::ZUHELONG, &tile_counts, 3, ALL_TILES);
+        assert_eq!(analysis.replacement_number, 1);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("1z"));
+    }
+
+    #[test]
+    fn analyze_with_melds() {
+        let tile_counts = TileCounts::from_code("147m258p369s1z");
+        for form in [WinningForms::QUANBUKAO, WinningForms::QIXINGBUKAO] {
+            let analysis = analyze(form, &tile_counts, 3, ALL_TILES);
+            assert_eq!(analysis, Analysis::UNREACHABLE);
+        }
+
+        let tile_counts = TileCounts::from_code("147m25p");
+        let analysis = analyze(WinningForms::ZUHELONG, &tile_counts, 2, ALL_TILES);
+        assert_eq!(analysis, Analysis::UNREACHABLE);
+    }
+
+    #[test]
+    fn analyze_allowed_tiles() {
+        let tile_counts = TileCounts::from_code("147m258p369s1234z");
+        let allowed_tiles = ALL_TILES & !TileFlags::from_code("7z");
+        let analysis = analyze(WinningForms::QUANBUKAO, &tile_counts, 4, allowed_tiles);
+        assert_eq!(analysis.replacement_number, 1);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("56z"));
+
+        let analysis = analyze(WinningForms::QIXINGBUKAO, &tile_counts, 4, allowed_tiles);
+        assert_eq!(analysis, Analysis::UNREACHABLE);
+    }
+}
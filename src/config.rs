@@ -2,6 +2,10 @@
 // SPDX-License-Identifier: MIT
 // This file is part of https://github.com/Apricot-S/xiangting
 
+use crate::bingpai::MAX_NUM_FULU_MIANZI;
+use crate::tile::{ALL_TILES, Tile, TileFlags};
+use core::ops::{BitOr, BitOrAssign};
+
 /// The number of players.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PlayerCount {
@@ -12,3 +16,317 @@ pub enum PlayerCount {
     /// - Tiles from 2m (二萬) to 8m (八萬) are not used.
     Three,
 }
+
+/// A suit of number tiles (数牌).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Suit {
+    /// 萬子: Characters (m).
+    Wanzi,
+    /// 筒子: Dots (p).
+    Tongzi,
+    /// 索子: Bamboos (s).
+    Suozi,
+}
+
+impl Suit {
+    /// Returns the tiles of the suit.
+    #[inline]
+    pub(crate) const fn tiles(self) -> TileFlags {
+        0b111111111 << (9 * self as usize)
+    }
+}
+
+/// A set of winning forms (和了形) to be considered in the calculation.
+///
+/// The forms can be combined with the `|` operator.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::WinningForms;
+/// let forms = WinningForms::STANDARD | WinningForms::QIDUIZI;
+/// assert!(forms.contains(WinningForms::QIDUIZI));
+/// assert!(!forms.contains(WinningForms::SHISANYAO));
+/// assert_eq!(WinningForms::default(), forms | WinningForms::SHISANYAO);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WinningForms(u16);
+
+impl WinningForms {
+    /// The standard form (4 melds and a pair, 一般形).
+    pub const STANDARD: Self = Self(1 << 0);
+    /// Seven pairs (七対子).
+    pub const QIDUIZI: Self = Self(1 << 1);
+    /// Thirteen orphans (国士無双).
+    pub const SHISANYAO: Self = Self(1 << 2);
+    /// All triplets (対々和): 4 triplets or quads (刻子) and a pair.
+    ///
+    /// This form is not selected by default
+    /// since it is always covered by the standard form.
+    pub const DUIDUIHE: Self = Self(1 << 3);
+    /// All sequences (平和形): 4 sequences (順子) and a pair.
+    ///
+    /// The tiles of the pair can be limited with [`Config::pair_tiles`].
+    /// This form is not selected by default
+    /// since it is always covered by the standard form.
+    pub const PINGHE: Self = Self(1 << 4);
+    /// Outside hand (混全帯么九形): 4 melds and a pair,
+    /// each of which contains a terminal or an honor (么九牌).
+    ///
+    /// This form is not selected by default
+    /// since it is always covered by the standard form.
+    pub const HUNQUANDAI: Self = Self(1 << 5);
+    /// Terminals in all sets (純全帯么九形): 4 melds and a pair,
+    /// each of which contains a terminal (老頭牌).
+    ///
+    /// This form is not selected by default
+    /// since it is always covered by the standard form.
+    pub const CHUNQUANDAI: Self = Self(1 << 6);
+    /// Lesser honors and knitted tiles (全不靠): 14 different tiles
+    /// out of the 7 honors and the 9 tiles of a knitted straight (组合龙),
+    /// e.g., 147m258p369s1234567z without 1m and 7z.
+    ///
+    /// This form is not selected by default
+    /// since it is used only in Chinese Official rules (国標麻将).
+    pub const QUANBUKAO: Self = Self(1 << 7);
+    /// Greater honors and knitted tiles (七星不靠): the 7 honors
+    /// and 7 different tiles of a knitted straight (组合龙), e.g., 147m258p36s1234567z.
+    ///
+    /// This form is not selected by default
+    /// since it is used only in Chinese Official rules (国標麻将).
+    pub const QIXINGBUKAO: Self = Self(1 << 8);
+    /// Knitted straight (组合龙): 147, 258 and 369 of different suits, a meld and a pair,
+    /// e.g., 147m258p369s + 123s + 11z.
+    ///
+    /// This form is not selected by default
+    /// since it is used only in Chinese Official rules (国標麻将).
+    pub const ZUHELONG: Self = Self(1 << 9);
+
+    /// Returns an empty set of winning forms.
+    #[inline]
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns `true` if no winning form is selected.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all forms in `other` are selected.
+    #[inline]
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl Default for WinningForms {
+    /// Returns the standard form, seven pairs and thirteen orphans.
+    fn default() -> Self {
+        Self::STANDARD | Self::QIDUIZI | Self::SHISANYAO
+    }
+}
+
+impl BitOr for WinningForms {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for WinningForms {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// The rules applied to the calculation.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{Config, PlayerCount, WinningForms};
+/// let config = Config {
+///     player_count: PlayerCount::Three,
+///     forms: WinningForms::STANDARD,
+///     ..Default::default()
+/// };
+/// assert_eq!(config.forms, WinningForms::STANDARD);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// The number of players.
+    pub player_count: PlayerCount,
+    /// The winning forms to be considered.
+    pub forms: WinningForms,
+    /// The tiles allowed in the winning hand,
+    /// e.g., simples only (断么九) or one suit and honors (混一色).
+    ///
+    /// Tiles outside the set must be discarded and are never drawn.
+    /// Thirteen orphans (国士無双) can be completed only if all terminals and honors are allowed.
+    pub allowed_tiles: TileFlags,
+    /// The tiles allowed as the pair (雀頭) of the forms made of melds and a pair,
+    /// i.e., [`WinningForms::STANDARD`], [`WinningForms::DUIDUIHE`], [`WinningForms::PINGHE`],
+    /// [`WinningForms::HUNQUANDAI`] and [`WinningForms::CHUNQUANDAI`],
+    /// e.g., 2, 5 and 8 of each suit (二五八将) as in Changsha mahjong (长沙麻将).
+    ///
+    /// Seven pairs (七対子) and thirteen orphans (国士無双) are not affected.
+    pub pair_tiles: TileFlags,
+    /// Whether four identical tiles count as two pairs in seven pairs (七対子),
+    /// as in Chinese Official rules (国標麻将).
+    ///
+    /// If `false`, they count as only one pair as in riichi mahjong.
+    pub qiduizi_quads_as_two_pairs: bool,
+    /// The void suit (定缺) of Sichuan mahjong (四川麻将).
+    ///
+    /// If `Some`, honors (字牌) cannot be used in the hand,
+    /// and the tiles of the void suit must be discarded and are never drawn.
+    /// Seven pairs (七対子) can be selected as usual, and combined with
+    /// [`qiduizi_quads_as_two_pairs`](Self::qiduizi_quads_as_two_pairs)
+    /// for dragon seven pairs (龙七对).
+    pub void_suit: Option<Suit>,
+    /// Whether the hand has 16 tiles as in Taiwanese mahjong (台湾麻将),
+    /// so that a winning hand consists of five melds (面子) and a pair (雀頭).
+    ///
+    /// If `true`, up to 5 melds and 17 tiles are allowed,
+    /// and only the forms made of melds and a pair can be completed.
+    pub sixteen_tiles: bool,
+    /// The tile designated as the wildcard (百搭, 鬼牌), e.g., the white dragon (白板).
+    ///
+    /// If `Some`, the copies of the tile in the pure hand (純手牌) stand for any tile
+    /// in addition to the wildcards of [`Hand::num_wildcards`](crate::Hand::num_wildcards),
+    /// and drawing the tile always reduces the replacement number unless the hand is complete.
+    pub wildcard_tile: Option<Tile>,
+}
+
+impl Config {
+    /// Returns `true` if the precomputed tables can be used for the calculation.
+    #[inline]
+    pub(crate) fn uses_tables(&self) -> bool {
+        let table_forms = WinningForms::STANDARD | WinningForms::QIDUIZI | WinningForms::SHISANYAO;
+        table_forms.contains(self.forms)
+            && self.allowed_tiles & ALL_TILES == ALL_TILES
+            && self.pair_tiles & ALL_TILES == ALL_TILES
+            && self.void_suit.is_none()
+            && !self.sixteen_tiles
+            && self.wildcard_tile.is_none()
+    }
+
+    /// Returns the maximum number of melds in a hand.
+    #[inline]
+    pub(crate) fn max_num_mianzi(&self) -> u8 {
+        if self.sixteen_tiles {
+            MAX_NUM_FULU_MIANZI + 1
+        } else {
+            MAX_NUM_FULU_MIANZI
+        }
+    }
+}
+
+impl Default for Config {
+    /// Returns the config for four-player mahjong with the default winning forms and all tiles allowed.
+    fn default() -> Self {
+        Self {
+            player_count: PlayerCount::Four,
+            forms: WinningForms::default(),
+            allowed_tiles: ALL_TILES,
+            pair_tiles: ALL_TILES,
+            qiduizi_quads_as_two_pairs: false,
+            void_suit: None,
+            sixteen_tiles: false,
+            wildcard_tile: None,
+        }
+    }
+}
+
+impl From<PlayerCount> for Config {
+    fn from(player_count: PlayerCount) -> Self {
+        Self {
+            player_count,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn winning_forms_contains() {
+        let forms = WinningForms::STANDARD | WinningForms::SHISANYAO;
+        assert!(forms.contains(WinningForms::STANDARD));
+        assert!(!forms.contains(WinningForms::QIDUIZI));
+        assert!(forms.contains(WinningForms::SHISANYAO));
+        assert!(forms.contains(WinningForms::empty()));
+        assert!(!forms.contains(WinningForms::default()));
+    }
+
+    #[test]
+    fn config_uses_tables() {
+        assert!(Config::default().uses_tables());
+        let config = Config {
+            allowed_tiles: 0b0000000_111111111_111111111_111111111,
+            ..Default::default()
+        };
+        assert!(!config.uses_tables());
+
+        let config = Config {
+            forms: WinningForms::STANDARD | WinningForms::DUIDUIHE,
+            ..Default::default()
+        };
+        assert!(!config.uses_tables());
+
+        let config = Config {
+            pair_tiles: 0b0000000_010010010_010010010_010010010,
+            ..Default::default()
+        };
+        assert!(!config.uses_tables());
+
+        let config = Config {
+            qiduizi_quads_as_two_pairs: true,
+            ..Default::default()
+        };
+        assert!(config.uses_tables());
+
+        let config = Config {
+            void_suit: Some(Suit::Wanzi),
+            ..Default::default()
+        };
+        assert!(!config.uses_tables());
+
+        let config = Config {
+            sixteen_tiles: true,
+            ..Default::default()
+        };
+        assert!(!config.uses_tables());
+
+        let config = Config {
+            wildcard_tile: Some(31),
+            ..Default::default()
+        };
+        assert!(!config.uses_tables());
+    }
+
+    #[test]
+    fn suit_tiles() {
+        assert_eq!(Suit::Wanzi.tiles(), 0b111111111);
+        assert_eq!(Suit::Tongzi.tiles(), 0b111111111 << 9);
+        assert_eq!(Suit::Suozi.tiles(), 0b111111111 << 18);
+    }
+
+    #[test]
+    fn winning_forms_bitor_assign() {
+        let mut forms = WinningForms::empty();
+        assert!(forms.is_empty());
+        forms |= WinningForms::QIDUIZI;
+        assert_eq!(forms, WinningForms::QIDUIZI);
+    }
+}
@@ -0,0 +1,1094 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use super::bukao;
+use super::shisanyao;
+use crate::analysis::Analysis;
+use crate::bingpai::{Bingpai, Bingpai3p, BingpaiError};
+use crate::config::{Config, PlayerCount, WinningForms};
+use crate::hand::Meld;
+use crate::tile::{
+    ALL_TILES, HONOR_TILES, NUM_TILE_INDEX, Tile, TileCounts, TileFlags, WANZI_2_TO_8, YAOJIU_TILES,
+};
+
+// Five melds in 16-tile mahjong.
+const MAX_NUM_MIANZI: usize = 5;
+
+/// The winning forms in the order of the calculation.
+pub(crate) const FORMS: [WinningForms; 10] = [
+    WinningForms::STANDARD,
+    WinningForms::QIDUIZI,
+    WinningForms::SHISANYAO,
+    WinningForms::DUIDUIHE,
+    WinningForms::PINGHE,
+    WinningForms::HUNQUANDAI,
+    WinningForms::CHUNQUANDAI,
+    WinningForms::QUANBUKAO,
+    WinningForms::QIXINGBUKAO,
+    WinningForms::ZUHELONG,
+];
+
+// The first tiles of the sequences (順子), i.e., 1 to 7 of each suit.
+const SHUNZI_STARTS: TileFlags = 0b1111111 | (0b1111111 << 9) | (0b1111111 << 18);
+
+// The first tiles of the sequences containing a terminal, i.e., 1 and 7 of each suit.
+const QUANDAI_SHUNZI_STARTS: TileFlags = 0b1000001 | (0b1000001 << 9) | (0b1000001 << 18);
+
+// The terminals (老頭牌), i.e., 1 and 9 of each suit.
+const LAOTOU_TILES: TileFlags = YAOJIU_TILES & ((1 << 27) - 1);
+
+/// The blocks that can be used in a target hand of the standard form.
+#[derive(Debug, Clone)]
+pub(crate) struct BlockRules {
+    /// The first tiles of the allowed sequences (順子).
+    pub(crate) shunzi: TileFlags,
+    /// The tiles of the allowed triplets (刻子).
+    pub(crate) kezi: TileFlags,
+    /// The tiles of the allowed pairs (雀頭).
+    pub(crate) quetou: TileFlags,
+}
+
+impl BlockRules {
+    /// Returns the rules that allow any block made of `allowed_tiles`.
+    pub(crate) fn new(allowed_tiles: TileFlags) -> Self {
+        Self {
+            shunzi: SHUNZI_STARTS & allowed_tiles & (allowed_tiles >> 1) & (allowed_tiles >> 2),
+            kezi: allowed_tiles,
+            quetou: allowed_tiles,
+        }
+    }
+
+    /// Returns the rules of a winning form made of melds and a pair under `config`.
+    pub(crate) fn with_form(form: WinningForms, config: &Config) -> Self {
+        let allowed_tiles = allowed_tiles(config);
+        let rules = Self {
+            quetou: allowed_tiles & config.pair_tiles,
+            ..Self::new(allowed_tiles)
+        };
+        match form {
+            WinningForms::DUIDUIHE => Self { shunzi: 0, ..rules },
+            WinningForms::PINGHE => Self { kezi: 0, ..rules },
+            WinningForms::HUNQUANDAI => Self {
+                shunzi: rules.shunzi & QUANDAI_SHUNZI_STARTS,
+                kezi: rules.kezi & YAOJIU_TILES,
+                quetou: rules.quetou & YAOJIU_TILES,
+            },
+            WinningForms::CHUNQUANDAI => Self {
+                shunzi: rules.shunzi & QUANDAI_SHUNZI_STARTS,
+                kezi: rules.kezi & LAOTOU_TILES,
+                quetou: rules.quetou & LAOTOU_TILES,
+            },
+            _ => rules,
+        }
+    }
+}
+
+// The best results indexed by [the number of pairs][the number of melds].
+type Table = [[Analysis; MAX_NUM_MIANZI + 1]; 2];
+
+// The best results indexed by [the number of melds][the number of pairs]
+// [the number of sequences started at the previous tile][... at the tile before the previous one].
+type States = [[[[Analysis; 5]; 5]; 2]; MAX_NUM_MIANZI + 1];
+
+const EMPTY_TABLE: Table = [[Analysis::UNREACHABLE; MAX_NUM_MIANZI + 1]; 2];
+const EMPTY_STATES: States = [[[[Analysis::UNREACHABLE; 5]; 5]; 2]; MAX_NUM_MIANZI + 1];
+
+/// Calculates the results of a suit for each number of pairs and melds.
+///
+/// Each tile is visited in order, choosing the triplet, the pair and the sequences starting at it.
+/// The tiles of `base` are always in the target in addition to the chosen blocks.
+/// A tile is necessary if the target has more copies of it than the hand,
+/// and unnecessary if the hand has more copies of it than the target.
+fn analyze_suit(
+    tile_counts: &[u8],
+    base: &[u8],
+    offset: usize,
+    rules: &BlockRules,
+    num_required_mianzi: usize,
+) -> Table {
+    let mut states = EMPTY_STATES;
+    states[0][0][0][0] = Analysis {
+        replacement_number: 0,
+        necessary_tiles: 0,
+        unnecessary_tiles: 0,
+    };
+
+    for (i, (&count, &num_base)) in tile_counts.iter().zip(base).enumerate() {
+        let flag: TileFlags = 1 << (offset + i);
+        let num_base = usize::from(num_base);
+        let max_kezi = usize::from(rules.kezi & flag != 0);
+        let max_shunzi = if rules.shunzi & flag != 0 { 4 } else { 0 };
+
+        let mut next = EMPTY_STATES;
+        for (m, states_m) in states.iter().enumerate() {
+            for (p, states_p) in states_m.iter().enumerate() {
+                let max_quetou = usize::from(p == 0 && rules.quetou & flag != 0);
+                for (a, states_a) in states_p.iter().enumerate() {
+                    for (b, state) in states_a.iter().enumerate() {
+                        if state.replacement_number == u8::MAX {
+                            continue;
+                        }
+
+                        for k in 0..=max_kezi {
+                            for q in 0..=max_quetou {
+                                for s in 0..=max_shunzi {
+                                    let num_target = num_base + 3 * k + 2 * q + s + a + b;
+                                    if m + k + s > num_required_mianzi || num_target > 4 {
+                                        break;
+                                    }
+
+                                    let num_target = num_target as u8;
+                                    let mut analysis = *state;
+                                    if num_target > count {
+                                        analysis.replacement_number += num_target - count;
+                                        analysis.necessary_tiles |= flag;
+                                    } else if num_target < count {
+                                        analysis.unnecessary_tiles |= flag;
+                                    }
+                                    next[m + k + s][p + q][s][a].update_min(analysis);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        states = next;
+    }
+
+    let mut table = EMPTY_TABLE;
+    for (m, states_m) in states.iter().enumerate() {
+        for (p, states_p) in states_m.iter().enumerate() {
+            // No sequence can be pending at the end of a suit.
+            table[p][m] = states_p[0][0];
+        }
+    }
+    table
+}
+
+fn combine(lhs: &Table, rhs: &Table) -> Table {
+    let mut table = EMPTY_TABLE;
+    for (p1, lhs_p) in lhs.iter().enumerate() {
+        for (m1, l) in lhs_p.iter().enumerate() {
+            if l.replacement_number == u8::MAX {
+                continue;
+            }
+            for (p2, rhs_p) in rhs.iter().enumerate().take(2 - p1) {
+                for (m2, r) in rhs_p.iter().enumerate().take(MAX_NUM_MIANZI + 1 - m1) {
+                    if r.replacement_number == u8::MAX {
+                        continue;
+                    }
+                    table[p1 + p2][m1 + m2].update_min(Analysis {
+                        replacement_number: l.replacement_number + r.replacement_number,
+                        necessary_tiles: l.necessary_tiles | r.necessary_tiles,
+                        unnecessary_tiles: l.unnecessary_tiles | r.unnecessary_tiles,
+                    });
+                }
+            }
+        }
+    }
+    table
+}
+
+/// Calculates the result of the standard form (一般形) whose blocks follow `rules`.
+#[inline]
+pub(crate) fn analyze_standard(
+    tile_counts: &TileCounts,
+    num_required_mianzi: u8,
+    rules: &BlockRules,
+) -> Analysis {
+    analyze_standard_with_base(
+        tile_counts,
+        &[0; NUM_TILE_INDEX],
+        num_required_mianzi,
+        1,
+        rules,
+    )
+}
+
+/// Calculates the result of the standard form (一般形) that contains the tiles of `base`
+/// in addition to `num_mianzi` melds and `num_quetou` pairs following `rules`.
+pub(crate) fn analyze_standard_with_base(
+    tile_counts: &TileCounts,
+    base: &TileCounts,
+    num_mianzi: u8,
+    num_quetou: u8,
+    rules: &BlockRules,
+) -> Analysis {
+    let num_mianzi = usize::from(num_mianzi);
+    if rules.shunzi == 0 {
+        return analyze_kezi_only(tile_counts, base, num_mianzi, rules)[usize::from(num_quetou)]
+            [num_mianzi];
+    }
+
+    let table = [(0, 9), (9, 18), (18, 27), (27, 34)]
+        .into_iter()
+        .map(|(start, end)| {
+            let (tile_counts, base) = (&tile_counts[start..end], &base[start..end]);
+            analyze_suit(tile_counts, base, start, rules, num_mianzi)
+        })
+        .reduce(|lhs, rhs| combine(&lhs, &rhs))
+        .unwrap();
+
+    table[usize::from(num_quetou)][num_mianzi]
+}
+
+/// Calculates the results of the hands without sequences (順子), e.g., all triplets (対々和),
+/// for each number of pairs and melds.
+///
+/// Without sequences, the target of a tile does not depend on the other tiles,
+/// so the tiles are visited in order keeping only the numbers of pairs and melds.
+fn analyze_kezi_only(
+    tile_counts: &TileCounts,
+    base: &TileCounts,
+    num_required_mianzi: usize,
+    rules: &BlockRules,
+) -> Table {
+    let mut table = EMPTY_TABLE;
+    table[0][0] = Analysis {
+        replacement_number: 0,
+        necessary_tiles: 0,
+        unnecessary_tiles: 0,
+    };
+
+    for (i, (&count, &num_base)) in tile_counts.iter().zip(base).enumerate() {
+        let flag: TileFlags = 1 << i;
+        let max_kezi = usize::from(rules.kezi & flag != 0);
+
+        let mut next = EMPTY_TABLE;
+        for (p, table_p) in table.iter().enumerate() {
+            let max_quetou = usize::from(p == 0 && rules.quetou & flag != 0);
+            for (m, state) in table_p.iter().enumerate() {
+                if state.replacement_number == u8::MAX {
+                    continue;
+                }
+
+                for k in 0..=max_kezi {
+                    for q in 0..=max_quetou {
+                        let num_target = usize::from(num_base) + 3 * k + 2 * q;
+                        if m + k > num_required_mianzi || num_target > 4 {
+                            continue;
+                        }
+
+                        let num_target = num_target as u8;
+                        let mut analysis = *state;
+                        if num_target > count {
+                            analysis.replacement_number += num_target - count;
+                            analysis.necessary_tiles |= flag;
+                        } else if num_target < count {
+                            analysis.unnecessary_tiles |= flag;
+                        }
+                        next[p + q][m + k].update_min(analysis);
+                    }
+                }
+            }
+        }
+        table = next;
+    }
+    table
+}
+
+/// Calculates the result of seven pairs (七対子) made of `allowed_tiles`.
+///
+/// The cheapest seven pairs are chosen.
+/// A pair can be in some of the best choices if it costs no more than the seventh cheapest one.
+/// If `quads_as_two_pairs` is `true`, each kind can also be chosen as a second pair,
+/// which never costs less than the first one.
+pub(crate) fn analyze_qiduizi(
+    tile_counts: &TileCounts,
+    num_required_mianzi: u8,
+    allowed_tiles: TileFlags,
+    quads_as_two_pairs: bool,
+) -> Analysis {
+    if num_required_mianzi < 4 {
+        return Analysis::UNREACHABLE;
+    }
+
+    // The number of pairs for each cost (0, 1 or 2 tiles to draw).
+    let mut num_pairs = [0u8; 3];
+    for (i, &count) in tile_counts.iter().enumerate() {
+        if allowed_tiles & (1 << i) != 0 {
+            num_pairs[usize::from(2u8.saturating_sub(count))] += 1;
+            if quads_as_two_pairs {
+                num_pairs[usize::from(4u8.saturating_sub(count).min(2))] += 1;
+            }
+        }
+    }
+
+    let mut replacement_number = 0;
+    let mut num_chosen = 0;
+    let mut max_cost = None;
+    for (cost, &n) in num_pairs.iter().enumerate() {
+        let n = n.min(7 - num_chosen);
+        replacement_number += cost as u8 * n;
+        num_chosen += n;
+        if num_chosen == 7 {
+            max_cost = Some(cost as u8);
+            break;
+        }
+    }
+    let Some(max_cost) = max_cost else {
+        // Fewer than 7 pairs can be made of the allowed tiles.
+        return Analysis::UNREACHABLE;
+    };
+    let num_candidates: u8 = num_pairs[..=usize::from(max_cost)].iter().sum();
+
+    let mut necessary_tiles = 0;
+    let mut unnecessary_tiles = 0;
+    for (i, &count) in tile_counts.iter().enumerate() {
+        let flag: TileFlags = 1 << i;
+        if allowed_tiles & flag == 0 {
+            if count > 0 {
+                unnecessary_tiles |= flag;
+            }
+            continue;
+        }
+
+        let max_count = if quads_as_two_pairs { 4 } else { 2 };
+        // The cost of the next pair to be completed, and of the last pair that has a tile.
+        let next_cost = if count < 2 {
+            2 - count
+        } else {
+            4u8.saturating_sub(count)
+        };
+        let last_cost = if count <= 2 {
+            2 - count
+        } else {
+            4u8.saturating_sub(count)
+        };
+        let can_be_chosen = next_cost <= max_cost;
+        let can_be_excluded = last_cost > max_cost || (last_cost == max_cost && num_candidates > 7);
+        if can_be_chosen && count < max_count {
+            necessary_tiles |= flag;
+        }
+        if count > max_count || (can_be_excluded && count > 0) {
+            unnecessary_tiles |= flag;
+        }
+    }
+
+    Analysis {
+        replacement_number,
+        necessary_tiles,
+        unnecessary_tiles,
+    }
+}
+
+/// Calculates the result of thirteen orphans (国士無双),
+/// which can be completed only if all terminals and honors are allowed.
+pub(crate) fn analyze_shisanyao(bingpai: &Bingpai, allowed_tiles: TileFlags) -> Analysis {
+    if allowed_tiles & YAOJIU_TILES != YAOJIU_TILES {
+        return Analysis::UNREACHABLE;
+    }
+
+    let (replacement_number, necessary_tiles) = shisanyao::calculate_necessary_tiles(bingpai);
+    let (_, unnecessary_tiles) = shisanyao::calculate_unnecessary_tiles(bingpai);
+    Analysis {
+        replacement_number,
+        necessary_tiles,
+        unnecessary_tiles,
+    }
+}
+
+/// Returns the tiles allowed in the winning hand under `config`.
+pub(crate) fn allowed_tiles(config: &Config) -> TileFlags {
+    let allowed_tiles = match config.player_count {
+        PlayerCount::Four => config.allowed_tiles & ALL_TILES,
+        PlayerCount::Three => config.allowed_tiles & ALL_TILES & !WANZI_2_TO_8,
+    };
+    match config.void_suit {
+        Some(suit) => allowed_tiles & !HONOR_TILES & !suit.tiles(),
+        None => allowed_tiles,
+    }
+}
+
+/// Validates the hand with `num_wildcards` wildcards under `config`.
+pub(crate) fn validate<'a>(
+    tile_counts: &'a TileCounts,
+    melds: &[Meld],
+    num_wildcards: u8,
+    config: &Config,
+) -> Result<Bingpai<'a>, BingpaiError> {
+    if let Some(tile) = config.wildcard_tile {
+        if usize::from(tile) >= NUM_TILE_INDEX {
+            return Err(BingpaiError::InvalidWildcardTile(tile));
+        }
+    }
+
+    let max_num_mianzi = config.max_num_mianzi();
+    let bingpai = match config.player_count {
+        PlayerCount::Four => {
+            Bingpai::with_wildcards(tile_counts, melds, num_wildcards, max_num_mianzi)
+        }
+        PlayerCount::Three => {
+            Bingpai3p::with_wildcards(tile_counts, melds, num_wildcards, max_num_mianzi)
+                .map(Into::into)
+        }
+    }?;
+
+    if let Some(suit) = config.void_suit {
+        if let Some(i) = tile_counts[27..].iter().position(|&c| c > 0) {
+            return Err(BingpaiError::InvalidTileForSichuan((27 + i) as Tile));
+        }
+        // Only melds of the other suits can be made.
+        let meld_tiles = suit.tiles() | HONOR_TILES;
+        if let Some(&m) = melds.iter().find(|m| meld_tiles & (1 << m.tile()) != 0) {
+            return Err(BingpaiError::InvalidMeldForSichuan(m));
+        }
+    }
+
+    Ok(bingpai)
+}
+
+/// Returns an error if the hand or `config` has wildcards,
+/// for the calculations that do not support them.
+pub(crate) fn check_no_wildcards(num_wildcards: u8, config: &Config) -> Result<(), BingpaiError> {
+    if num_wildcards > 0 || config.wildcard_tile.is_some() {
+        return Err(BingpaiError::UnsupportedWildcards);
+    }
+    Ok(())
+}
+
+/// Moves the copies of the wildcard tile of `config` from the pure hand to the wildcards.
+///
+/// The hand must have been validated.
+pub(crate) fn split_wildcards(
+    tile_counts: &TileCounts,
+    num_wildcards: u8,
+    config: &Config,
+) -> (TileCounts, u8) {
+    let mut tile_counts = *tile_counts;
+    let mut num_wildcards = num_wildcards;
+    if let Some(tile) = config.wildcard_tile {
+        num_wildcards += tile_counts[usize::from(tile)];
+        tile_counts[usize::from(tile)] = 0;
+    }
+    (tile_counts, num_wildcards)
+}
+
+/// Fills the missing tiles of the result for the hand without the wildcards with the wildcards.
+///
+/// Since the wildcards count in the number of required melds,
+/// at least `num_wildcards` tiles are missing in any winning hand.
+/// Thus the best winning hands are the same, and each wildcard saves one draw.
+pub(crate) fn substitute_wildcards(
+    analysis: Analysis,
+    num_wildcards: u8,
+    config: &Config,
+) -> Analysis {
+    if analysis.replacement_number == u8::MAX {
+        return analysis;
+    }
+
+    let replacement_number = analysis.replacement_number - num_wildcards;
+    let necessary_tiles = match config.wildcard_tile {
+        _ if replacement_number == 0 => 0,
+        // Drawing a wildcard fills any missing tile.
+        Some(tile) => analysis.necessary_tiles | (1 << tile),
+        None => analysis.necessary_tiles,
+    };
+    Analysis {
+        replacement_number,
+        necessary_tiles,
+        unnecessary_tiles: analysis.unnecessary_tiles,
+    }
+}
+
+/// Calculates the result of each winning form under `config`.
+///
+/// The results are in the order of [`FORMS`], and [`None`] for the forms not selected.
+pub(crate) fn analyze_forms(
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    num_wildcards: u8,
+    config: &Config,
+) -> Result<[Option<Analysis>; FORMS.len()], BingpaiError> {
+    let num_required_mianzi =
+        validate(tile_counts, melds, num_wildcards, config)?.num_required_bingpai_mianzi();
+    let (tile_counts, num_wildcards) = split_wildcards(tile_counts, num_wildcards, config);
+    let tile_counts = &tile_counts;
+    let bingpai = Bingpai::from_validated(tile_counts, num_required_mianzi);
+    let allowed_tiles = allowed_tiles(config);
+
+    Ok(FORMS.map(|form| {
+        config.forms.contains(form).then(|| {
+            let analysis = match form {
+                // Only melds and a pair can make a hand of 16 tiles.
+                WinningForms::QIDUIZI
+                | WinningForms::SHISANYAO
+                | WinningForms::QUANBUKAO
+                | WinningForms::QIXINGBUKAO
+                | WinningForms::ZUHELONG
+                    if config.sixteen_tiles =>
+                {
+                    Analysis::UNREACHABLE
+                }
+                WinningForms::QIDUIZI => {
+                    let quads_as_two_pairs = config.qiduizi_quads_as_two_pairs;
+                    analyze_qiduizi(
+                        tile_counts,
+                        num_required_mianzi,
+                        allowed_tiles,
+                        quads_as_two_pairs,
+                    )
+                }
+                WinningForms::SHISANYAO => analyze_shisanyao(&bingpai, allowed_tiles),
+                WinningForms::QUANBUKAO | WinningForms::QIXINGBUKAO | WinningForms::ZUHELONG => {
+                    bukao::analyze(form, tile_counts, num_required_mianzi, allowed_tiles)
+                }
+                _ => {
+                    let rules = BlockRules::with_form(form, config);
+                    analyze_standard(tile_counts, num_required_mianzi, &rules)
+                }
+            };
+            substitute_wildcards(analysis, num_wildcards, config)
+        })
+    }))
+}
+
+/// Calculates the result of the selected winning forms under `config`.
+pub(crate) fn analyze(
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    num_wildcards: u8,
+    config: &Config,
+) -> Result<Analysis, BingpaiError> {
+    let mut analysis = Analysis::UNREACHABLE;
+    for result in analyze_forms(tile_counts, melds, num_wildcards, config)?
+        .into_iter()
+        .flatten()
+    {
+        analysis.update_min(result);
+    }
+    Ok(analysis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qiduizi;
+    use crate::rng::SplitMix64;
+    use crate::standard;
+    use crate::test_utils::{FromTileCode, random_hand};
+
+    #[test]
+    fn analyze_standard_matches_tables() {
+        let rules = BlockRules::new(ALL_TILES);
+        let mut rng = SplitMix64(1);
+        for num_tiles in [1, 2, 4, 5, 7, 8, 10, 11, 13, 14] {
+            for _ in 0..200 {
+                let tile_counts = random_hand(&mut rng, num_tiles, ALL_TILES);
+                let bingpai = Bingpai::new(&tile_counts).unwrap();
+                let (r, n, u) = standard::analyze(&bingpai);
+                let analysis =
+                    analyze_standard(&tile_counts, bingpai.num_required_bingpai_mianzi(), &rules);
+                assert_eq!(analysis.replacement_number, r, "{tile_counts:?}");
+                assert_eq!(analysis.necessary_tiles, n, "{tile_counts:?}");
+                assert_eq!(analysis.unnecessary_tiles, u, "{tile_counts:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_standard_matches_tables_3p() {
+        let allowed_tiles = ALL_TILES & !WANZI_2_TO_8;
+        let rules = BlockRules::new(allowed_tiles);
+        let mut rng = SplitMix64(2);
+        for num_tiles in [1, 2, 4, 5, 7, 8, 10, 11, 13, 14] {
+            for _ in 0..200 {
+                let tile_counts = random_hand(&mut rng, num_tiles, allowed_tiles);
+                let bingpai = Bingpai3p::new(&tile_counts).unwrap();
+                let (r, n, u) = standard::analyze_3p(&bingpai);
+                let analysis =
+                    analyze_standard(&tile_counts, bingpai.num_required_bingpai_mianzi(), &rules);
+                assert_eq!(analysis.replacement_number, r, "{tile_counts:?}");
+                assert_eq!(analysis.necessary_tiles, n, "{tile_counts:?}");
+                assert_eq!(analysis.unnecessary_tiles, u, "{tile_counts:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_qiduizi_matches_module() {
+        let mut rng = SplitMix64(3);
+        for quads_as_two_pairs in [false, true] {
+            for num_tiles in [13, 14] {
+                for _ in 0..500 {
+                    let tile_counts = random_hand(&mut rng, num_tiles, ALL_TILES);
+                    let bingpai = Bingpai::new(&tile_counts).unwrap();
+                    let (r, n) = qiduizi::calculate_necessary_tiles(&bingpai, quads_as_two_pairs);
+                    let (_, u) = qiduizi::calculate_unnecessary_tiles(&bingpai, quads_as_two_pairs);
+                    let r0 = qiduizi::calculate_replacement_number(&bingpai, quads_as_two_pairs);
+                    assert_eq!(r0, r, "{tile_counts:?}");
+                    let analysis = analyze_qiduizi(&tile_counts, 4, ALL_TILES, quads_as_two_pairs);
+                    assert_eq!(analysis.replacement_number, r, "{tile_counts:?}");
+                    assert_eq!(analysis.necessary_tiles, n, "{tile_counts:?}");
+                    assert_eq!(analysis.unnecessary_tiles, u, "{tile_counts:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_qiduizi_matches_module_3p() {
+        let allowed_tiles = ALL_TILES & !WANZI_2_TO_8;
+        let mut rng = SplitMix64(12);
+        for quads_as_two_pairs in [false, true] {
+            for num_tiles in [13, 14] {
+                for _ in 0..500 {
+                    let tile_counts = random_hand(&mut rng, num_tiles, allowed_tiles);
+                    let bingpai = Bingpai3p::new(&tile_counts).unwrap();
+                    let (r, n) =
+                        qiduizi::calculate_necessary_tiles_3p(&bingpai, quads_as_two_pairs);
+                    let (_, u) =
+                        qiduizi::calculate_unnecessary_tiles_3p(&bingpai, quads_as_two_pairs);
+                    let analysis =
+                        analyze_qiduizi(&tile_counts, 4, allowed_tiles, quads_as_two_pairs);
+                    assert_eq!(analysis.replacement_number, r, "{tile_counts:?}");
+                    assert_eq!(analysis.necessary_tiles, n, "{tile_counts:?}");
+                    assert_eq!(analysis.unnecessary_tiles, u, "{tile_counts:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_standard_duiduihe_matches_brute_force() {
+        let rules = BlockRules::with_form(WinningForms::DUIDUIHE, &Config::default());
+        let mut rng = SplitMix64(4);
+        for num_tiles in [1, 2, 4, 5, 7, 8, 10, 11, 13, 14, 16, 17] {
+            for _ in 0..200 {
+                let tile_counts = random_hand(&mut rng, num_tiles, ALL_TILES);
+                let num_required_mianzi = (num_tiles / 3) as u8;
+                // The pair and the cheapest triplets of the other tiles.
+                let expected = (0..34)
+                    .map(|pair| {
+                        let mut costs: [u8; 33] = [0; 33];
+                        let others = (0..34).filter(|&i| i != pair);
+                        for (cost, i) in costs.iter_mut().zip(others) {
+                            *cost = 3u8.saturating_sub(tile_counts[i]);
+                        }
+                        costs.sort_unstable();
+                        let num_kezi = usize::from(num_required_mianzi);
+                        2u8.saturating_sub(tile_counts[pair]) + costs[..num_kezi].iter().sum::<u8>()
+                    })
+                    .min()
+                    .unwrap();
+                let analysis = analyze_standard(&tile_counts, num_required_mianzi, &rules);
+                assert_eq!(analysis.replacement_number, expected, "{tile_counts:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_kezi_only_matches_suits() {
+        let config = Config {
+            allowed_tiles: ALL_TILES & !TileFlags::from_code("9m"),
+            pair_tiles: TileFlags::from_code("258m258p258s1234567z"),
+            ..Default::default()
+        };
+        let rules = BlockRules::with_form(WinningForms::DUIDUIHE, &config);
+        let base = TileCounts::from_code("111p");
+        let mut rng = SplitMix64(11);
+        for num_tiles in [1, 2, 4, 5, 7, 8, 10, 11, 13, 14, 16, 17] {
+            for _ in 0..100 {
+                let tile_counts = random_hand(&mut rng, num_tiles, ALL_TILES);
+                let num_mianzi = num_tiles / 3;
+                for base in [[0; NUM_TILE_INDEX], base] {
+                    let expected = [(0, 9), (9, 18), (18, 27), (27, 34)]
+                        .into_iter()
+                        .map(|(start, end)| {
+                            let (tile_counts, base) = (&tile_counts[start..end], &base[start..end]);
+                            analyze_suit(tile_counts, base, start, &rules, num_mianzi)
+                        })
+                        .reduce(|lhs, rhs| combine(&lhs, &rhs))
+                        .unwrap();
+                    let table = analyze_kezi_only(&tile_counts, &base, num_mianzi, &rules);
+                    for p in 0..2 {
+                        let (table, expected) = (&table[p], &expected[p]);
+                        assert_eq!(
+                            table[..=num_mianzi],
+                            expected[..=num_mianzi],
+                            "{tile_counts:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_standard_pinghe_matches_brute_force() {
+        // Not the dragons (三元牌)
+        let config = Config {
+            pair_tiles: ALL_TILES & !TileFlags::from_code("567z"),
+            ..Default::default()
+        };
+        let rules = BlockRules::with_form(WinningForms::PINGHE, &config);
+        let starts: [usize; 21] = core::array::from_fn(|i| i / 7 * 9 + i % 7);
+        let mut rng = SplitMix64(5);
+        for num_tiles in [13, 14] {
+            for _ in 0..50 {
+                let tile_counts = random_hand(&mut rng, num_tiles, ALL_TILES);
+                let mut expected = u8::MAX;
+                for a in 0..21 {
+                    for b in a..21 {
+                        for c in b..21 {
+                            for d in c..21 {
+                                let mut target = [0u8; 34];
+                                for s in [a, b, c, d] {
+                                    for o in 0..3 {
+                                        target[starts[s] + o] += 1;
+                                    }
+                                }
+                                if target.iter().any(|&n| n > 4) {
+                                    continue;
+                                }
+                                let cost: u8 = target
+                                    .iter()
+                                    .zip(&tile_counts)
+                                    .map(|(&w, &h)| w.saturating_sub(h))
+                                    .sum();
+                                for pair in 0..34 {
+                                    let (w, h) = (target[pair], tile_counts[pair]);
+                                    if config.pair_tiles & (1 << pair) == 0 || w + 2 > 4 {
+                                        continue;
+                                    }
+                                    let pair_cost = (w + 2).saturating_sub(h) - w.saturating_sub(h);
+                                    expected = expected.min(cost + pair_cost);
+                                }
+                            }
+                        }
+                    }
+                }
+                let analysis = analyze_standard(&tile_counts, 4, &rules);
+                assert_eq!(analysis.replacement_number, expected, "{tile_counts:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_standard_pair_tiles_matches_fixed_pairs() {
+        // 二五八将
+        let config = Config {
+            pair_tiles: TileFlags::from_code("258m258p258s"),
+            ..Default::default()
+        };
+        let rules = BlockRules::with_form(WinningForms::STANDARD, &config);
+        let any_pair = BlockRules::new(ALL_TILES);
+        let mut rng = SplitMix64(10);
+        for num_tiles in [1, 2, 4, 5, 7, 8, 10, 11, 13, 14] {
+            for _ in 0..100 {
+                let tile_counts = random_hand(&mut rng, num_tiles, ALL_TILES);
+                let num_required_mianzi = (num_tiles / 3) as u8;
+                // The best of the hands with each of the allowed pairs.
+                let mut expected = Analysis::UNREACHABLE;
+                for pair in (0..NUM_TILE_INDEX).filter(|&i| config.pair_tiles & (1 << i) != 0) {
+                    let mut base = [0; NUM_TILE_INDEX];
+                    base[pair] = 2;
+                    expected.update_min(analyze_standard_with_base(
+                        &tile_counts,
+                        &base,
+                        num_required_mianzi,
+                        0,
+                        &any_pair,
+                    ));
+                }
+                let analysis = analyze_standard(&tile_counts, num_required_mianzi, &rules);
+                assert_eq!(analysis, expected, "{tile_counts:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_standard_pair_tiles() {
+        let config = Config {
+            pair_tiles: TileFlags::from_code("258m258p258s"),
+            ..Default::default()
+        };
+        let rules = BlockRules::with_form(WinningForms::STANDARD, &config);
+
+        // 123m456p789s222z + 11z as the pair is not allowed.
+        let tile_counts = TileCounts::from_code("123m456p789s11222z");
+        let analysis = analyze_standard(&tile_counts, 4, &rules);
+        assert_eq!(analysis.replacement_number, 2);
+
+        let tile_counts = TileCounts::from_code("123m456p789s2225m");
+        let analysis = analyze_standard(&tile_counts, 4, &rules);
+        assert_eq!(analysis.replacement_number, 1);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("5m"));
+    }
+
+    #[test]
+    fn analyze_standard_quandai_matches_brute_force() {
+        let mut rng = SplitMix64(6);
+        for form in [WinningForms::HUNQUANDAI, WinningForms::CHUNQUANDAI] {
+            let rules = BlockRules::with_form(form, &Config::default());
+            // The sequences starting at 1 and 7, and the triplets of the terminals and honors.
+            let mut blocks = [(0, 0); 19];
+            for (i, block) in blocks.iter_mut().enumerate() {
+                *block = match i {
+                    0..6 => (i / 2 * 9 + i % 2 * 6, 1),
+                    _ => ([0, 8, 9, 17, 18, 26, 27, 28, 29, 30, 31, 32, 33][i - 6], 0),
+                };
+            }
+            let is_allowed = |&(tile, is_shunzi): &(usize, usize)| {
+                let flags = if is_shunzi == 1 {
+                    rules.shunzi
+                } else {
+                    rules.kezi
+                };
+                flags & (1 << tile) != 0
+            };
+            for num_tiles in [13, 14] {
+                for _ in 0..20 {
+                    let tile_counts = random_hand(&mut rng, num_tiles, ALL_TILES);
+                    let mut expected = u8::MAX;
+                    for a in 0..19 {
+                        for b in a..19 {
+                            for c in b..19 {
+                                for d in c..19 {
+                                    let melds = [a, b, c, d].map(|i| blocks[i]);
+                                    if !melds.iter().all(is_allowed) {
+                                        continue;
+                                    }
+                                    let mut target = [0u8; 34];
+                                    for (tile, is_shunzi) in melds {
+                                        for o in 0..3 {
+                                            target[tile + o * is_shunzi] += 1;
+                                        }
+                                    }
+                                    if target.iter().any(|&n| n > 4) {
+                                        continue;
+                                    }
+                                    let cost: u8 = target
+                                        .iter()
+                                        .zip(&tile_counts)
+                                        .map(|(&w, &h)| w.saturating_sub(h))
+                                        .sum();
+                                    for pair in 0..34 {
+                                        let (w, h) = (target[pair], tile_counts[pair]);
+                                        if rules.quetou & (1 << pair) == 0 || w + 2 > 4 {
+                                            continue;
+                                        }
+                                        let pair_cost =
+                                            (w + 2).saturating_sub(h) - w.saturating_sub(h);
+                                        expected = expected.min(cost + pair_cost);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    let analysis = analyze_standard(&tile_counts, 4, &rules);
+                    assert_eq!(analysis.replacement_number, expected, "{tile_counts:?}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_standard_quandai() {
+        let tile_counts = TileCounts::from_code("123789m123p99s11z");
+        let config = Config::default();
+
+        let rules = BlockRules::with_form(WinningForms::HUNQUANDAI, &config);
+        let analysis = analyze_standard(&tile_counts, 4, &rules);
+        assert_eq!(analysis.replacement_number, 1);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("9s1z"));
+
+        let rules = BlockRules::with_form(WinningForms::CHUNQUANDAI, &config);
+        let analysis = analyze_standard(&tile_counts, 4, &rules);
+        // 123789m123p999s + 2 draws for the pair
+        assert_eq!(analysis.replacement_number, 3);
+        assert_eq!(analysis.unnecessary_tiles, TileFlags::from_code("1z"));
+    }
+
+    #[test]
+    fn analyze_standard_tanyao() {
+        // 123m456p789s11222z
+        let tile_counts = TileCounts::from_code("123m456p789s11222z");
+        let rules = BlockRules::new(TileFlags::from_code("2345678m2345678p2345678s"));
+        let analysis = analyze_standard(&tile_counts, 4, &rules);
+        // 234m456p678s + 3 draws for a meld and 2 draws for the pair
+        assert_eq!(analysis.replacement_number, 7);
+        assert_eq!(analysis.unnecessary_tiles, TileFlags::from_code("1m9s12z"));
+    }
+
+    #[test]
+    fn analyze_standard_no_allowed_tiles() {
+        let tile_counts = TileCounts::from_code("123m456p789s11222z");
+        let analysis = analyze_standard(&tile_counts, 4, &BlockRules::new(0));
+        assert_eq!(analysis, Analysis::UNREACHABLE);
+    }
+
+    #[test]
+    fn analyze_standard_five_mianzi() {
+        let rules = BlockRules::new(ALL_TILES);
+
+        let tile_counts = TileCounts::from_code("123456789m123456p11z");
+        let analysis = analyze_standard(&tile_counts, 5, &rules);
+        assert_eq!(analysis.replacement_number, 0);
+
+        let tile_counts = TileCounts::from_code("123456789m12345p11z");
+        let analysis = analyze_standard(&tile_counts, 5, &rules);
+        assert_eq!(analysis.replacement_number, 1);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("36p"));
+        assert_eq!(analysis.unnecessary_tiles, 0);
+    }
+
+    #[test]
+    fn analyze_forms_sixteen_tiles() {
+        let tile_counts = TileCounts::from_code("1133m5577p99s1122z");
+        let config = Config {
+            sixteen_tiles: true,
+            ..Default::default()
+        };
+        let results = analyze_forms(&tile_counts, &[], 0, &config).unwrap();
+        assert_eq!(results[0].unwrap().replacement_number, 4);
+        assert_eq!(results[1], Some(Analysis::UNREACHABLE));
+        assert_eq!(results[2], Some(Analysis::UNREACHABLE));
+    }
+
+    #[test]
+    fn analyze_wildcards_matches_brute_force() {
+        let config = Config::default();
+        let mut rng = SplitMix64(9);
+        for num_tiles in [1, 3, 4, 6, 7, 9, 10, 12, 13] {
+            for _ in 0..20 {
+                let tile_counts = random_hand(&mut rng, num_tiles, ALL_TILES);
+                let analysis = analyze(&tile_counts, &[], 1, &config).unwrap();
+
+                // The wildcard stands for the best tile.
+                let mut expected = u8::MAX;
+                for i in 0..NUM_TILE_INDEX {
+                    if tile_counts[i] == 4 {
+                        continue;
+                    }
+                    let mut substituted = tile_counts;
+                    substituted[i] += 1;
+                    let r = analyze(&substituted, &[], 0, &config).unwrap();
+                    expected = expected.min(r.replacement_number);
+                }
+                assert_eq!(analysis.replacement_number, expected, "{tile_counts:?}");
+
+                if (num_tiles + 1) % 3 != 1 {
+                    continue;
+                }
+                // The necessary tiles reduce the replacement number when drawn.
+                let mut necessary_tiles = 0;
+                for i in 0..NUM_TILE_INDEX {
+                    if tile_counts[i] == 4 {
+                        continue;
+                    }
+                    let mut drawn = tile_counts;
+                    drawn[i] += 1;
+                    let r = analyze(&drawn, &[], 1, &config).unwrap();
+                    if r.replacement_number < analysis.replacement_number {
+                        necessary_tiles |= 1 << i;
+                    }
+                }
+                assert_eq!(analysis.necessary_tiles, necessary_tiles, "{tile_counts:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_wildcard_tile() {
+        // 123m456p789s1z22z + 2 copies of 5z
+        let tile_counts = TileCounts::from_code("123m456p789s12255z");
+        let config = Config {
+            wildcard_tile: Some(31),
+            ..Default::default()
+        };
+        let analysis = analyze(&tile_counts, &[], 0, &config).unwrap();
+        let without_wildcard_tiles = TileCounts::from_code("123m456p789s122z");
+        let expected = analyze(&without_wildcard_tiles, &[], 2, &config).unwrap();
+        assert_eq!(analysis, expected);
+        // 123m456p789s 1z5z5z 22z
+        assert_eq!(analysis.replacement_number, 0);
+        assert_eq!(analysis.necessary_tiles, 0);
+
+        let tile_counts = TileCounts::from_code("123m456p789s1225z");
+        let analysis = analyze(&tile_counts, &[], 0, &config).unwrap();
+        assert_eq!(analysis.replacement_number, 1);
+        // 1z or 2z, or another wildcard
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("125z"));
+        assert_eq!(analysis.unnecessary_tiles, 0);
+    }
+
+    #[test]
+    fn analyze_qiduizi_quads_matches_brute_force() {
+        let mut rng = SplitMix64(7);
+        for num_tiles in [13, 14] {
+            for _ in 0..500 {
+                let tile_counts = random_hand(&mut rng, num_tiles, ALL_TILES);
+                // The best results indexed by the number of pairs, choosing 0, 1 or 2 pairs of each kind.
+                let mut states = [Analysis::UNREACHABLE; 8];
+                states[0] = Analysis {
+                    replacement_number: 0,
+                    ..Analysis::UNREACHABLE
+                };
+                for (i, &count) in tile_counts.iter().enumerate() {
+                    let mut next = [Analysis::UNREACHABLE; 8];
+                    for (n, state) in states.iter().enumerate() {
+                        if state.replacement_number == u8::MAX {
+                            continue;
+                        }
+                        for k in 0..=2.min(7 - n) {
+                            let target = 2 * k as u8;
+                            let flag: TileFlags = 1 << i;
+                            next[n + k].update_min(Analysis {
+                                replacement_number: state.replacement_number
+                                    + target.saturating_sub(count),
+                                necessary_tiles: state.necessary_tiles
+                                    | if target > count { flag } else { 0 },
+                                unnecessary_tiles: state.unnecessary_tiles
+                                    | if count > target { flag } else { 0 },
+                            });
+                        }
+                    }
+                    states = next;
+                }
+                let analysis = analyze_qiduizi(&tile_counts, 4, ALL_TILES, true);
+                assert_eq!(analysis, states[7], "{tile_counts:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn analyze_qiduizi_quads() {
+        let tile_counts = TileCounts::from_code("1111m2233p4455s6z");
+        let analysis = analyze_qiduizi(&tile_counts, 4, ALL_TILES, false);
+        assert_eq!(analysis.replacement_number, 3);
+        assert_eq!(analysis.unnecessary_tiles, TileFlags::from_code("1m"));
+
+        let analysis = analyze_qiduizi(&tile_counts, 4, ALL_TILES, true);
+        assert_eq!(analysis.replacement_number, 1);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("6z"));
+        assert_eq!(analysis.unnecessary_tiles, 0);
+
+        // Four kinds can make seven pairs.
+        let tile_counts = TileCounts::from_code("11112222m33p4z");
+        let analysis = analyze_qiduizi(&tile_counts, 4, TileFlags::from_code("1234m"), true);
+        // 1111m2222m3333m44m, discarding 33p4z
+        assert_eq!(analysis.replacement_number, 6);
+        assert_eq!(analysis.unnecessary_tiles, TileFlags::from_code("3p4z"));
+    }
+
+    #[test]
+    fn analyze_qiduizi_too_few_allowed_kinds() {
+        let tile_counts = TileCounts::from_code("1122334455667z");
+        let analysis = analyze_qiduizi(&tile_counts, 4, TileFlags::from_code("123456z"), false);
+        assert_eq!(analysis, Analysis::UNREACHABLE);
+    }
+
+    #[test]
+    fn analyze_shisanyao_requires_all_yaojiu() {
+        let tile_counts = TileCounts::from_code("19m19p19s1234567z");
+        let bingpai = Bingpai::new(&tile_counts).unwrap();
+        let analysis = analyze_shisanyao(&bingpai, ALL_TILES & !TileFlags::from_code("7z"));
+        assert_eq!(analysis, Analysis::UNREACHABLE);
+        let analysis = analyze_shisanyao(&bingpai, YAOJIU_TILES);
+        assert_eq!(analysis.replacement_number, 1);
+    }
+}
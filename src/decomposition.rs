@@ -0,0 +1,1186 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use super::bukao;
+use super::constrained;
+use super::qiduizi;
+use super::shisanyao;
+use super::standard;
+use crate::bingpai::{Bingpai, Bingpai3p, BingpaiError};
+use crate::config::{Config, PlayerCount, WinningForms};
+use crate::constrained::{BlockRules, FORMS};
+use crate::hand::{Hand, Meld};
+use crate::tile::{Tile, TileCounts, TileFlags};
+
+const MAX_NUM_BLOCKS: usize = 17;
+
+/// The kind of a block of tiles in a decomposition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockKind {
+    /// 刻子: A triplet (e.g., 111m).
+    Kezi,
+    /// 順子: A sequence (e.g., 123m).
+    Shunzi,
+    /// 雀頭: The pair of the hand (e.g., 11m).
+    Quetou,
+    /// 対子: A pair used as a partial block (e.g., 11m).
+    Duizi,
+    /// 両面: An open partial sequence (e.g., 23m).
+    Liangmian,
+    /// 嵌張: A closed partial sequence (e.g., 13m).
+    Kanzhang,
+    /// 辺張: An edge partial sequence (12m or 89m).
+    Bianzhang,
+    /// 么九牌: A single terminal or honor tile of thirteen orphans.
+    Yaojiu,
+    /// 不靠: A single tile of a knitted straight (组合龙) or an honor
+    /// of the knitted forms (全不靠, 七星不靠 and 组合龙).
+    Bukao,
+    /// 孤立牌: A floating tile that does not belong to any other block.
+    Gupai,
+}
+
+impl BlockKind {
+    /// Returns the offsets of the tiles of the block from its first tile.
+    #[inline]
+    pub(crate) fn offsets(self) -> &'static [usize] {
+        match self {
+            Self::Kezi => &[0, 0, 0],
+            Self::Shunzi => &[0, 1, 2],
+            Self::Quetou | Self::Duizi => &[0, 0],
+            Self::Liangmian | Self::Bianzhang => &[0, 1],
+            Self::Kanzhang => &[0, 2],
+            Self::Yaojiu | Self::Bukao | Self::Gupai => &[0],
+        }
+    }
+}
+
+/// A block of tiles in a decomposition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Block {
+    /// The kind of the block.
+    pub kind: BlockKind,
+    /// The first tile of the block.
+    pub tile: Tile,
+}
+
+impl Block {
+    const EMPTY: Self = Self {
+        kind: BlockKind::Gupai,
+        tile: 0,
+    };
+}
+
+/// A decomposition of a pure hand (純手牌) into blocks.
+///
+/// Every tile of the pure hand belongs to exactly one block.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Decomposition {
+    form: WinningForms,
+    blocks: [Block; MAX_NUM_BLOCKS],
+    num_blocks: u8,
+}
+
+impl Decomposition {
+    #[inline]
+    fn new(form: WinningForms) -> Self {
+        Self {
+            form,
+            blocks: [Block::EMPTY; MAX_NUM_BLOCKS],
+            num_blocks: 0,
+        }
+    }
+
+    #[inline]
+    fn push(&mut self, block: Block) {
+        self.blocks[self.num_blocks as usize] = block;
+        self.num_blocks += 1;
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Option<Block> {
+        if self.num_blocks == 0 {
+            return None;
+        }
+        self.num_blocks -= 1;
+        let block = self.blocks[self.num_blocks as usize];
+        // Keep the unused blocks empty so that the derived traits compare only the used blocks.
+        self.blocks[self.num_blocks as usize] = Block::EMPTY;
+        Some(block)
+    }
+
+    #[inline]
+    fn last(&self) -> Option<&Block> {
+        self.blocks().last()
+    }
+
+    /// Returns the winning form (和了形) that the decomposition belongs to.
+    ///
+    /// Exactly one of the winning forms, e.g., [`WinningForms::STANDARD`].
+    #[inline]
+    #[must_use]
+    pub fn form(&self) -> WinningForms {
+        self.form
+    }
+
+    /// Returns the blocks in the order of their first tiles.
+    #[inline]
+    #[must_use]
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks[..self.num_blocks as usize]
+    }
+}
+
+/// The decompositions of a winning form.
+#[derive(Debug, Clone)]
+enum FormDecompositions {
+    /// The forms made of melds and a pair, which can have many decompositions.
+    Search(StandardSearch),
+    /// The forms that have exactly one decomposition.
+    Single(Option<Decomposition>),
+    /// The knitted forms, which have the decompositions for each knitted straight.
+    Knitted(KnittedSearch),
+}
+
+impl Iterator for FormDecompositions {
+    type Item = Decomposition;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Self::Search(search) => search.next(),
+            Self::Single(decomposition) => decomposition.take(),
+            Self::Knitted(search) => search.next(),
+        }
+    }
+}
+
+/// An iterator over the optimal decompositions of a hand.
+///
+/// Created by [`decompose`] and [`decompose_with_config`].
+#[derive(Debug, Clone)]
+pub struct Decompositions {
+    replacement_number: u8,
+    // The decompositions of each form in the order of `FORMS`.
+    forms: [Option<FormDecompositions>; FORMS.len()],
+}
+
+impl Decompositions {
+    /// Returns an iterator that yields no decompositions.
+    #[inline]
+    pub(crate) fn empty(replacement_number: u8) -> Self {
+        Self {
+            replacement_number,
+            forms: Default::default(),
+        }
+    }
+
+    /// Returns the replacement number (= xiàngtīng number + 1) achieved by the decompositions.
+    #[inline]
+    #[must_use]
+    pub fn replacement_number(&self) -> u8 {
+        self.replacement_number
+    }
+}
+
+impl Iterator for Decompositions {
+    type Item = Decomposition;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for form in &mut self.forms {
+            let Some(decompositions) = form else {
+                continue;
+            };
+            match decompositions.next() {
+                Some(decomposition) => return Some(decomposition),
+                None => *form = None,
+            }
+        }
+        None
+    }
+}
+
+/// Enumerates the decompositions of a given hand that achieve its replacement number.
+///
+/// The decompositions of the standard form (一般形) consist of melds (面子), at most one pair (雀頭),
+/// partial blocks (搭子) and floating tiles (孤立牌).
+/// The number of melds and partial blocks does not exceed the number of melds required
+/// to complete the hand.
+/// The decompositions of seven pairs (七対子) consist of pairs (対子) and floating tiles,
+/// and those of thirteen orphans (国士無双) consist of terminal and honor tiles (么九牌),
+/// at most one pair and floating tiles.
+/// The decompositions of all triplets (対々和) are those of the standard form without sequences.
+/// The decompositions of the knitted forms (全不靠, 七星不靠 and 组合龙) consist of
+/// the tiles of a knitted straight and honors (不靠) and floating tiles,
+/// and those of a knitted straight (组合龙) also have the blocks of the standard form.
+///
+/// The decompositions of the standard form are counted as follows:
+/// `2 * (required melds) + 1 - 2 * (melds) - (partial blocks) - (pair)`.
+/// This count may be lower than the replacement number
+/// when the hand holds all copies of the tiles needed for a partial block.
+/// In that case, the decompositions whose count equals the replacement number are enumerated.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `player_count` - A reference to the number of players.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{BlockKind, PlayerCount, WinningForms, decompose};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 123m456p789s1122z
+/// let hand: [u8; 34] = [
+///     1, 1, 1, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 1, 1, 1, // s
+///     2, 2, 0, 0, 0, 0, 0, // z
+/// ];
+///
+/// let mut decompositions = decompose(&hand, &PlayerCount::Four)?;
+/// assert_eq!(decompositions.replacement_number(), 1u8);
+///
+/// // 123m 456p 789s 11z (pair) 22z
+/// let decomposition = decompositions.next().unwrap();
+/// assert_eq!(decomposition.form(), WinningForms::STANDARD);
+/// let kinds = decomposition.blocks().iter().map(|b| b.kind);
+/// assert!(kinds.eq([
+///     BlockKind::Shunzi,
+///     BlockKind::Shunzi,
+///     BlockKind::Shunzi,
+///     BlockKind::Quetou,
+///     BlockKind::Duizi,
+/// ]));
+///
+/// // 123m 456p 789s 11z 22z (pair)
+/// assert!(decompositions.next().is_some());
+/// assert!(decompositions.next().is_none());
+/// # Ok(())
+/// # }
+/// ```
+#[inline]
+pub fn decompose(
+    bingpai: &impl Hand,
+    player_count: &PlayerCount,
+) -> Result<Decompositions, BingpaiError> {
+    decompose_with_config(bingpai, &player_count.clone().into())
+}
+
+/// Enumerates the decompositions of a given hand that achieve its replacement number
+/// under the given rules.
+///
+/// Only the winning forms selected in `config` are considered.
+/// See [`decompose`] for the blocks of each form.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid or no selected winning form can be completed with the hand.
+pub fn decompose_with_config(
+    bingpai: &impl Hand,
+    config: &Config,
+) -> Result<Decompositions, BingpaiError> {
+    constrained::check_no_wildcards(bingpai.num_wildcards(), config)?;
+    let tile_counts = bingpai.bingpai();
+    let melds = bingpai.melds();
+    let (bingpai, replacement_numbers) = if config.uses_tables() {
+        calculate_replacement_numbers(tile_counts, melds, config)?
+    } else {
+        let results = constrained::analyze_forms(tile_counts, melds, 0, config)?;
+        (
+            constrained::validate(tile_counts, melds, 0, config)?,
+            results.map(|result| result.map(|a| a.replacement_number)),
+        )
+    };
+
+    let replacement_number = replacement_numbers
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(u8::MAX);
+    if replacement_number == u8::MAX {
+        return Err(BingpaiError::NoReachableForm);
+    }
+
+    let allowed_tiles = constrained::allowed_tiles(config);
+    let forms = core::array::from_fn(|i| {
+        let form = FORMS[i];
+        (replacement_numbers[i] == Some(replacement_number)).then(|| match form {
+            WinningForms::QIDUIZI => {
+                let quads_as_two_pairs = config.qiduizi_quads_as_two_pairs;
+                let decomposition =
+                    decompose_qiduizi(tile_counts, allowed_tiles, quads_as_two_pairs);
+                FormDecompositions::Single(Some(decomposition))
+            }
+            WinningForms::SHISANYAO => {
+                FormDecompositions::Single(Some(decompose_shisanyao(tile_counts)))
+            }
+            WinningForms::QUANBUKAO | WinningForms::QIXINGBUKAO | WinningForms::ZUHELONG => {
+                FormDecompositions::Knitted(KnittedSearch::new(
+                    tile_counts,
+                    bingpai.num_required_bingpai_mianzi(),
+                    replacement_number,
+                    form,
+                    allowed_tiles,
+                ))
+            }
+            _ => FormDecompositions::Search(StandardSearch::new(
+                tile_counts,
+                bingpai.num_required_bingpai_mianzi(),
+                replacement_number,
+                form,
+                BlockRules::with_form(form, config),
+            )),
+        })
+    });
+
+    Ok(Decompositions {
+        replacement_number,
+        forms,
+    })
+}
+
+/// Calculates the replacement number of each winning form with the precomputed tables.
+fn calculate_replacement_numbers<'a>(
+    tile_counts: &'a TileCounts,
+    melds: &[Meld],
+    config: &Config,
+) -> Result<(Bingpai<'a>, [Option<u8>; FORMS.len()]), BingpaiError> {
+    let (bingpai, r0) = match config.player_count {
+        PlayerCount::Four => {
+            let bingpai = Bingpai::with_melds(tile_counts, melds)?;
+            let r0 = standard::calculate_replacement_number(&bingpai);
+            (bingpai, r0)
+        }
+        PlayerCount::Three => {
+            let bingpai_3p = Bingpai3p::with_melds(tile_counts, melds)?;
+            let r0 = standard::calculate_replacement_number_3p(&bingpai_3p);
+            (bingpai_3p.into(), r0)
+        }
+    };
+
+    let forms = config.forms;
+    let r0 = forms.contains(WinningForms::STANDARD).then_some(r0);
+    let r1 = forms.contains(WinningForms::QIDUIZI).then(|| {
+        qiduizi::calculate_replacement_number(&bingpai, config.qiduizi_quads_as_two_pairs)
+    });
+    let r2 = forms
+        .contains(WinningForms::SHISANYAO)
+        .then(|| shisanyao::calculate_replacement_number(&bingpai));
+
+    // The other forms are never selected when the tables are used.
+    let mut replacement_numbers = [None; FORMS.len()];
+    replacement_numbers[..3].copy_from_slice(&[r0, r1, r2]);
+    Ok((bingpai, replacement_numbers))
+}
+
+fn decompose_qiduizi(
+    tile_counts: &TileCounts,
+    allowed_tiles: TileFlags,
+    quads_as_two_pairs: bool,
+) -> Decomposition {
+    let mut decomposition = Decomposition::new(WinningForms::QIDUIZI);
+    for (i, &c) in tile_counts.iter().enumerate() {
+        let tile = i as Tile;
+        let num_duizi = match c {
+            _ if allowed_tiles & (1 << i) == 0 => 0,
+            4 if quads_as_two_pairs => 2,
+            2.. => 1,
+            _ => 0,
+        };
+        for _ in 0..num_duizi {
+            decomposition.push(Block {
+                kind: BlockKind::Duizi,
+                tile,
+            });
+        }
+        for _ in 0..(c - 2 * num_duizi) {
+            decomposition.push(Block {
+                kind: BlockKind::Gupai,
+                tile,
+            });
+        }
+    }
+    decomposition
+}
+
+#[inline]
+fn is_yaojiu(tile: usize) -> bool {
+    tile >= 27 || tile % 9 == 0 || tile % 9 == 8
+}
+
+fn decompose_shisanyao(tile_counts: &TileCounts) -> Decomposition {
+    let mut decomposition = Decomposition::new(WinningForms::SHISANYAO);
+    let mut has_quetou = false;
+    for (i, &c) in tile_counts.iter().enumerate() {
+        let tile = i as Tile;
+        let mut c = c;
+        if is_yaojiu(i) && c > 0 {
+            let kind = if !has_quetou && c >= 2 {
+                has_quetou = true;
+                c -= 2;
+                BlockKind::Quetou
+            } else {
+                c -= 1;
+                BlockKind::Yaojiu
+            };
+            decomposition.push(Block { kind, tile });
+        }
+        for _ in 0..c {
+            decomposition.push(Block {
+                kind: BlockKind::Gupai,
+                tile,
+            });
+        }
+    }
+    decomposition
+}
+
+/// The decompositions of a knitted form for each knitted straight (组合龙) that achieves the target.
+#[derive(Debug, Clone)]
+struct KnittedSearch {
+    tile_counts: TileCounts,
+    num_required_mianzi: u8,
+    target: u8,
+    form: WinningForms,
+    allowed_tiles: TileFlags,
+    next_knitted: usize,
+    // The knitted tiles (不靠) of the decompositions already visited.
+    visited: [TileFlags; bukao::KNITTED_STRAIGHTS.len()],
+    num_visited: usize,
+    // The knitted tiles of the current decompositions of a knitted straight
+    // and the search over the other tiles.
+    current: Option<(TileFlags, StandardSearch)>,
+}
+
+impl KnittedSearch {
+    fn new(
+        tile_counts: &TileCounts,
+        num_required_mianzi: u8,
+        target: u8,
+        form: WinningForms,
+        allowed_tiles: TileFlags,
+    ) -> Self {
+        Self {
+            tile_counts: *tile_counts,
+            num_required_mianzi,
+            target,
+            form,
+            allowed_tiles,
+            next_knitted: 0,
+            visited: [0; bukao::KNITTED_STRAIGHTS.len()],
+            num_visited: 0,
+            current: None,
+        }
+    }
+
+    /// Returns the decomposition of the knitted tiles `bukao` and the floating tiles.
+    fn decompose_bukao(&self, bukao: TileFlags) -> Decomposition {
+        let mut decomposition = Decomposition::new(self.form);
+        for (i, &c) in self.tile_counts.iter().enumerate() {
+            let tile = i as Tile;
+            let is_bukao = bukao & (1 << i) != 0;
+            if is_bukao {
+                decomposition.push(Block {
+                    kind: BlockKind::Bukao,
+                    tile,
+                });
+            }
+            for _ in 0..(c - u8::from(is_bukao)) {
+                decomposition.push(Block {
+                    kind: BlockKind::Gupai,
+                    tile,
+                });
+            }
+        }
+        decomposition
+    }
+}
+
+impl Iterator for KnittedSearch {
+    type Item = Decomposition;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((bukao, search)) = &mut self.current {
+                if let Some(mut decomposition) = search.next() {
+                    for i in (0..self.tile_counts.len()).filter(|&i| *bukao & (1 << i) != 0) {
+                        decomposition.push(Block {
+                            kind: BlockKind::Bukao,
+                            tile: i as Tile,
+                        });
+                    }
+                    let num_blocks = decomposition.num_blocks as usize;
+                    decomposition.blocks[..num_blocks].sort_unstable_by_key(|b| b.tile);
+                    return Some(decomposition);
+                }
+                self.current = None;
+            }
+
+            let knitted = *bukao::KNITTED_STRAIGHTS.get(self.next_knitted)?;
+            self.next_knitted += 1;
+            let analysis = bukao::analyze_with(
+                self.form,
+                &self.tile_counts,
+                self.num_required_mianzi,
+                knitted,
+                self.allowed_tiles,
+            );
+            if analysis.replacement_number != self.target {
+                continue;
+            }
+
+            let held = self
+                .tile_counts
+                .iter()
+                .enumerate()
+                .filter(|&(_, &c)| c > 0)
+                .fold(0, |flags, (i, _)| flags | (1 << i));
+            let bukao = match self.form {
+                WinningForms::ZUHELONG => held & knitted,
+                _ => held & bukao::bukao_tiles(knitted, self.allowed_tiles),
+            };
+            // Different knitted straights can lead to the same decompositions.
+            if self.visited[..self.num_visited].contains(&bukao) {
+                continue;
+            }
+            self.visited[self.num_visited] = bukao;
+            self.num_visited += 1;
+
+            if self.form != WinningForms::ZUHELONG {
+                return Some(self.decompose_bukao(bukao));
+            }
+
+            // The other tiles make a meld and a pair.
+            let mut tile_counts = self.tile_counts;
+            for (i, count) in tile_counts.iter_mut().enumerate() {
+                *count -= ((bukao >> i) & 1) as u8;
+            }
+            let num_missing = (knitted & !held).count_ones() as u8;
+            let search = StandardSearch::new(
+                &tile_counts,
+                self.num_required_mianzi - 3,
+                self.target - num_missing,
+                self.form,
+                BlockRules::new(self.allowed_tiles),
+            );
+            self.current = Some((bukao, search));
+        }
+    }
+}
+
+// The kinds of the blocks in the order of the search.
+// Liangmian and Bianzhang share the same position.
+const OPTIONS: [BlockKind; 7] = [
+    BlockKind::Kezi,
+    BlockKind::Shunzi,
+    BlockKind::Quetou,
+    BlockKind::Duizi,
+    BlockKind::Liangmian,
+    BlockKind::Kanzhang,
+    BlockKind::Gupai,
+];
+
+#[inline]
+fn option_index(kind: BlockKind) -> usize {
+    match kind {
+        BlockKind::Bianzhang => 4,
+        _ => OPTIONS.iter().position(|&k| k == kind).unwrap(),
+    }
+}
+
+/// A depth-first search over the decompositions of the standard form.
+///
+/// The first remaining tile is always taken into a block,
+/// and the blocks starting at the same tile are taken in the order of [`OPTIONS`]
+/// so that each decomposition is visited exactly once.
+#[derive(Debug, Clone)]
+struct StandardSearch {
+    hand: TileCounts,
+    tile_counts: TileCounts,
+    num_remaining_tiles: u8,
+    num_required_mianzi: u8,
+    target: u8,
+    rules: BlockRules,
+    current: Decomposition,
+    num_mianzi: u8,
+    num_dazi: u8,
+    has_quetou: bool,
+    // The numbers of isolated tiles that can start a meld only, a pair only, and both.
+    num_seeds: [u8; 3],
+    next_option: usize,
+    is_finished: bool,
+}
+
+impl StandardSearch {
+    fn new(
+        tile_counts: &TileCounts,
+        num_required_mianzi: u8,
+        target: u8,
+        form: WinningForms,
+        rules: BlockRules,
+    ) -> Self {
+        Self {
+            hand: *tile_counts,
+            tile_counts: *tile_counts,
+            num_remaining_tiles: tile_counts.iter().sum(),
+            num_required_mianzi,
+            target,
+            rules,
+            current: Decomposition::new(form),
+            num_mianzi: 0,
+            num_dazi: 0,
+            has_quetou: false,
+            num_seeds: [0; 3],
+            next_option: 0,
+            is_finished: false,
+        }
+    }
+
+    /// Returns the lower bound of the count of the decompositions extending the current one.
+    fn lower_bound(&self) -> u8 {
+        let m = self.num_required_mianzi;
+        let count = 2 * m + 1 - 2 * self.num_mianzi - self.num_dazi - u8::from(self.has_quetou);
+        let num_slots = m - self.num_mianzi - self.num_dazi;
+        let n = self.num_remaining_tiles;
+
+        // The maximum reduction of the count by the remaining tiles.
+        let max_reduction = (0..=num_slots.min(n / 3))
+            .map(|num_mianzi| {
+                let rest = n - 3 * num_mianzi;
+                let quetou = u8::from(!self.has_quetou && rest >= 2);
+                let num_dazi = (num_slots - num_mianzi).min((rest - 2 * quetou) / 2);
+                2 * num_mianzi + quetou + num_dazi
+            })
+            .max()
+            .unwrap_or(0);
+
+        count - max_reduction
+    }
+
+    /// Returns the count of the current decomposition of all tiles.
+    ///
+    /// Each missing block needs an isolated tile to start from.
+    /// Without enough isolated tiles that can be a part of an allowed block,
+    /// the rest of the missing blocks need one more tile each.
+    fn final_count(&self) -> u8 {
+        let num_missing_mianzi = self.num_required_mianzi - self.num_mianzi - self.num_dazi;
+        let num_missing_quetou = u8::from(!self.has_quetou);
+        let [mianzi_only, quetou_only, both] = self.num_seeds;
+
+        // The maximum matching between the missing blocks and the isolated tiles.
+        let num_started = (num_missing_mianzi + num_missing_quetou)
+            .min(
+                num_missing_mianzi.min(mianzi_only + both)
+                    + num_missing_quetou.min(quetou_only + both),
+            )
+            .min(mianzi_only + quetou_only + both);
+        self.lower_bound() + num_missing_mianzi + num_missing_quetou - num_started
+    }
+
+    /// Returns the index of `num_seeds` for the isolated tile,
+    /// or `None` if it cannot be a part of any allowed block.
+    fn seed_index(&self, tile: usize) -> Option<usize> {
+        let flag: TileFlags = 1 << tile;
+        let rules = &self.rules;
+        // All copies of a tile held cannot be extended to a triplet or a pair with another block.
+        let has_all_copies = self.hand[tile] == 4;
+        let is_mianzi_seed = (rules.kezi & flag != 0 && !has_all_copies)
+            || rules.shunzi & (flag | flag >> 1 | flag >> 2) != 0;
+        let is_quetou_seed = rules.quetou & flag != 0 && !has_all_copies;
+        match (is_mianzi_seed, is_quetou_seed) {
+            (true, false) => Some(0),
+            (false, true) => Some(1),
+            (true, true) => Some(2),
+            (false, false) => None,
+        }
+    }
+
+    fn block_at(&self, tile: usize, option: usize) -> Option<BlockKind> {
+        let is_shupai = tile < 27;
+        let number = tile % 9;
+        let has_slot = self.num_mianzi + self.num_dazi < self.num_required_mianzi;
+        // A partial sequence must be a part of an allowed sequence.
+        let flag: TileFlags = 1 << tile;
+        let has_kezi = self.rules.kezi & flag != 0;
+        let has_shunzi = self.rules.shunzi & flag != 0;
+        let has_quetou = self.rules.quetou & flag != 0;
+        let has_liangmian = self.rules.shunzi & (flag | flag >> 1) != 0;
+
+        let kind = match OPTIONS[option] {
+            BlockKind::Kezi if has_slot && has_kezi => BlockKind::Kezi,
+            BlockKind::Shunzi if has_slot && has_shunzi && is_shupai && number < 7 => {
+                BlockKind::Shunzi
+            }
+            BlockKind::Quetou if !self.has_quetou && has_quetou => BlockKind::Quetou,
+            BlockKind::Duizi if has_slot && has_kezi => BlockKind::Duizi,
+            BlockKind::Liangmian if has_slot && has_liangmian && is_shupai && number < 8 => {
+                if number == 0 || number == 7 {
+                    BlockKind::Bianzhang
+                } else {
+                    BlockKind::Liangmian
+                }
+            }
+            BlockKind::Kanzhang if has_slot && has_shunzi && is_shupai && number < 7 => {
+                BlockKind::Kanzhang
+            }
+            BlockKind::Gupai => BlockKind::Gupai,
+            _ => return None,
+        };
+
+        let offsets = kind.offsets();
+        offsets
+            .iter()
+            .all(|&o| {
+                let num_required = offsets.iter().filter(|&&p| p == o).count() as u8;
+                self.tile_counts[tile + o] >= num_required
+            })
+            .then_some(kind)
+    }
+
+    /// Takes the block out of the remaining tiles, or puts it back if `take` is `false`.
+    fn apply(&mut self, block: Block, take: bool) {
+        for &o in block.kind.offsets() {
+            let count = &mut self.tile_counts[block.tile as usize + o];
+            if take {
+                *count -= 1;
+                self.num_remaining_tiles -= 1;
+            } else {
+                *count += 1;
+                self.num_remaining_tiles += 1;
+            }
+        }
+
+        let counter = match block.kind {
+            BlockKind::Kezi | BlockKind::Shunzi => &mut self.num_mianzi,
+            BlockKind::Duizi
+            | BlockKind::Liangmian
+            | BlockKind::Kanzhang
+            | BlockKind::Bianzhang => &mut self.num_dazi,
+            BlockKind::Quetou => {
+                self.has_quetou = take;
+                return;
+            }
+            BlockKind::Gupai => match self.seed_index(block.tile as usize) {
+                Some(i) => &mut self.num_seeds[i],
+                None => return,
+            },
+            BlockKind::Yaojiu | BlockKind::Bukao => return,
+        };
+        if take {
+            *counter += 1;
+        } else {
+            *counter -= 1;
+        }
+    }
+
+    fn push(&mut self, block: Block) {
+        self.apply(block, true);
+        self.current.push(block);
+        self.next_option = 0;
+    }
+
+    fn backtrack(&mut self) {
+        match self.current.pop() {
+            Some(block) => {
+                self.apply(block, false);
+                self.next_option = option_index(block.kind) + 1;
+            }
+            None => self.is_finished = true,
+        }
+    }
+}
+
+impl Iterator for StandardSearch {
+    type Item = Decomposition;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.is_finished {
+            let Some(tile) = self.tile_counts.iter().position(|&c| c > 0) else {
+                // All tiles are taken into blocks.
+                let found = (self.final_count() == self.target).then(|| self.current.clone());
+                self.backtrack();
+                match found {
+                    Some(decomposition) => return Some(decomposition),
+                    None => continue,
+                }
+            };
+
+            let first_option = match self.current.last() {
+                Some(last) if last.tile as usize == tile => option_index(last.kind),
+                _ => 0,
+            };
+            let block = (self.next_option.max(first_option)..OPTIONS.len())
+                .filter_map(|option| self.block_at(tile, option))
+                .map(|kind| Block {
+                    kind,
+                    tile: tile as Tile,
+                })
+                .find(|&block| {
+                    let mut next = self.clone();
+                    next.apply(block, true);
+                    next.lower_bound() <= self.target
+                });
+
+            match block {
+                Some(block) => self.push(block),
+                None => self.backtrack(),
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hand::{FullHand, HandWithWildcards, Meld};
+    use crate::replacement_number::calculate_replacement_number;
+    use crate::test_utils::FromTileCode;
+    use crate::tile::NUM_TILE_INDEX;
+
+    fn count_tiles(decomposition: &Decomposition) -> TileCounts {
+        let mut tile_counts = [0; NUM_TILE_INDEX];
+        for block in decomposition.blocks() {
+            for &o in block.kind.offsets() {
+                tile_counts[block.tile as usize + o] += 1;
+            }
+        }
+        tile_counts
+    }
+
+    #[test]
+    fn decompose_covers_all_tiles() {
+        for code in [
+            "123m456p789s1122z",
+            "199m146779p12s246z",
+            "1111247777m1123z",
+            "2234556788p1234z",
+            "19m19p19s1234567z",
+            "1122m3344p5566s7z",
+            "1m",
+        ] {
+            let bingpai = TileCounts::from_code(code);
+            let decompositions = decompose(&bingpai, &PlayerCount::Four).unwrap();
+            assert_eq!(
+                decompositions.replacement_number(),
+                calculate_replacement_number(&bingpai, &PlayerCount::Four).unwrap(),
+                "{code}"
+            );
+
+            let mut num_decompositions = 0;
+            for decomposition in decompositions {
+                assert_eq!(count_tiles(&decomposition), bingpai, "{code}");
+                num_decompositions += 1;
+            }
+            assert!(num_decompositions > 0, "{code}");
+        }
+    }
+
+    #[test]
+    fn decompose_has_no_duplicates() {
+        let bingpai = TileCounts::from_code("11223344556677m");
+        let decompositions: [Option<Decomposition>; 64] = {
+            let mut iter = decompose(&bingpai, &PlayerCount::Four).unwrap();
+            core::array::from_fn(|_| iter.next())
+        };
+        let decompositions: &[Option<Decomposition>] = &decompositions;
+        for (i, d1) in decompositions.iter().flatten().enumerate() {
+            for d2 in decompositions.iter().flatten().skip(i + 1) {
+                assert_ne!(d1, d2);
+            }
+        }
+    }
+
+    #[test]
+    fn decompose_qiduizi() {
+        let bingpai = TileCounts::from_code("1122m3344p5566s7z");
+        let mut decompositions = decompose(&bingpai, &PlayerCount::Four).unwrap();
+        assert_eq!(decompositions.replacement_number(), 1);
+
+        let qiduizi = decompositions
+            .find(|d| d.form() == WinningForms::QIDUIZI)
+            .unwrap();
+        let num_duizi = qiduizi
+            .blocks()
+            .iter()
+            .filter(|b| b.kind == BlockKind::Duizi)
+            .count();
+        assert_eq!(num_duizi, 6);
+        assert_eq!(
+            qiduizi.blocks().last(),
+            Some(&Block {
+                kind: BlockKind::Gupai,
+                tile: 33
+            })
+        );
+    }
+
+    #[test]
+    fn decompose_with_config_qiduizi_quads() {
+        let bingpai = TileCounts::from_code("1111m2233p4455s66z");
+        let config = Config {
+            forms: WinningForms::QIDUIZI,
+            qiduizi_quads_as_two_pairs: true,
+            ..Default::default()
+        };
+        let mut decompositions = decompose_with_config(&bingpai, &config).unwrap();
+        assert_eq!(decompositions.replacement_number(), 0);
+
+        let qiduizi = decompositions.next().unwrap();
+        assert_eq!(qiduizi.blocks().len(), 7);
+        assert!(qiduizi.blocks().iter().all(|b| b.kind == BlockKind::Duizi));
+        assert!(decompositions.next().is_none());
+    }
+
+    #[test]
+    fn decompose_shisanyao() {
+        let bingpai = TileCounts::from_code("119m19p19s123456z");
+        let mut decompositions = decompose(&bingpai, &PlayerCount::Four).unwrap();
+        assert_eq!(decompositions.replacement_number(), 1);
+
+        let shisanyao = decompositions.next().unwrap();
+        assert_eq!(shisanyao.form(), WinningForms::SHISANYAO);
+        assert_eq!(
+            shisanyao.blocks()[0],
+            Block {
+                kind: BlockKind::Quetou,
+                tile: 0
+            }
+        );
+        assert_eq!(shisanyao.blocks().len(), 12);
+        assert!(decompositions.next().is_none());
+    }
+
+    #[test]
+    fn decompose_with_config_selects_forms() {
+        let bingpai = TileCounts::from_code("1122m3344p5566s7z");
+        let config = Config {
+            forms: WinningForms::STANDARD,
+            ..Default::default()
+        };
+        let decompositions = decompose_with_config(&bingpai, &config).unwrap();
+        assert!(decompositions.replacement_number() > 1);
+        assert!(
+            decompositions
+                .into_iter()
+                .all(|d| d.form() == WinningForms::STANDARD)
+        );
+    }
+
+    #[test]
+    fn decompose_with_melds() {
+        let bingpai = TileCounts::from_code("1234m");
+        let melds = [Meld::Pon(27), Meld::Pon(28), Meld::Pon(29)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let decompositions = decompose(&hand, &PlayerCount::Four).unwrap();
+        assert_eq!(decompositions.replacement_number(), 1);
+        // 123m 4m, 234m 1m
+        assert_eq!(decompositions.count(), 2);
+    }
+
+    #[test]
+    fn decompose_with_config_allowed_tiles() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        // Simples only (断么九)
+        let allowed_tiles = TileFlags::from_code("2345678m2345678p2345678s");
+        let config = Config {
+            allowed_tiles,
+            ..Default::default()
+        };
+        let decompositions = decompose_with_config(&bingpai, &config).unwrap();
+        assert_eq!(decompositions.replacement_number(), 7);
+
+        let mut num_decompositions = 0;
+        for decomposition in decompositions {
+            assert_eq!(count_tiles(&decomposition), bingpai);
+            for block in decomposition.blocks() {
+                if block.kind == BlockKind::Gupai {
+                    continue;
+                }
+                for &o in block.kind.offsets() {
+                    assert_ne!(allowed_tiles & (1 << (block.tile as usize + o)), 0);
+                }
+            }
+            num_decompositions += 1;
+        }
+        assert!(num_decompositions > 0);
+    }
+
+    #[test]
+    fn decompose_with_config_duiduihe() {
+        let bingpai = TileCounts::from_code("123m111p222s3344z");
+        let config = Config {
+            forms: WinningForms::DUIDUIHE,
+            ..Default::default()
+        };
+        let decompositions = decompose_with_config(&bingpai, &config).unwrap();
+        assert_eq!(decompositions.replacement_number(), 3);
+
+        let mut num_decompositions = 0;
+        for decomposition in decompositions {
+            assert_eq!(decomposition.form(), WinningForms::DUIDUIHE);
+            assert_eq!(count_tiles(&decomposition), bingpai);
+            assert!(decomposition.blocks().iter().all(|b| matches!(
+                b.kind,
+                BlockKind::Kezi | BlockKind::Quetou | BlockKind::Duizi | BlockKind::Gupai
+            )));
+            num_decompositions += 1;
+        }
+        assert!(num_decompositions > 0);
+    }
+
+    #[test]
+    fn decompose_with_config_pinghe() {
+        let bingpai = TileCounts::from_code("111m234p567p345s11z");
+        let config = Config {
+            forms: WinningForms::PINGHE,
+            pair_tiles: TileFlags::from_code("123456789m123456789p123456789s"),
+            ..Default::default()
+        };
+        let decompositions = decompose_with_config(&bingpai, &config).unwrap();
+        assert_eq!(decompositions.replacement_number(), 2);
+
+        let mut num_decompositions = 0;
+        for decomposition in decompositions {
+            assert_eq!(decomposition.form(), WinningForms::PINGHE);
+            assert_eq!(count_tiles(&decomposition), bingpai);
+            for block in decomposition.blocks() {
+                assert_ne!(block.kind, BlockKind::Kezi);
+                if block.kind == BlockKind::Quetou {
+                    assert!(block.tile < 27);
+                }
+            }
+            num_decompositions += 1;
+        }
+        assert!(num_decompositions > 0);
+    }
+
+    #[test]
+    fn decompose_with_config_quandai() {
+        let bingpai = TileCounts::from_code("12789m23p99s1z");
+        let config = Config {
+            forms: WinningForms::HUNQUANDAI,
+            ..Default::default()
+        };
+        let decompositions = decompose_with_config(&bingpai, &config).unwrap();
+        assert_eq!(decompositions.replacement_number(), 2);
+
+        let mut num_decompositions = 0;
+        for decomposition in decompositions {
+            assert_eq!(decomposition.form(), WinningForms::HUNQUANDAI);
+            assert_eq!(count_tiles(&decomposition), bingpai);
+            for block in decomposition.blocks() {
+                let tile = block.tile as usize;
+                match block.kind {
+                    BlockKind::Shunzi | BlockKind::Kanzhang => {
+                        assert!(matches!(tile % 9, 0 | 6));
+                    }
+                    BlockKind::Liangmian | BlockKind::Bianzhang => {
+                        // A part of 123 or 789
+                        assert!(matches!(tile % 9, 0 | 1 | 6 | 7));
+                    }
+                    BlockKind::Kezi | BlockKind::Quetou | BlockKind::Duizi => {
+                        assert!(tile >= 27 || tile % 9 == 0 || tile % 9 == 8);
+                    }
+                    _ => (),
+                }
+            }
+            num_decompositions += 1;
+        }
+        assert!(num_decompositions > 0);
+    }
+
+    #[test]
+    fn decompose_with_config_knitted() {
+        let bingpai = TileCounts::from_code("147m258p369s12345z");
+        let config = Config {
+            forms: WinningForms::QUANBUKAO,
+            ..Default::default()
+        };
+        let mut decompositions = decompose_with_config(&bingpai, &config).unwrap();
+        assert_eq!(decompositions.replacement_number(), 0);
+
+        let decomposition = decompositions.next().unwrap();
+        assert_eq!(decomposition.form(), WinningForms::QUANBUKAO);
+        assert_eq!(decomposition.blocks().len(), 14);
+        assert!(
+            decomposition
+                .blocks()
+                .iter()
+                .all(|b| b.kind == BlockKind::Bukao)
+        );
+        assert!(decompositions.next().is_none());
+
+        let bingpai = TileCounts::from_code("147m258p369s123s11z");
+        let config = Config {
+            forms: WinningForms::ZUHELONG,
+            ..Default::default()
+        };
+        let mut decompositions = decompose_with_config(&bingpai, &config).unwrap();
+        assert_eq!(decompositions.replacement_number(), 0);
+
+        // 1m 4m 7m 2p 5p 8p 123s 3s 6s 9s 11z
+        let decomposition = decompositions.next().unwrap();
+        assert_eq!(decomposition.form(), WinningForms::ZUHELONG);
+        assert_eq!(count_tiles(&decomposition), bingpai);
+        let kinds = decomposition.blocks().iter().map(|b| b.kind);
+        assert_eq!(kinds.clone().filter(|&k| k == BlockKind::Bukao).count(), 9);
+        assert_eq!(kinds.clone().filter(|&k| k == BlockKind::Shunzi).count(), 1);
+        assert_eq!(kinds.clone().filter(|&k| k == BlockKind::Quetou).count(), 1);
+        assert!(decompositions.next().is_none());
+    }
+
+    #[test]
+    fn decompose_3p() {
+        let bingpai = TileCounts::from_code("1199m19p19s12345z");
+        let decompositions = decompose(&bingpai, &PlayerCount::Three).unwrap();
+        assert!(
+            decompositions
+                .into_iter()
+                .all(|d| count_tiles(&d) == bingpai)
+        );
+    }
+
+    #[test]
+    fn decompose_err_wildcards() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let hand = HandWithWildcards {
+            hand: &bingpai,
+            num_wildcards: 1,
+        };
+        let decompositions = decompose(&hand, &PlayerCount::Four);
+        assert!(matches!(
+            decompositions,
+            Err(BingpaiError::UnsupportedWildcards)
+        ));
+
+        let config = Config {
+            wildcard_tile: Some(33),
+            ..Default::default()
+        };
+        let decompositions = decompose_with_config(&bingpai, &config);
+        assert!(matches!(
+            decompositions,
+            Err(BingpaiError::UnsupportedWildcards)
+        ));
+    }
+}
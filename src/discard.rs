@@ -0,0 +1,397 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use super::constrained;
+use super::qiduizi;
+use super::shisanyao;
+use super::standard;
+use crate::bingpai::{Bingpai, Bingpai3p, BingpaiError};
+use crate::config::{Config, PlayerCount, WinningForms};
+use crate::hand::{Hand, Meld};
+use crate::tile::{NUM_TILE_INDEX, TileCounts, TileFlags};
+use crate::ukeire::{count_remaining_tiles, count_unavailable_tiles};
+use core::cmp::Ordering;
+
+/// The result of discarding a tile from a hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Discard {
+    /// The replacement number (= xiàngtīng number + 1) after the discard.
+    pub replacement_number: u8,
+    /// The necessary tiles (有効牌) after the discard.
+    pub necessary_tiles: TileFlags,
+    /// The total number of remaining copies of the necessary tiles after the discard.
+    pub num_remaining_tiles: u8,
+}
+
+/// The results of discarding each tile, indexed by the discarded tile.
+///
+/// The element is `None` if the tile is not in the pure hand.
+pub type DiscardTable = [Option<Discard>; NUM_TILE_INDEX];
+
+type PartialTable = [Option<(u8, TileFlags)>; NUM_TILE_INDEX];
+
+/// Calculates the replacement number, the necessary tiles and the acceptance (受け入れ)
+/// after discarding each tile from a given hand of 3n+2 tiles.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `visible` - A reference to the counts of the visible tiles other than the hand
+///   (e.g., discards, melds of other players and dora indicators).
+///   If `None`, only the copies held in the hand are taken into account.
+/// * `player_count` - A reference to the number of players.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid, the number of tiles in the pure hand is not 3n+2,
+/// or the hand and the visible tiles contain more than 4 copies of a tile.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{PlayerCount, calculate_discards};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 123m456p789s11223z
+/// let hand: [u8; 34] = [
+///     1, 1, 1, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 1, 1, 1, // s
+///     2, 2, 1, 0, 0, 0, 0, // z
+/// ];
+///
+/// let discards = calculate_discards(&hand, None, &PlayerCount::Four)?;
+///
+/// // Discarding 3z
+/// let discard = discards[29].unwrap();
+/// assert_eq!(discard.replacement_number, 1u8);
+/// assert_eq!(discard.necessary_tiles, 0b0000011_000000000_000000000_000000000); // 12z
+/// assert_eq!(discard.num_remaining_tiles, 4u8);
+///
+/// // 4z is not in the hand.
+/// assert!(discards[30].is_none());
+/// # Ok(())
+/// # }
+/// ```
+#[inline]
+pub fn calculate_discards(
+    bingpai: &impl Hand,
+    visible: Option<&TileCounts>,
+    player_count: &PlayerCount,
+) -> Result<DiscardTable, BingpaiError> {
+    calculate_discards_with_config(bingpai, visible, &player_count.clone().into())
+}
+
+/// Calculates the replacement number, the necessary tiles and the acceptance (受け入れ)
+/// after discarding each tile from a given hand of 3n+2 tiles under the given rules.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `visible` - A reference to the counts of the visible tiles other than the hand
+///   (e.g., discards, melds of other players and dora indicators).
+///   If `None`, only the copies held in the hand are taken into account.
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid, the number of tiles in the pure hand is not 3n+2,
+/// no selected winning form can be completed with the hand,
+/// or the hand and the visible tiles contain more than 4 copies of a tile.
+pub fn calculate_discards_with_config(
+    bingpai: &impl Hand,
+    visible: Option<&TileCounts>,
+    config: &Config,
+) -> Result<DiscardTable, BingpaiError> {
+    constrained::check_no_wildcards(bingpai.num_wildcards(), config)?;
+    let tile_counts = bingpai.bingpai();
+    let melds = bingpai.melds();
+
+    let table = if !config.uses_tables() {
+        calculate_discards_constrained(tile_counts, melds, config)
+    } else {
+        match config.player_count {
+            PlayerCount::Four => calculate_discards_4p(tile_counts, melds, config),
+            PlayerCount::Three => calculate_discards_3p(tile_counts, melds, config),
+        }
+    }?;
+
+    let unavailable = count_unavailable_tiles(bingpai, visible.unwrap_or(&[0; NUM_TILE_INDEX]))?;
+
+    let mut discards = [None; NUM_TILE_INDEX];
+    for (discard, result) in discards.iter_mut().zip(table) {
+        let Some((replacement_number, necessary_tiles)) = result else {
+            continue;
+        };
+        if replacement_number == u8::MAX {
+            return Err(BingpaiError::NoReachableForm);
+        }
+
+        let (_, num_remaining_tiles) = count_remaining_tiles(necessary_tiles, &unavailable);
+        *discard = Some(Discard {
+            replacement_number,
+            necessary_tiles,
+            num_remaining_tiles,
+        });
+    }
+
+    Ok(discards)
+}
+
+fn check_tile_count(tile_counts: &TileCounts) -> Result<(), BingpaiError> {
+    let num_bingpai: u8 = tile_counts.iter().sum();
+    match num_bingpai % 3 {
+        2 => Ok(()),
+        _ => Err(BingpaiError::InvalidTileCountForDiscard(num_bingpai)),
+    }
+}
+
+fn update_min(lhs: &mut Option<(u8, TileFlags)>, rhs: (u8, TileFlags)) {
+    let Some((replacement_number, necessary_tiles)) = lhs else {
+        *lhs = Some(rhs);
+        return;
+    };
+    match rhs.0.cmp(replacement_number) {
+        Ordering::Less => *lhs = Some(rhs),
+        Ordering::Equal => *necessary_tiles |= rhs.1,
+        Ordering::Greater => (),
+    }
+}
+
+/// Updates `table` with the results of seven pairs and thirteen orphans,
+/// which are cheap enough to be recalculated for each discard.
+fn update_table_with_special_forms(
+    table: &mut PartialTable,
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    forms: WinningForms,
+    calculate_qiduizi: impl Fn(&TileCounts, &[Meld]) -> (u8, TileFlags),
+) {
+    for (i, result) in table.iter_mut().enumerate() {
+        if tile_counts[i] == 0 {
+            continue;
+        }
+
+        let mut discarded = *tile_counts;
+        discarded[i] -= 1;
+
+        if forms.contains(WinningForms::QIDUIZI) {
+            update_min(result, calculate_qiduizi(&discarded, melds));
+        }
+
+        if forms.contains(WinningForms::SHISANYAO) {
+            // The hand after the discard is always valid.
+            let bingpai = Bingpai::with_melds(&discarded, melds).unwrap();
+            update_min(result, shisanyao::calculate_necessary_tiles(&bingpai));
+        }
+    }
+}
+
+fn calculate_discards_4p(
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    config: &Config,
+) -> Result<PartialTable, BingpaiError> {
+    let bingpai = Bingpai::with_melds(tile_counts, melds)?;
+    check_tile_count(tile_counts)?;
+
+    let mut table = [None; NUM_TILE_INDEX];
+
+    if config.forms.contains(WinningForms::STANDARD) {
+        table = standard::calculate_discards(&bingpai);
+    }
+
+    update_table_with_special_forms(&mut table, tile_counts, melds, config.forms, |t, m| {
+        let bingpai = Bingpai::with_melds(t, m).unwrap();
+        qiduizi::calculate_necessary_tiles(&bingpai, config.qiduizi_quads_as_two_pairs)
+    });
+
+    Ok(table)
+}
+
+fn calculate_discards_3p(
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    config: &Config,
+) -> Result<PartialTable, BingpaiError> {
+    let bingpai_3p = Bingpai3p::with_melds(tile_counts, melds)?;
+    check_tile_count(tile_counts)?;
+
+    let mut table = [None; NUM_TILE_INDEX];
+
+    if config.forms.contains(WinningForms::STANDARD) {
+        table = standard::calculate_discards_3p(&bingpai_3p);
+    }
+
+    update_table_with_special_forms(&mut table, tile_counts, melds, config.forms, |t, m| {
+        let bingpai_3p = Bingpai3p::with_melds(t, m).unwrap();
+        qiduizi::calculate_necessary_tiles_3p(&bingpai_3p, config.qiduizi_quads_as_two_pairs)
+    });
+
+    Ok(table)
+}
+
+/// Calculates the result for each discard one by one,
+/// since the constrained forms have no precomputed tables.
+fn calculate_discards_constrained(
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    config: &Config,
+) -> Result<PartialTable, BingpaiError> {
+    constrained::validate(tile_counts, melds, 0, config)?;
+    check_tile_count(tile_counts)?;
+
+    let mut table = [None; NUM_TILE_INDEX];
+    for (i, result) in table.iter_mut().enumerate() {
+        if tile_counts[i] == 0 {
+            continue;
+        }
+
+        let mut discarded = *tile_counts;
+        discarded[i] -= 1;
+
+        // The hand after the discard is always valid.
+        let analysis = constrained::analyze(&discarded, melds, 0, config)?;
+        *result = Some((analysis.replacement_number, analysis.necessary_tiles));
+    }
+
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hand::FullHand;
+    use crate::necessary_tiles::calculate_necessary_tiles_with_config;
+    use crate::test_utils::FromTileCode;
+    use crate::ukeire::calculate_ukeire_with_config;
+
+    fn verify(hand: &FullHand, visible: Option<&TileCounts>, config: &Config) {
+        let discards = calculate_discards_with_config(hand, visible, config).unwrap();
+        let empty = [0; NUM_TILE_INDEX];
+
+        for (i, discard) in discards.iter().enumerate() {
+            if hand.bingpai[i] == 0 {
+                assert!(discard.is_none());
+                continue;
+            }
+
+            let mut discarded = *hand.bingpai;
+            discarded[i] -= 1;
+            let discarded_hand = FullHand {
+                bingpai: &discarded,
+                melds: hand.melds,
+            };
+            let (r, n) = calculate_necessary_tiles_with_config(&discarded_hand, config).unwrap();
+            // The discarded tile cannot be drawn again.
+            let mut visible = *visible.unwrap_or(&empty);
+            visible[i] += 1;
+            let ukeire = calculate_ukeire_with_config(&discarded_hand, &visible, config).unwrap();
+
+            let discard = discard.unwrap();
+            assert_eq!(discard.replacement_number, r);
+            assert_eq!(discard.necessary_tiles, n);
+            assert_eq!(discard.num_remaining_tiles, ukeire.num_remaining_tiles);
+        }
+    }
+
+    #[test]
+    fn calculate_discards_matches_each_discard() {
+        for code in [
+            "123m456p789s11223z",
+            "1188m288p55s11777z",
+            "19m19p19s12345677z",
+            "199m146779p12s246z",
+        ] {
+            let bingpai = TileCounts::from_code(code);
+            let hand = FullHand {
+                bingpai: &bingpai,
+                melds: &[],
+            };
+            verify(&hand, None, &Config::default());
+        }
+    }
+
+    #[test]
+    fn calculate_discards_3_player_matches_each_discard() {
+        for code in [
+            "1199m288p55s11777z",
+            "19m19p19s12345677z",
+            "11999m147p258s123z",
+        ] {
+            let bingpai = TileCounts::from_code(code);
+            let hand = FullHand {
+                bingpai: &bingpai,
+                melds: &[],
+            };
+            verify(&hand, None, &PlayerCount::Three.into());
+        }
+    }
+
+    #[test]
+    fn calculate_discards_with_melds_and_visible() {
+        let bingpai = TileCounts::from_code("1234m456p1z");
+        let melds = [Meld::Pon(33), Meld::Chi(24)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let visible = TileCounts::from_code("14m7z");
+        verify(&hand, Some(&visible), &Config::default());
+    }
+
+    #[test]
+    fn calculate_discards_with_config_qiduizi_only() {
+        let bingpai = TileCounts::from_code("1188m288p55s11777z");
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &[],
+        };
+        let config = Config {
+            forms: WinningForms::QIDUIZI,
+            ..Default::default()
+        };
+        verify(&hand, None, &config);
+    }
+
+    #[test]
+    fn calculate_discards_with_config_allowed_tiles() {
+        let bingpai = TileCounts::from_code("123m456p789s11222z");
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &[],
+        };
+        // Simples only (断么九)
+        let config = Config {
+            allowed_tiles: TileFlags::from_code("2345678m2345678p2345678s"),
+            ..Default::default()
+        };
+        verify(&hand, None, &config);
+    }
+
+    #[test]
+    fn calculate_discards_err_3n_plus_1() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let discards = calculate_discards(&bingpai, None, &PlayerCount::Four);
+        assert!(matches!(
+            discards,
+            Err(BingpaiError::InvalidTileCountForDiscard(13))
+        ));
+    }
+
+    #[test]
+    fn calculate_discards_err_too_many_visible_copies() {
+        let bingpai = TileCounts::from_code("123m456p789s11223z");
+        let visible = TileCounts::from_code("3333z");
+        let discards = calculate_discards(&bingpai, Some(&visible), &PlayerCount::Four);
+        assert!(matches!(
+            discards,
+            Err(BingpaiError::TooManyVisibleCopies { tile: 29, count: 5 })
+        ));
+    }
+}
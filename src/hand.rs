@@ -0,0 +1,344 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use crate::bingpai::BingpaiError;
+use crate::tile::{NUM_TILE_INDEX, Tile, TileCounts};
+
+const MAX_RED_FIVE_COPIES: u8 = 1;
+const RED_FIVE_INDICES: [usize; 3] = [4, 13, 22];
+
+/// A hand that can be passed to the calculation functions.
+///
+/// This trait is implemented for [`TileCounts`], [`TileCountsWithRed`], [`FullHand`]
+/// and [`HandWithWildcards`].
+pub trait Hand {
+    /// Returns the pure hand (純手牌) as [`TileCounts`].
+    ///
+    /// Red fives must be counted as normal fives.
+    fn bingpai(&self) -> &TileCounts;
+
+    /// Returns the melds (副露).
+    ///
+    /// The default implementation returns no melds.
+    fn melds(&self) -> &[Meld] {
+        &[]
+    }
+
+    /// Returns the number of wildcards (百搭) in the pure hand,
+    /// which are not counted in [`bingpai`](Self::bingpai).
+    ///
+    /// The default implementation returns 0.
+    fn num_wildcards(&self) -> u8 {
+        0
+    }
+}
+
+impl Hand for TileCounts {
+    #[inline]
+    fn bingpai(&self) -> &TileCounts {
+        self
+    }
+}
+
+/// A pure hand (純手牌) that tracks red fives (赤ドラ) separately.
+///
+/// The red fives are folded into 5m, 5p and 5s when the hand is passed to the calculation functions.
+/// In MPSZ notation, red fives are written as `0m`, `0p` and `0s`.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{PlayerCount, TileCountsWithRed, calculate_replacement_number};
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let hand: TileCountsWithRed = "340m456p789s11222z".parse()?;
+/// assert_eq!(hand.red_fives(), [1, 0, 0]);
+/// assert_eq!(hand.to_string(), "340m456p789s11222z");
+///
+/// let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four)?;
+/// assert_eq!(replacement_number, 0u8);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TileCountsWithRed {
+    tile_counts: TileCounts,
+    red_fives: [u8; 3],
+}
+
+impl TileCountsWithRed {
+    /// Creates a hand from the counts of the tiles and the red fives.
+    ///
+    /// # Arguments
+    ///
+    /// * `tile_counts` - A reference to the counts of the tiles excluding red fives.
+    /// * `red_fives` - The number of red fives of each suit, in the order of 0m, 0p and 0s.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Err`] if there is more than one red five in a suit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use xiangting::{BingpaiError, Hand, TileCountsWithRed};
+    /// # fn main() -> Result<(), BingpaiError> {
+    /// // 055m
+    /// let tile_counts: [u8; 34] = [
+    ///     0, 0, 0, 0, 2, 0, 0, 0, 0, // m
+    ///     0, 0, 0, 0, 0, 0, 0, 0, 0, // p
+    ///     0, 0, 0, 0, 0, 0, 0, 0, 0, // s
+    ///     0, 0, 0, 0, 0, 0, 0, // z
+    /// ];
+    ///
+    /// let hand = TileCountsWithRed::new(&tile_counts, [1, 0, 0])?;
+    /// assert_eq!(hand.bingpai()[4], 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new(tile_counts: &TileCounts, red_fives: [u8; 3]) -> Result<Self, BingpaiError> {
+        let mut folded = *tile_counts;
+        for (&i, &count) in RED_FIVE_INDICES.iter().zip(red_fives.iter()) {
+            if count > MAX_RED_FIVE_COPIES {
+                return Err(BingpaiError::TooManyRedFives {
+                    tile: i as Tile,
+                    count,
+                });
+            }
+            folded[i] = folded[i].saturating_add(count);
+        }
+
+        Ok(Self {
+            tile_counts: folded,
+            red_fives,
+        })
+    }
+
+    /// Returns the number of red fives of each suit, in the order of 0m, 0p and 0s.
+    #[inline]
+    #[must_use]
+    pub fn red_fives(&self) -> [u8; 3] {
+        self.red_fives
+    }
+}
+
+impl Hand for TileCountsWithRed {
+    #[inline]
+    fn bingpai(&self) -> &TileCounts {
+        &self.tile_counts
+    }
+}
+
+/// 副露面子: A meld.
+///
+/// The tile of each variant represents the meld as follows.
+///
+/// - [`Chi`](Meld::Chi): the lowest tile of the sequence (e.g., `1m` for `123m`).
+/// - Others: the tile of the triplet or the quadruple.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Meld {
+    /// 吃 (チー): A sequence claimed from a discard.
+    Chi(Tile),
+    /// 碰 (ポン): A triplet claimed from a discard.
+    Pon(Tile),
+    /// 大明槓 (ダイミンカン): A quadruple claimed from a discard.
+    Daiminkan(Tile),
+    /// 加槓 (カカン): A quadruple made by adding a tile to a claimed triplet.
+    Kakan(Tile),
+    /// 暗槓 (アンカン): A concealed quadruple.
+    Ankan(Tile),
+}
+
+impl Meld {
+    #[inline]
+    #[must_use]
+    pub(crate) fn tile(&self) -> Tile {
+        match *self {
+            Meld::Chi(t) | Meld::Pon(t) | Meld::Daiminkan(t) | Meld::Kakan(t) | Meld::Ankan(t) => t,
+        }
+    }
+
+    /// Returns `true` if the meld represents real tiles.
+    #[must_use]
+    pub(crate) fn is_valid(&self) -> bool {
+        match *self {
+            // The sequence must start from 1-7 of a suit.
+            Meld::Chi(t) => t < 27 && t % 9 < 7,
+            _ => (self.tile() as usize) < NUM_TILE_INDEX,
+        }
+    }
+
+    /// Adds the tiles of the meld to `tile_counts`.
+    pub(crate) fn add_to(&self, tile_counts: &mut TileCounts) {
+        let t = self.tile() as usize;
+        match *self {
+            Meld::Chi(_) => {
+                tile_counts[t] += 1;
+                tile_counts[t + 1] += 1;
+                tile_counts[t + 2] += 1;
+            }
+            Meld::Pon(_) => tile_counts[t] += 3,
+            Meld::Daiminkan(_) | Meld::Kakan(_) | Meld::Ankan(_) => tile_counts[t] += 4,
+        }
+    }
+}
+
+/// 手牌: A hand consisting of a pure hand (純手牌) and melds (副露).
+///
+/// The calculation functions check that each tile appears at most 4 times
+/// across the pure hand and the melds.
+/// Seven pairs (七対子) and thirteen orphans (国士無双) are not considered
+/// when the hand has any melds.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{FullHand, Meld, PlayerCount, calculate_replacement_number};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 123m456p11222z + 789s (Chi)
+/// let bingpai: [u8; 34] = [
+///     1, 1, 1, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 0, 0, 0, // s
+///     2, 3, 0, 0, 0, 0, 0, // z
+/// ];
+/// let melds = [Meld::Chi(24)];
+/// let hand = FullHand {
+///     bingpai: &bingpai,
+///     melds: &melds,
+/// };
+///
+/// let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four)?;
+/// assert_eq!(replacement_number, 0u8);
+///
+/// // 9s is used 5 times.
+/// let melds = [Meld::Chi(24), Meld::Ankan(26)];
+/// let hand = FullHand {
+///     bingpai: &bingpai,
+///     melds: &melds,
+/// };
+///
+/// let result = calculate_replacement_number(&hand, &PlayerCount::Four);
+/// assert!(matches!(
+///     result,
+///     Err(BingpaiError::TooManyCopiesWithMelds { tile: 26, count: 5 })
+/// ));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FullHand<'a> {
+    /// 兵牌: The hand excluding melds (a.k.a. pure hand, 純手牌).
+    pub bingpai: &'a TileCounts,
+    /// 副露: The melds.
+    pub melds: &'a [Meld],
+}
+
+impl Hand for FullHand<'_> {
+    #[inline]
+    fn bingpai(&self) -> &TileCounts {
+        self.bingpai
+    }
+
+    #[inline]
+    fn melds(&self) -> &[Meld] {
+        self.melds
+    }
+}
+
+/// A hand with wildcards (百搭, 鬼牌) that can stand for any tile.
+///
+/// Wildcards are supported in the replacement number, the necessary tiles and the unnecessary tiles.
+/// Each wildcard fills one of the missing tiles of the winning hand.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{HandWithWildcards, PlayerCount, calculate_replacement_number};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 123m456p789s1122z + 1 wildcard
+/// let bingpai: [u8; 34] = [
+///     1, 1, 1, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 1, 1, 1, // s
+///     2, 2, 0, 0, 0, 0, 0, // z
+/// ];
+/// let hand = HandWithWildcards {
+///     hand: &bingpai,
+///     num_wildcards: 1,
+/// };
+///
+/// let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four)?;
+/// assert_eq!(replacement_number, 0u8);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandWithWildcards<'a, H: Hand> {
+    /// The hand excluding the wildcards.
+    pub hand: &'a H,
+    /// The number of wildcards in the pure hand (純手牌).
+    pub num_wildcards: u8,
+}
+
+impl<H: Hand> Hand for HandWithWildcards<'_, H> {
+    #[inline]
+    fn bingpai(&self) -> &TileCounts {
+        self.hand.bingpai()
+    }
+
+    #[inline]
+    fn melds(&self) -> &[Meld] {
+        self.hand.melds()
+    }
+
+    #[inline]
+    fn num_wildcards(&self) -> u8 {
+        self.num_wildcards
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::FromTileCode;
+
+    #[test]
+    fn new_fold_red_fives() {
+        let tile_counts = TileCounts::from_code("55m5p");
+        let hand = TileCountsWithRed::new(&tile_counts, [1, 1, 1]).unwrap();
+        assert_eq!(hand.bingpai(), &TileCounts::from_code("555m55p5s"));
+        assert_eq!(hand.red_fives(), [1, 1, 1]);
+    }
+
+    #[test]
+    fn new_err_too_many_red_fives() {
+        let tile_counts = TileCounts::from_code("5s");
+        let hand = TileCountsWithRed::new(&tile_counts, [0, 0, 2]);
+        assert!(matches!(
+            hand,
+            Err(BingpaiError::TooManyRedFives { tile: 22, count: 2 })
+        ));
+    }
+
+    #[test]
+    fn meld_is_valid() {
+        assert!(Meld::Chi(0).is_valid());
+        assert!(Meld::Chi(24).is_valid());
+        assert!(!Meld::Chi(7).is_valid());
+        assert!(!Meld::Chi(27).is_valid());
+        assert!(Meld::Pon(33).is_valid());
+        assert!(!Meld::Ankan(34).is_valid());
+    }
+
+    #[test]
+    fn meld_add_to() {
+        let mut tile_counts = TileCounts::from_code("1m");
+        Meld::Chi(0).add_to(&mut tile_counts);
+        Meld::Pon(9).add_to(&mut tile_counts);
+        Meld::Kakan(33).add_to(&mut tile_counts);
+        assert_eq!(tile_counts, TileCounts::from_code("1123m111p7777z"));
+    }
+}
@@ -27,40 +27,109 @@
 //! # }
 //! ```
 
+#[cfg(not(feature = "build-file"))]
+extern crate alloc;
+
+#[cfg(not(feature = "build-file"))]
+mod analysis;
 #[cfg(not(feature = "build-file"))]
 mod bingpai;
 #[cfg(not(feature = "build-file"))]
+mod bukao;
+#[cfg(not(feature = "build-file"))]
 mod config;
 #[cfg(not(feature = "build-file"))]
+mod constrained;
+#[cfg(not(feature = "build-file"))]
+mod decomposition;
+#[cfg(not(feature = "build-file"))]
+mod discard;
+#[cfg(not(feature = "build-file"))]
+mod hand;
+#[cfg(not(feature = "build-file"))]
+mod mpsz;
+#[cfg(not(feature = "build-file"))]
 mod necessary_tiles;
 #[cfg(not(feature = "build-file"))]
+mod probability;
+#[cfg(not(feature = "build-file"))]
 mod qiduizi;
 #[cfg(not(feature = "build-file"))]
 mod replacement_number;
 #[cfg(not(feature = "build-file"))]
+mod required_blocks;
+#[cfg(not(feature = "build-file"))]
+mod rng;
+#[cfg(not(feature = "build-file"))]
 mod shisanyao;
 #[cfg(not(feature = "build-file"))]
 mod standard;
 #[cfg(not(feature = "build-file"))]
 mod tile;
 #[cfg(not(feature = "build-file"))]
+mod ukeire;
+#[cfg(not(feature = "build-file"))]
 mod unnecessary_tiles;
+#[cfg(not(feature = "build-file"))]
+mod wait;
+#[cfg(not(feature = "build-file"))]
+mod winning;
 
 #[cfg(all(test, not(feature = "build-file")))]
 mod test_utils;
 
+#[cfg(not(feature = "build-file"))]
+pub use analysis::{
+    Analysis, FormBreakdown, analyze, analyze_with_config, calculate_form_breakdown,
+    calculate_form_breakdown_with_config,
+};
 #[cfg(not(feature = "build-file"))]
 pub use bingpai::BingpaiError;
 #[cfg(not(feature = "build-file"))]
-pub use config::PlayerCount;
+pub use config::{Config, PlayerCount, Suit, WinningForms};
+#[cfg(not(feature = "build-file"))]
+pub use decomposition::{
+    Block, BlockKind, Decomposition, Decompositions, decompose, decompose_with_config,
+};
+#[cfg(not(feature = "build-file"))]
+pub use discard::{Discard, DiscardTable, calculate_discards, calculate_discards_with_config};
+#[cfg(not(feature = "build-file"))]
+pub use hand::{FullHand, Hand, HandWithWildcards, Meld, TileCountsWithRed};
 #[cfg(not(feature = "build-file"))]
-pub use necessary_tiles::calculate_necessary_tiles;
+pub use mpsz::{
+    ParseTileError, format_tile_counts, format_tile_flags, parse_tile_counts, parse_tile_flags,
+};
 #[cfg(not(feature = "build-file"))]
-pub use replacement_number::calculate_replacement_number;
+pub use necessary_tiles::{calculate_necessary_tiles, calculate_necessary_tiles_with_config};
+#[cfg(not(feature = "build-file"))]
+pub use probability::{
+    DrawProbability, ProbabilityMethod, calculate_draw_probability,
+    calculate_draw_probability_with_config,
+};
+#[cfg(not(feature = "build-file"))]
+pub use replacement_number::{
+    calculate_replacement_number, calculate_replacement_number_with_config,
+};
+#[cfg(not(feature = "build-file"))]
+pub use required_blocks::{analyze_with_required_blocks, analyze_with_required_blocks_with_config};
 #[cfg(not(feature = "build-file"))]
 pub use tile::{Tile, TileCounts, TileFlags, TileFlagsExt};
 #[cfg(not(feature = "build-file"))]
-pub use unnecessary_tiles::calculate_unnecessary_tiles;
+pub use ukeire::{
+    TwoStepUkeire, Ukeire, calculate_two_step_ukeire, calculate_two_step_ukeire_with_config,
+    calculate_ukeire, calculate_ukeire_with_config,
+};
+#[cfg(not(feature = "build-file"))]
+pub use unnecessary_tiles::{calculate_unnecessary_tiles, calculate_unnecessary_tiles_with_config};
+#[cfg(not(feature = "build-file"))]
+pub use wait::{
+    WaitKinds, WaitStatus, Waits, calculate_wait_status, calculate_wait_status_with_config,
+    calculate_waits, calculate_waits_with_config,
+};
+#[cfg(not(feature = "build-file"))]
+pub use winning::{
+    is_winning, is_winning_with_config, winning_decompositions, winning_decompositions_with_config,
+};
 
 #[cfg(feature = "build-map")]
 #[doc(hidden)]
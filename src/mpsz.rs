@@ -0,0 +1,421 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use crate::hand::{Hand, TileCountsWithRed};
+use crate::tile::{NUM_TILE_INDEX, Tile, TileCounts, TileFlags};
+use core::fmt;
+use core::str::FromStr;
+use thiserror::Error;
+
+const SUITS: [char; 4] = ['m', 'p', 's', 'z'];
+
+/// Errors that occur when parsing a string in MPSZ notation.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ParseTileError {
+    /// The string contains a character that is neither a digit nor a suit.
+    #[error("unexpected character {0:?}")]
+    UnexpectedCharacter(char),
+    /// Tile numbers are not followed by a suit (`m`, `p`, `s` or `z`).
+    #[error("tile number {0} is not followed by a suit")]
+    MissingSuit(char),
+    /// The combination of the number and the suit does not represent a tile (e.g., `0m`, `8z`).
+    #[error("{number}{suit} is not a valid tile")]
+    InvalidTile {
+        /// The number of the tile.
+        number: char,
+        /// The suit of the tile.
+        suit: char,
+    },
+    /// A tile appears more times than can be counted.
+    #[error("tile {0} appears too many times")]
+    TooManyCopies(Tile),
+    /// A red five (`0m`, `0p` or `0s`) appears more than once.
+    #[error("red five {0} appears more than once")]
+    TooManyRedFives(Tile),
+}
+
+/// Iterates over the tiles of the string from the end, paired with their number and suit.
+fn parse_tiles(tiles: &str) -> impl Iterator<Item = Result<(char, char), ParseTileError>> + '_ {
+    let mut current_suit: Option<char> = None;
+
+    tiles.chars().rev().filter_map(move |c| match c {
+        'm' | 'p' | 's' | 'z' => {
+            current_suit = Some(c);
+            None
+        }
+        '0'..='9' => match current_suit {
+            Some(suit) => Some(Ok((c, suit))),
+            None => Some(Err(ParseTileError::MissingSuit(c))),
+        },
+        _ => Some(Err(ParseTileError::UnexpectedCharacter(c))),
+    })
+}
+
+fn to_tile(number: char, suit: char) -> Result<Tile, ParseTileError> {
+    let base = match suit {
+        'm' => 0,
+        'p' => 9,
+        's' => 18,
+        'z' => 27,
+        _ => unreachable!("suit must be one of m, p, s or z but was {suit}"),
+    };
+    let max = if suit == 'z' { '7' } else { '9' };
+
+    if !('1'..=max).contains(&number) {
+        return Err(ParseTileError::InvalidTile { number, suit });
+    }
+
+    Ok(base + (number as u8 - b'1'))
+}
+
+/// Parses a string in MPSZ notation (e.g., `"123m456p789s11222z"`) into [`TileCounts`].
+///
+/// Each group of digits is followed by its suit:
+/// `m` for characters (萬子), `p` for dots (筒子), `s` for bamboos (索子), and `z` for honors (字牌).
+/// Honors are numbered from East (`1z`) to Red (`7z`).
+///
+/// The parser only checks the syntax. Use the calculation functions to validate the hand.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the string is not in MPSZ notation.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{ParseTileError, parse_tile_counts};
+/// # fn main() -> Result<(), ParseTileError> {
+/// let hand = parse_tile_counts("123m456p789s11222z")?;
+/// assert_eq!(
+///     hand,
+///     [
+///         1, 1, 1, 0, 0, 0, 0, 0, 0, // m
+///         0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+///         0, 0, 0, 0, 0, 0, 1, 1, 1, // s
+///         2, 3, 0, 0, 0, 0, 0, // z
+///     ]
+/// );
+///
+/// assert_eq!(
+///     parse_tile_counts("8z"),
+///     Err(ParseTileError::InvalidTile { number: '8', suit: 'z' })
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_tile_counts(tiles: &str) -> Result<TileCounts, ParseTileError> {
+    count_tiles(tiles, None)
+}
+
+/// Counts the tiles of the string.
+/// Red fives are counted in `red_fives` if given, otherwise they are rejected.
+fn count_tiles(
+    tiles: &str,
+    mut red_fives: Option<&mut [u8; 3]>,
+) -> Result<TileCounts, ParseTileError> {
+    let mut tile_counts = [0u8; NUM_TILE_INDEX];
+    for item in parse_tiles(tiles) {
+        let (number, suit) = item?;
+        if let (Some(red_fives), '0', Some(s)) = (
+            red_fives.as_deref_mut(),
+            number,
+            SUITS[..3].iter().position(|&c| c == suit),
+        ) {
+            if red_fives[s] > 0 {
+                return Err(ParseTileError::TooManyRedFives((9 * s + 4) as Tile));
+            }
+            red_fives[s] += 1;
+            continue;
+        }
+        let tile = to_tile(number, suit)?;
+        let count = &mut tile_counts[tile as usize];
+        *count = count
+            .checked_add(1)
+            .ok_or(ParseTileError::TooManyCopies(tile))?;
+    }
+    Ok(tile_counts)
+}
+
+/// Parses a string in MPSZ notation (e.g., `"1m456p789s12z"`) into [`TileFlags`].
+///
+/// The notation is the same as [`parse_tile_counts`].
+/// Tiles that appear more than once are set only once.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the string is not in MPSZ notation.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{ParseTileError, parse_tile_flags};
+/// # fn main() -> Result<(), ParseTileError> {
+/// let tiles = parse_tile_flags("1m456p789s12z")?;
+/// assert_eq!(tiles, 0b0000011_111000000_000111000_000000001);
+/// # Ok(())
+/// # }
+/// ```
+pub fn parse_tile_flags(tiles: &str) -> Result<TileFlags, ParseTileError> {
+    parse_tiles(tiles).try_fold(0, |flags, item| {
+        let (number, suit) = item?;
+        let tile = to_tile(number, suit)?;
+        Ok(flags | (1 << tile))
+    })
+}
+
+/// Writes the tiles in MPSZ notation.
+/// Red fives are written as `0` in place of the first fives of each suit.
+fn write_tiles(
+    f: &mut fmt::Formatter<'_>,
+    count: impl Fn(usize) -> u8,
+    red_fives: [u8; 3],
+) -> fmt::Result {
+    for (s, &suit) in SUITS.iter().enumerate() {
+        let (base, len) = (9 * s, if suit == 'z' { 7 } else { 9 });
+        let mut has_tiles = false;
+        for n in 0..len {
+            let num_red = if n == 4 && s < 3 { red_fives[s] } else { 0 };
+            for c in 0..count(base + n) {
+                let digit = if c < num_red { 0 } else { n + 1 };
+                write!(f, "{digit}")?;
+                has_tiles = true;
+            }
+        }
+        if has_tiles {
+            write!(f, "{suit}")?;
+        }
+    }
+    Ok(())
+}
+
+struct DisplayTileCounts<'a>(&'a TileCounts);
+
+impl fmt::Display for DisplayTileCounts<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_tiles(f, |i| self.0[i], [0; 3])
+    }
+}
+
+struct DisplayTileFlags(TileFlags);
+
+impl fmt::Display for DisplayTileFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_tiles(f, |i| ((self.0 >> i) & 1) as u8, [0; 3])
+    }
+}
+
+impl FromStr for TileCountsWithRed {
+    type Err = ParseTileError;
+
+    /// Parses a string in MPSZ notation, where red fives are written as `0m`, `0p` and `0s`.
+    fn from_str(tiles: &str) -> Result<Self, Self::Err> {
+        let mut red_fives = [0u8; 3];
+        let tile_counts = count_tiles(tiles, Some(&mut red_fives))?;
+        Ok(Self::new(&tile_counts, red_fives).expect("red fives must be 1 or less in each suit"))
+    }
+}
+
+impl fmt::Display for TileCountsWithRed {
+    /// Formats the hand in MPSZ notation, where red fives are written as `0m`, `0p` and `0s`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_tiles(f, |i| self.bingpai()[i], self.red_fives())
+    }
+}
+
+/// Formats [`TileCounts`] in MPSZ notation.
+///
+/// This is the reverse of [`parse_tile_counts`].
+/// Tiles are written in index order, and suits without tiles are omitted.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::format_tile_counts;
+/// // 123m456p789s11222z
+/// let hand: [u8; 34] = [
+///     1, 1, 1, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 1, 1, 1, // s
+///     2, 3, 0, 0, 0, 0, 0, // z
+/// ];
+///
+/// assert_eq!(format_tile_counts(&hand).to_string(), "123m456p789s11222z");
+/// ```
+pub fn format_tile_counts(tile_counts: &TileCounts) -> impl fmt::Display + '_ {
+    DisplayTileCounts(tile_counts)
+}
+
+/// Formats [`TileFlags`] in MPSZ notation.
+///
+/// This is the reverse of [`parse_tile_flags`].
+/// Bits beyond Red (7z) are ignored.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::format_tile_flags;
+/// let tiles = 0b0000011_111000000_000111000_000000001;
+/// assert_eq!(format_tile_flags(tiles).to_string(), "1m456p789s12z");
+/// ```
+pub fn format_tile_flags(tile_flags: TileFlags) -> impl fmt::Display {
+    DisplayTileFlags(tile_flags)
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn parse_tile_counts_normal() {
+        let tile_counts = parse_tile_counts("123m456p789s12344z").unwrap();
+        let expected: TileCounts = [
+            1, 1, 1, 0, 0, 0, 0, 0, 0, // m
+            0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+            0, 0, 0, 0, 0, 0, 1, 1, 1, // s
+            1, 1, 1, 2, 0, 0, 0, // z
+        ];
+        assert_eq!(tile_counts, expected);
+    }
+
+    #[test]
+    fn parse_tile_counts_repeated_suits() {
+        let tile_counts = parse_tile_counts("11m22p33s44z11m2p7s").unwrap();
+        let expected: TileCounts = [
+            4, 0, 0, 0, 0, 0, 0, 0, 0, // m
+            0, 3, 0, 0, 0, 0, 0, 0, 0, // p
+            0, 0, 2, 0, 0, 0, 1, 0, 0, // s
+            0, 0, 0, 2, 0, 0, 0, // z
+        ];
+        assert_eq!(tile_counts, expected);
+    }
+
+    #[test]
+    fn parse_tile_counts_empty() {
+        assert_eq!(parse_tile_counts(""), Ok([0u8; 34]));
+    }
+
+    #[test]
+    fn parse_tile_counts_missing_suit() {
+        assert_eq!(
+            parse_tile_counts("123m456"),
+            Err(ParseTileError::MissingSuit('6'))
+        );
+    }
+
+    #[test]
+    fn parse_tile_counts_unexpected_character() {
+        assert_eq!(
+            parse_tile_counts("123m 456p"),
+            Err(ParseTileError::UnexpectedCharacter(' '))
+        );
+        assert_eq!(
+            parse_tile_counts("123x"),
+            Err(ParseTileError::UnexpectedCharacter('x'))
+        );
+    }
+
+    #[test]
+    fn parse_tile_counts_invalid_tile() {
+        assert_eq!(
+            parse_tile_counts("0m"),
+            Err(ParseTileError::InvalidTile {
+                number: '0',
+                suit: 'm'
+            })
+        );
+        assert_eq!(
+            parse_tile_counts("8z"),
+            Err(ParseTileError::InvalidTile {
+                number: '8',
+                suit: 'z'
+            })
+        );
+    }
+
+    #[test]
+    fn parse_tile_counts_too_many_copies() {
+        let tiles = "1".repeat(256) + "z";
+        assert_eq!(
+            parse_tile_counts(&tiles),
+            Err(ParseTileError::TooManyCopies(27))
+        );
+    }
+
+    #[test]
+    fn parse_tile_counts_with_red_normal() {
+        let hand: TileCountsWithRed = "055m0p406s".parse().unwrap();
+        assert_eq!(hand.bingpai(), &parse_tile_counts("555m5p456s").unwrap());
+        assert_eq!(hand.red_fives(), [1, 1, 1]);
+    }
+
+    #[test]
+    fn parse_tile_counts_with_red_too_many_red_fives() {
+        let hand = "00p".parse::<TileCountsWithRed>();
+        assert_eq!(hand, Err(ParseTileError::TooManyRedFives(13)));
+    }
+
+    #[test]
+    fn parse_tile_counts_with_red_red_honor() {
+        let hand = "0z".parse::<TileCountsWithRed>();
+        assert_eq!(
+            hand,
+            Err(ParseTileError::InvalidTile {
+                number: '0',
+                suit: 'z'
+            })
+        );
+    }
+
+    #[test]
+    fn parse_tile_flags_repeated_suits() {
+        let tile_flags = parse_tile_flags("11m22p33s44z11m2p7s").unwrap();
+        assert_eq!(tile_flags, 0b0001000_001000100_000000010_000000001);
+    }
+
+    #[test]
+    fn parse_tile_flags_invalid_tile() {
+        assert_eq!(
+            parse_tile_flags("9z"),
+            Err(ParseTileError::InvalidTile {
+                number: '9',
+                suit: 'z'
+            })
+        );
+    }
+
+    #[test]
+    fn format_tile_counts_normal() {
+        let tile_counts = parse_tile_counts("7z33s11m2p22p44z11m7s").unwrap();
+        assert_eq!(
+            format_tile_counts(&tile_counts).to_string(),
+            "1111m222p337s447z"
+        );
+    }
+
+    #[test]
+    fn format_tile_counts_empty() {
+        assert_eq!(format_tile_counts(&[0u8; 34]).to_string(), "");
+    }
+
+    #[test]
+    fn format_tile_counts_with_red_normal() {
+        let hand: TileCountsWithRed = "5550m0p456s".parse().unwrap();
+        assert_eq!(hand.to_string(), "0555m0p456s");
+    }
+
+    #[test]
+    fn format_tile_flags_normal() {
+        let tile_flags: TileFlags = 0b1000001_000000000_100000001_000010000;
+        assert_eq!(format_tile_flags(tile_flags).to_string(), "5m19p17z");
+    }
+
+    #[test]
+    fn format_tile_flags_ignore_out_of_range() {
+        let tile_flags: TileFlags = (1 << 34) | 1;
+        assert_eq!(format_tile_flags(tile_flags).to_string(), "1m");
+    }
+}
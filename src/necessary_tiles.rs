@@ -2,19 +2,23 @@
 // SPDX-License-Identifier: MIT
 // This file is part of https://github.com/Apricot-S/xiangting
 
+use super::constrained;
 use super::qiduizi;
 use super::shisanyao;
 use super::standard;
+use crate::analysis::update_min;
 use crate::bingpai::{Bingpai, Bingpai3p, BingpaiError};
-use crate::config::PlayerCount;
+use crate::config::{Config, PlayerCount, WinningForms};
+use crate::hand::{Hand, Meld};
 use crate::tile::{TileCounts, TileFlags};
-use core::cmp::Ordering;
 
 /// Calculates the replacement number (= xiàngtīng number + 1) and necessary tiles for a given hand.
 ///
 /// # Arguments
 ///
-/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌).
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
 /// * `player_count` - A reference to the number of players.
 ///
 /// # Errors
@@ -70,79 +74,143 @@ use core::cmp::Ordering;
 /// ```
 #[inline]
 pub fn calculate_necessary_tiles(
-    bingpai: &TileCounts,
+    bingpai: &impl Hand,
     player_count: &PlayerCount,
 ) -> Result<(u8, TileFlags), BingpaiError> {
-    match player_count {
-        PlayerCount::Four => calculate_necessary_tiles_4p(bingpai),
-        PlayerCount::Three => calculate_necessary_tiles_3p(bingpai),
+    calculate_necessary_tiles_with_config(bingpai, &player_count.clone().into())
+}
+
+/// Calculates the replacement number (= xiàngtīng number + 1) and necessary tiles for a given hand
+/// under the given rules.
+///
+/// Only the winning forms selected in `config` are considered.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid or no selected winning form can be completed with the hand.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{Config, WinningForms, calculate_necessary_tiles_with_config};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 19m19p19s12345677z
+/// let hand: [u8; 34] = [
+///     1, 0, 0, 0, 0, 0, 0, 0, 1, // m
+///     1, 0, 0, 0, 0, 0, 0, 0, 1, // p
+///     1, 0, 0, 0, 0, 0, 0, 0, 1, // s
+///     1, 1, 1, 1, 1, 1, 2, // z
+/// ];
+///
+/// let config = Config {
+///     forms: WinningForms::STANDARD | WinningForms::QIDUIZI,
+///     ..Default::default()
+/// };
+/// let (replacement_number, necessary_tiles) = calculate_necessary_tiles_with_config(&hand, &config)?;
+/// assert_eq!(replacement_number, 6u8);
+/// assert_eq!(necessary_tiles, 0b0111111_100000001_100000001_100000001); // 19m19p19s123456z
+/// # Ok(())
+/// # }
+/// ```
+pub fn calculate_necessary_tiles_with_config(
+    bingpai: &impl Hand,
+    config: &Config,
+) -> Result<(u8, TileFlags), BingpaiError> {
+    let (replacement_number, necessary_tiles) =
+        if !config.uses_tables() || bingpai.num_wildcards() > 0 {
+            let num_wildcards = bingpai.num_wildcards();
+            constrained::analyze(bingpai.bingpai(), bingpai.melds(), num_wildcards, config)
+                .map(|a| (a.replacement_number, a.necessary_tiles))
+        } else {
+            match config.player_count {
+                PlayerCount::Four => {
+                    calculate_necessary_tiles_4p(bingpai.bingpai(), bingpai.melds(), config)
+                }
+                PlayerCount::Three => {
+                    calculate_necessary_tiles_3p(bingpai.bingpai(), bingpai.melds(), config)
+                }
+            }
+        }?;
+
+    match replacement_number {
+        u8::MAX => Err(BingpaiError::NoReachableForm),
+        r => Ok((r, necessary_tiles)),
     }
 }
 
-fn calculate_necessary_tiles_4p(tile_counts: &TileCounts) -> Result<(u8, TileFlags), BingpaiError> {
-    let bingpai = Bingpai::new(tile_counts)?;
+fn calculate_necessary_tiles_4p(
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    config: &Config,
+) -> Result<(u8, TileFlags), BingpaiError> {
+    let bingpai = Bingpai::with_melds(tile_counts, melds)?;
+
+    let mut result = (u8::MAX, 0);
 
-    let (mut replacement_number, mut necessary_tiles) =
-        standard::calculate_necessary_tiles(&bingpai);
+    if config.forms.contains(WinningForms::STANDARD) {
+        update_min(&mut result, standard::calculate_necessary_tiles(&bingpai));
+    }
 
-    let (r1, n1) = qiduizi::calculate_necessary_tiles(&bingpai);
-    match r1.cmp(&replacement_number) {
-        Ordering::Less => {
-            replacement_number = r1;
-            necessary_tiles = n1;
-        }
-        Ordering::Equal => necessary_tiles |= n1,
-        Ordering::Greater => (),
+    if config.forms.contains(WinningForms::QIDUIZI) {
+        update_min(
+            &mut result,
+            qiduizi::calculate_necessary_tiles(&bingpai, config.qiduizi_quads_as_two_pairs),
+        );
     }
 
-    let (r2, n2) = shisanyao::calculate_necessary_tiles(&bingpai);
-    match r2.cmp(&replacement_number) {
-        Ordering::Less => {
-            replacement_number = r2;
-            necessary_tiles = n2;
-        }
-        Ordering::Equal => necessary_tiles |= n2,
-        Ordering::Greater => (),
+    if config.forms.contains(WinningForms::SHISANYAO) {
+        update_min(&mut result, shisanyao::calculate_necessary_tiles(&bingpai));
     }
 
-    Ok((replacement_number, necessary_tiles))
+    Ok(result)
 }
 
-fn calculate_necessary_tiles_3p(tile_counts: &TileCounts) -> Result<(u8, TileFlags), BingpaiError> {
-    let bingpai_3p = Bingpai3p::new(tile_counts)?;
+fn calculate_necessary_tiles_3p(
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    config: &Config,
+) -> Result<(u8, TileFlags), BingpaiError> {
+    let bingpai_3p = Bingpai3p::with_melds(tile_counts, melds)?;
 
-    let (mut replacement_number, mut necessary_tiles) =
-        standard::calculate_necessary_tiles_3p(&bingpai_3p);
+    let mut result = (u8::MAX, 0);
 
-    let (r1, n1) = qiduizi::calculate_necessary_tiles_3p(&bingpai_3p);
-    match r1.cmp(&replacement_number) {
-        Ordering::Less => {
-            replacement_number = r1;
-            necessary_tiles = n1;
-        }
-        Ordering::Equal => necessary_tiles |= n1,
-        Ordering::Greater => (),
+    if config.forms.contains(WinningForms::STANDARD) {
+        update_min(
+            &mut result,
+            standard::calculate_necessary_tiles_3p(&bingpai_3p),
+        );
+    }
+
+    if config.forms.contains(WinningForms::QIDUIZI) {
+        update_min(
+            &mut result,
+            qiduizi::calculate_necessary_tiles_3p(&bingpai_3p, config.qiduizi_quads_as_two_pairs),
+        );
     }
 
     let bingpai = bingpai_3p.into();
 
-    let (r2, n2) = shisanyao::calculate_necessary_tiles(&bingpai);
-    match r2.cmp(&replacement_number) {
-        Ordering::Less => {
-            replacement_number = r2;
-            necessary_tiles = n2;
-        }
-        Ordering::Equal => necessary_tiles |= n2,
-        Ordering::Greater => (),
+    if config.forms.contains(WinningForms::SHISANYAO) {
+        update_min(&mut result, shisanyao::calculate_necessary_tiles(&bingpai));
     }
 
-    Ok((replacement_number, necessary_tiles))
+    Ok(result)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::bingpai::BingpaiError;
+    use crate::config::Suit;
+    use crate::hand::HandWithWildcards;
     use crate::test_utils::FromTileCode;
 
     #[test]
@@ -253,4 +321,174 @@ mod tests {
             Err(BingpaiError::InvalidTileForThreePlayer(1))
         ));
     }
+
+    #[test]
+    fn calculate_necessary_tiles_with_config_qiduizi_only() {
+        let bingpai = TileCounts::from_code("19m19p19s12345677z");
+        let config = Config {
+            forms: WinningForms::QIDUIZI,
+            ..Default::default()
+        };
+        let ret = calculate_necessary_tiles_with_config(&bingpai, &config);
+        let (replacement_number, necessary_tiles) = ret.unwrap();
+        assert_eq!(replacement_number, 6);
+        assert_eq!(necessary_tiles, TileFlags::from_code("19m19p19s123456z"));
+    }
+
+    #[test]
+    fn calculate_necessary_tiles_with_config_allowed_tiles() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        // Simples only (断么九)
+        let config = Config {
+            allowed_tiles: TileFlags::from_code("2345678m2345678p2345678s"),
+            ..Default::default()
+        };
+        let ret = calculate_necessary_tiles_with_config(&bingpai, &config);
+        let (replacement_number, necessary_tiles) = ret.unwrap();
+        assert_eq!(replacement_number, 7);
+        assert_eq!(necessary_tiles, config.allowed_tiles);
+    }
+
+    #[test]
+    fn calculate_necessary_tiles_with_config_duiduihe() {
+        let bingpai = TileCounts::from_code("123m111p222s3344z");
+        let config = Config {
+            forms: WinningForms::DUIDUIHE,
+            ..Default::default()
+        };
+        let ret = calculate_necessary_tiles_with_config(&bingpai, &config);
+        let (replacement_number, necessary_tiles) = ret.unwrap();
+        assert_eq!(replacement_number, 3);
+        assert_eq!(necessary_tiles, TileFlags::from_code("123m34z"));
+    }
+
+    #[test]
+    fn calculate_necessary_tiles_with_config_pinghe() {
+        let bingpai = TileCounts::from_code("234m567p345s78s11z");
+        let config = Config {
+            forms: WinningForms::PINGHE,
+            ..Default::default()
+        };
+        let ret = calculate_necessary_tiles_with_config(&bingpai, &config);
+        let (replacement_number, necessary_tiles) = ret.unwrap();
+        assert_eq!(replacement_number, 1);
+        assert_eq!(necessary_tiles, TileFlags::from_code("69s"));
+    }
+
+    #[test]
+    fn calculate_necessary_tiles_with_config_qiduizi_quads() {
+        let bingpai = TileCounts::from_code("1111m222p3344s556z");
+        let config = Config {
+            forms: WinningForms::QIDUIZI,
+            qiduizi_quads_as_two_pairs: true,
+            ..Default::default()
+        };
+        let ret = calculate_necessary_tiles_with_config(&bingpai, &config);
+        let (replacement_number, necessary_tiles) = ret.unwrap();
+        assert_eq!(replacement_number, 1);
+        assert_eq!(necessary_tiles, TileFlags::from_code("2p6z"));
+    }
+
+    #[test]
+    fn calculate_necessary_tiles_with_config_sichuan() {
+        let bingpai = TileCounts::from_code("123456789m1p233s");
+        let config = Config {
+            void_suit: Some(Suit::Suozi),
+            ..Default::default()
+        };
+        let ret = calculate_necessary_tiles_with_config(&bingpai, &config);
+        let (replacement_number, necessary_tiles) = ret.unwrap();
+        // 123456789m + 11p + a meld of pinzu
+        assert_eq!(replacement_number, 4);
+        assert_eq!(necessary_tiles & Suit::Suozi.tiles(), 0);
+    }
+
+    #[test]
+    fn calculate_necessary_tiles_with_config_knitted() {
+        let bingpai = TileCounts::from_code("147m258p369s1234z");
+        let config = Config {
+            forms: WinningForms::QUANBUKAO | WinningForms::QIXINGBUKAO,
+            ..Default::default()
+        };
+        let ret = calculate_necessary_tiles_with_config(&bingpai, &config);
+        let (replacement_number, necessary_tiles) = ret.unwrap();
+        assert_eq!(replacement_number, 1);
+        assert_eq!(necessary_tiles, TileFlags::from_code("567z"));
+
+        let bingpai = TileCounts::from_code("147m258p36s123s11z");
+        let config = Config {
+            forms: WinningForms::ZUHELONG,
+            ..Default::default()
+        };
+        let ret = calculate_necessary_tiles_with_config(&bingpai, &config);
+        let (replacement_number, necessary_tiles) = ret.unwrap();
+        assert_eq!(replacement_number, 1);
+        assert_eq!(necessary_tiles, TileFlags::from_code("9s"));
+    }
+
+    #[test]
+    fn calculate_necessary_tiles_with_config_quandai() {
+        let bingpai = TileCounts::from_code("123789m123p99s11z");
+        let config = Config {
+            forms: WinningForms::HUNQUANDAI,
+            ..Default::default()
+        };
+        let ret = calculate_necessary_tiles_with_config(&bingpai, &config);
+        let (replacement_number, necessary_tiles) = ret.unwrap();
+        assert_eq!(replacement_number, 1);
+        assert_eq!(necessary_tiles, TileFlags::from_code("9s1z"));
+    }
+
+    #[test]
+    fn calculate_necessary_tiles_with_config_err_no_forms() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let config = Config {
+            forms: WinningForms::empty(),
+            ..Default::default()
+        };
+        let ret = calculate_necessary_tiles_with_config(&bingpai, &config);
+        assert!(matches!(ret, Err(BingpaiError::NoReachableForm)));
+    }
+
+    #[test]
+    fn calculate_necessary_tiles_with_wildcards() {
+        // Only 1 wildcard, which makes a pair with any tile
+        let bingpai = [0; 34];
+        let hand = HandWithWildcards {
+            hand: &bingpai,
+            num_wildcards: 1,
+        };
+        let (replacement_number, necessary_tiles) =
+            calculate_necessary_tiles(&hand, &PlayerCount::Four).unwrap();
+        assert_eq!(replacement_number, 1);
+        assert_eq!(
+            necessary_tiles,
+            TileFlags::from_code("123456789m123456789p123456789s1234567z")
+        );
+
+        // 123m456p789s113z + 1 wildcard
+        let bingpai = TileCounts::from_code("123m456p789s113z");
+        let hand = HandWithWildcards {
+            hand: &bingpai,
+            num_wildcards: 1,
+        };
+        let (replacement_number, necessary_tiles) =
+            calculate_necessary_tiles(&hand, &PlayerCount::Four).unwrap();
+        assert_eq!(replacement_number, 1);
+        assert_eq!(necessary_tiles, TileFlags::from_code("13z"));
+    }
+
+    #[test]
+    fn calculate_necessary_tiles_with_config_pair_tiles() {
+        let bingpai = TileCounts::from_code("123m456p789s2225m");
+        // 二五八将
+        let config = Config {
+            pair_tiles: TileFlags::from_code("258m258p258s"),
+            ..Default::default()
+        };
+        let (replacement_number, necessary_tiles) =
+            calculate_necessary_tiles_with_config(&bingpai, &config).unwrap();
+        assert_eq!(replacement_number, 1);
+        assert_eq!(necessary_tiles, TileFlags::from_code("5m"));
+    }
 }
@@ -0,0 +1,628 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use super::constrained;
+use crate::bingpai::BingpaiError;
+use crate::config::{Config, PlayerCount};
+use crate::hand::{FullHand, Hand, Meld};
+use crate::necessary_tiles::calculate_necessary_tiles_with_config;
+use crate::rng::SplitMix64;
+use crate::tile::{NUM_TILE_INDEX, TileCounts, TileFlags, TileFlagsExt};
+use crate::ukeire::{
+    MAX_TILE_COPIES, count_remaining_tiles, count_unavailable_tiles, is_available,
+};
+use crate::unnecessary_tiles::calculate_unnecessary_tiles_with_config;
+use alloc::collections::BTreeMap;
+
+/// The method of calculating the probabilities.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbabilityMethod {
+    /// Enumerates all sequences of draws.
+    ///
+    /// The results are memoized for each hand and set of drawn tiles,
+    /// but the cost still grows exponentially with the number of draws.
+    Exact,
+    /// Simulates the given number of random sequences of draws.
+    MonteCarlo {
+        /// The number of simulated sequences.
+        num_trials: u32,
+        /// The seed of the pseudo-random number generator.
+        seed: u64,
+    },
+}
+
+/// The probabilities of reaching tenpai (聴牌) and winning (和了) within a number of draws.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DrawProbability {
+    /// The probability that the replacement number becomes 1 or less.
+    pub tenpai: f64,
+    /// The probability that the replacement number becomes 0.
+    pub win: f64,
+}
+
+/// Calculates the probabilities of reaching tenpai (聴牌) and winning (和了)
+/// within a given number of draws from the wall.
+///
+/// The wall consists of all copies of the tiles that are neither in the hand nor visible,
+/// and each of them is equally likely to be drawn.
+/// After each draw, the following policy is applied:
+///
+/// - If the drawn tile is not a necessary tile, it is discarded.
+/// - Otherwise, the tile that keeps the replacement number minimum is discarded.
+///   If several tiles do so, the one that maximizes the acceptance (受け入れ) is chosen,
+///   and the one with the lowest index among them.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `visible` - A reference to the counts of the visible tiles other than the hand
+///   (e.g., discards, melds of other players and dora indicators).
+///   If `None`, only the copies held in the hand are taken into account.
+/// * `num_draws` - The number of remaining draws.
+/// * `method` - A reference to the method of calculating the probabilities.
+/// * `player_count` - A reference to the number of players.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid, the number of tiles in the pure hand is not 3n+1,
+/// or the hand and the visible tiles contain more than 4 copies of a tile.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{PlayerCount, ProbabilityMethod, calculate_draw_probability};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 123m456p789s1112z
+/// let hand: [u8; 34] = [
+///     1, 1, 1, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 1, 1, 1, // s
+///     3, 1, 0, 0, 0, 0, 0, // z
+/// ];
+///
+/// let probability =
+///     calculate_draw_probability(&hand, None, 1, &ProbabilityMethod::Exact, &PlayerCount::Four)?;
+/// assert_eq!(probability.tenpai, 1.0);
+/// // 3 copies of 2z in 123 tiles
+/// assert_eq!(probability.win, 3.0 / 123.0);
+/// # Ok(())
+/// # }
+/// ```
+#[inline]
+pub fn calculate_draw_probability(
+    bingpai: &impl Hand,
+    visible: Option<&TileCounts>,
+    num_draws: u8,
+    method: &ProbabilityMethod,
+    player_count: &PlayerCount,
+) -> Result<DrawProbability, BingpaiError> {
+    calculate_draw_probability_with_config(
+        bingpai,
+        visible,
+        num_draws,
+        method,
+        &player_count.clone().into(),
+    )
+}
+
+/// Calculates the probabilities of reaching tenpai (聴牌) and winning (和了)
+/// within a given number of draws from the wall under the given rules.
+///
+/// See [`calculate_draw_probability`] for the model of the wall and the discard policy.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `visible` - A reference to the counts of the visible tiles other than the hand
+///   (e.g., discards, melds of other players and dora indicators).
+///   If `None`, only the copies held in the hand are taken into account.
+/// * `num_draws` - The number of remaining draws.
+/// * `method` - A reference to the method of calculating the probabilities.
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid, the number of tiles in the pure hand is not 3n+1,
+/// no selected winning form can be completed with the hand,
+/// or the hand and the visible tiles contain more than 4 copies of a tile.
+pub fn calculate_draw_probability_with_config(
+    bingpai: &impl Hand,
+    visible: Option<&TileCounts>,
+    num_draws: u8,
+    method: &ProbabilityMethod,
+    config: &Config,
+) -> Result<DrawProbability, BingpaiError> {
+    constrained::check_no_wildcards(bingpai.num_wildcards(), config)?;
+    // Validate first so that the tile count cannot overflow.
+    constrained::validate(bingpai.bingpai(), bingpai.melds(), 0, config)?;
+    let num_bingpai: u8 = bingpai.bingpai().iter().sum();
+    if num_bingpai % 3 != 1 {
+        return Err(BingpaiError::InvalidTileCountForDraw(num_bingpai));
+    }
+
+    let visible = visible.unwrap_or(&[0; NUM_TILE_INDEX]);
+    let unavailable = count_unavailable_tiles(bingpai, visible)?;
+    let (replacement_number, necessary_tiles) =
+        calculate_necessary_tiles_with_config(bingpai, config)?;
+
+    let simulator = Simulator {
+        melds: bingpai.melds(),
+        config,
+    };
+    let state = State {
+        bingpai: *bingpai.bingpai(),
+        unavailable,
+        replacement_number,
+        necessary_tiles,
+    };
+
+    match *method {
+        ProbabilityMethod::Exact => {
+            simulator.calculate_exact(&state, num_draws, &mut BTreeMap::new())
+        }
+        ProbabilityMethod::MonteCarlo { num_trials, seed } => {
+            simulator.simulate(&state, num_draws, num_trials, seed)
+        }
+    }
+}
+
+#[derive(Clone)]
+struct State {
+    bingpai: TileCounts,
+    unavailable: TileCounts,
+    replacement_number: u8,
+    necessary_tiles: TileFlags,
+}
+
+struct Simulator<'a> {
+    melds: &'a [Meld],
+    config: &'a Config,
+}
+
+impl Simulator<'_> {
+    fn count_wall_tiles(&self, unavailable: &TileCounts) -> TileCounts {
+        core::array::from_fn(|i| {
+            if is_available(i, self.config) {
+                MAX_TILE_COPIES - unavailable[i]
+            } else {
+                0
+            }
+        })
+    }
+
+    /// Draws a tile and discards a tile according to the policy.
+    ///
+    /// Returns `true` if the hand is complete with the drawn tile.
+    fn draw(&self, state: &mut State, tile: usize) -> Result<bool, BingpaiError> {
+        state.unavailable[tile] += 1;
+        if state.necessary_tiles & (1 << tile) == 0 {
+            // The drawn tile is discarded.
+            return Ok(false);
+        }
+
+        state.bingpai[tile] += 1;
+        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles_with_config(
+            &FullHand {
+                bingpai: &state.bingpai,
+                melds: self.melds,
+            },
+            self.config,
+        )?;
+        if replacement_number == 0 {
+            return Ok(true);
+        }
+
+        // (discarded tile, necessary tiles, acceptance) of the best discard
+        let mut best: Option<(usize, TileFlags, u8)> = None;
+        for (i, is_unnecessary) in unnecessary_tiles.to_array().into_iter().enumerate() {
+            if !is_unnecessary {
+                continue;
+            }
+
+            state.bingpai[i] -= 1;
+            let (_, necessary_tiles) = calculate_necessary_tiles_with_config(
+                &FullHand {
+                    bingpai: &state.bingpai,
+                    melds: self.melds,
+                },
+                self.config,
+            )?;
+            state.bingpai[i] += 1;
+
+            let (_, num_remaining_tiles) =
+                count_remaining_tiles(necessary_tiles, &state.unavailable);
+            if best.is_none_or(|(_, _, n)| num_remaining_tiles > n) {
+                best = Some((i, necessary_tiles, num_remaining_tiles));
+            }
+        }
+
+        // A hand that is not complete always has an unnecessary tile.
+        let (discarded, necessary_tiles, _) = best.unwrap();
+        state.bingpai[discarded] -= 1;
+        state.replacement_number = replacement_number;
+        state.necessary_tiles = necessary_tiles;
+        Ok(false)
+    }
+
+    /// Calculates the probabilities exactly.
+    ///
+    /// Every drawn tile is removed from the wall.
+    /// Since the necessary tiles depend only on the hand,
+    /// the results are memoized on the hand, the unavailable tiles and the number of draws.
+    fn calculate_exact(
+        &self,
+        state: &State,
+        num_draws: u8,
+        memo: &mut BTreeMap<(TileCounts, TileCounts, u8), DrawProbability>,
+    ) -> Result<DrawProbability, BingpaiError> {
+        let is_tenpai = state.replacement_number <= 1;
+        if is_tenpai && state.replacement_number > num_draws {
+            return Ok(DrawProbability {
+                tenpai: 1.0,
+                win: 0.0,
+            });
+        }
+        if state.replacement_number - 1 > num_draws {
+            return Ok(DrawProbability {
+                tenpai: 0.0,
+                win: 0.0,
+            });
+        }
+
+        let wall = self.count_wall_tiles(&state.unavailable);
+        let num_wall_tiles: u32 = wall.iter().map(|&c| u32::from(c)).sum();
+        if num_draws == 0 || num_wall_tiles == 0 {
+            return Ok(DrawProbability {
+                tenpai: f64::from(u8::from(is_tenpai)),
+                win: 0.0,
+            });
+        }
+
+        let key = (state.bingpai, state.unavailable, num_draws);
+        if let Some(&probability) = memo.get(&key) {
+            return Ok(probability);
+        }
+
+        let mut probability = DrawProbability {
+            tenpai: 0.0,
+            win: 0.0,
+        };
+        for (i, &c) in wall.iter().enumerate() {
+            if c == 0 {
+                continue;
+            }
+
+            let weight = f64::from(c) / f64::from(num_wall_tiles);
+            let mut next_state = state.clone();
+            let next = if self.draw(&mut next_state, i)? {
+                DrawProbability {
+                    tenpai: 1.0,
+                    win: 1.0,
+                }
+            } else {
+                self.calculate_exact(&next_state, num_draws - 1, memo)?
+            };
+            probability.tenpai += weight * next.tenpai;
+            probability.win += weight * next.win;
+        }
+        if is_tenpai {
+            // Avoid the rounding errors of the sum.
+            probability.tenpai = 1.0;
+        }
+
+        memo.insert(key, probability);
+        Ok(probability)
+    }
+
+    fn simulate(
+        &self,
+        state: &State,
+        num_draws: u8,
+        num_trials: u32,
+        seed: u64,
+    ) -> Result<DrawProbability, BingpaiError> {
+        if num_trials == 0 {
+            return Ok(DrawProbability {
+                tenpai: 0.0,
+                win: 0.0,
+            });
+        }
+
+        let mut rng = SplitMix64(seed);
+        let mut num_tenpai = 0u32;
+        let mut num_win = 0u32;
+
+        for _ in 0..num_trials {
+            let mut state = state.clone();
+            let mut wall = self.count_wall_tiles(&state.unavailable);
+            let mut num_wall_tiles: u32 = wall.iter().map(|&c| u32::from(c)).sum();
+            let mut is_tenpai = state.replacement_number <= 1;
+            let mut is_win = false;
+
+            for num_remaining_draws in (1..=num_draws).rev() {
+                // Tenpai cannot be reached, and thus neither can winning.
+                if num_wall_tiles == 0
+                    || (!is_tenpai && state.replacement_number - 1 > num_remaining_draws)
+                {
+                    break;
+                }
+
+                let tile = pick_tile(&wall, rng.next_below(num_wall_tiles));
+                wall[tile] -= 1;
+                num_wall_tiles -= 1;
+
+                if self.draw(&mut state, tile)? {
+                    is_win = true;
+                    is_tenpai = true;
+                    break;
+                }
+                is_tenpai |= state.replacement_number <= 1;
+            }
+
+            num_tenpai += u32::from(is_tenpai);
+            num_win += u32::from(is_win);
+        }
+
+        Ok(DrawProbability {
+            tenpai: f64::from(num_tenpai) / f64::from(num_trials),
+            win: f64::from(num_win) / f64::from(num_trials),
+        })
+    }
+}
+
+/// Returns the tile at the given position when the tiles in the wall are lined up in index order.
+fn pick_tile(wall: &TileCounts, mut position: u32) -> usize {
+    for (i, &c) in wall.iter().enumerate() {
+        if position < u32::from(c) {
+            return i;
+        }
+        position -= u32::from(c);
+    }
+    unreachable!("position must be less than the number of tiles in the wall")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Suit;
+    use crate::test_utils::FromTileCode;
+
+    fn exact(code: &str, visible: &str, num_draws: u8) -> DrawProbability {
+        let bingpai = TileCounts::from_code(code);
+        let visible = TileCounts::from_code(visible);
+        calculate_draw_probability(
+            &bingpai,
+            Some(&visible),
+            num_draws,
+            &ProbabilityMethod::Exact,
+            &PlayerCount::Four,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn calculate_draw_probability_tanki() {
+        let probability = exact("123m456p789s1112z", "", 2);
+        assert_eq!(probability.tenpai, 1.0);
+        let miss = (120.0 / 123.0) * (119.0 / 122.0);
+        assert!((probability.win - (1.0 - miss)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn calculate_draw_probability_no_draws() {
+        let probability = exact("123m456p789s1112z", "", 0);
+        assert_eq!(probability.tenpai, 1.0);
+        assert_eq!(probability.win, 0.0);
+
+        let probability = exact("123m456p789s1234z", "", 0);
+        assert_eq!(probability.tenpai, 0.0);
+        assert_eq!(probability.win, 0.0);
+    }
+
+    #[test]
+    fn calculate_draw_probability_all_visible() {
+        // All copies of 2z are visible.
+        let probability = exact("123m456p789s1112z", "222z", 3);
+        assert_eq!(probability.tenpai, 1.0);
+        assert_eq!(probability.win, 0.0);
+    }
+
+    #[test]
+    fn calculate_draw_probability_iishanten() {
+        // 1-shanten: 1z, 3z or 4z makes tenpai.
+        let probability = exact("123m456p789s1134z", "", 1);
+        assert!((probability.tenpai - 8.0 / 123.0).abs() < 1e-12);
+        assert_eq!(probability.win, 0.0);
+
+        let probability = exact("123m456p789s1134z", "", 2);
+        assert!(probability.tenpai > 8.0 / 123.0);
+        assert!(probability.win > 0.0);
+        assert!(probability.win < probability.tenpai);
+    }
+
+    /// Counts the sequences of draws of distinct copies in the wall
+    /// that reach tenpai and winning, out of all such sequences.
+    fn brute_force(
+        simulator: &Simulator,
+        state: &State,
+        wall: &[usize],
+        sequence: &mut [usize],
+        num_drawn: usize,
+    ) -> (u32, u32, u32) {
+        if num_drawn == sequence.len() {
+            let mut state = state.clone();
+            let mut is_tenpai = state.replacement_number <= 1;
+            for &k in sequence.iter() {
+                if simulator.draw(&mut state, wall[k]).unwrap() {
+                    return (1, 1, 1);
+                }
+                is_tenpai |= state.replacement_number <= 1;
+            }
+            return (u32::from(is_tenpai), 0, 1);
+        }
+
+        let mut counts = (0, 0, 0);
+        for k in 0..wall.len() {
+            if sequence[..num_drawn].contains(&k) {
+                continue;
+            }
+            sequence[num_drawn] = k;
+            let (t, w, n) = brute_force(simulator, state, wall, sequence, num_drawn + 1);
+            counts = (counts.0 + t, counts.1 + w, counts.2 + n);
+        }
+        counts
+    }
+
+    #[test]
+    fn calculate_draw_probability_matches_brute_force() {
+        // The wall is 9m1z33z4z55z.
+        let bingpai = TileCounts::from_code("123m456p789s1134z");
+        let mut visible = [MAX_TILE_COPIES; NUM_TILE_INDEX];
+        let wall = [8, 27, 29, 29, 30, 31, 31];
+        for (i, v) in visible.iter_mut().enumerate() {
+            *v -= bingpai[i] + wall.iter().filter(|&&t| t == i).count() as u8;
+        }
+
+        let config = Config::default();
+        let simulator = Simulator {
+            melds: &[],
+            config: &config,
+        };
+        let (replacement_number, necessary_tiles) =
+            calculate_necessary_tiles_with_config(&bingpai, &config).unwrap();
+        let state = State {
+            bingpai,
+            unavailable: count_unavailable_tiles(&bingpai, &visible).unwrap(),
+            replacement_number,
+            necessary_tiles,
+        };
+
+        for num_draws in 1..=4 {
+            let mut sequence = [0; 4];
+            let sequence = &mut sequence[..num_draws];
+            let (num_tenpai, num_win, num_sequences) =
+                brute_force(&simulator, &state, &wall, sequence, 0);
+            let probability = calculate_draw_probability(
+                &bingpai,
+                Some(&visible),
+                num_draws as u8,
+                &ProbabilityMethod::Exact,
+                &PlayerCount::Four,
+            )
+            .unwrap();
+            let expected_tenpai = f64::from(num_tenpai) / f64::from(num_sequences);
+            let expected_win = f64::from(num_win) / f64::from(num_sequences);
+            assert!((probability.tenpai - expected_tenpai).abs() < 1e-12);
+            assert!((probability.win - expected_win).abs() < 1e-12);
+            assert!(num_tenpai > 0);
+            assert!(num_win > 0 || num_draws < 2);
+        }
+    }
+
+    #[test]
+    fn calculate_draw_probability_monte_carlo_close_to_exact() {
+        let bingpai = TileCounts::from_code("123m456p789s1134z");
+        let expected = exact("123m456p789s1134z", "", 3);
+        let probability = calculate_draw_probability(
+            &bingpai,
+            None,
+            3,
+            &ProbabilityMethod::MonteCarlo {
+                num_trials: 100_000,
+                seed: 42,
+            },
+            &PlayerCount::Four,
+        )
+        .unwrap();
+        assert!((probability.tenpai - expected.tenpai).abs() < 0.01);
+        assert!((probability.win - expected.win).abs() < 0.01);
+    }
+
+    #[test]
+    fn calculate_draw_probability_monte_carlo_is_deterministic() {
+        let bingpai = TileCounts::from_code("1367m2589p2468s1z");
+        let method = ProbabilityMethod::MonteCarlo {
+            num_trials: 1_000,
+            seed: 1,
+        };
+        let probability1 =
+            calculate_draw_probability(&bingpai, None, 10, &method, &PlayerCount::Four).unwrap();
+        let probability2 =
+            calculate_draw_probability(&bingpai, None, 10, &method, &PlayerCount::Four).unwrap();
+        assert_eq!(probability1, probability2);
+    }
+
+    #[test]
+    fn calculate_draw_probability_3p_excludes_2m_to_8m() {
+        // 1-shanten: 1z, 3z or 4z makes tenpai, and 2m-8m are not in the wall.
+        let bingpai = TileCounts::from_code("111m456p789s1134z");
+        let probability = calculate_draw_probability(
+            &bingpai,
+            None,
+            1,
+            &ProbabilityMethod::Exact,
+            &PlayerCount::Three,
+        )
+        .unwrap();
+        assert!((probability.tenpai - 8.0 / 95.0).abs() < 1e-12);
+        assert_eq!(probability.win, 0.0);
+    }
+
+    #[test]
+    fn calculate_draw_probability_err_3n_plus_2() {
+        let bingpai = TileCounts::from_code("123m456p789s11223z");
+        let probability = calculate_draw_probability(
+            &bingpai,
+            None,
+            1,
+            &ProbabilityMethod::Exact,
+            &PlayerCount::Four,
+        );
+        assert!(matches!(
+            probability,
+            Err(BingpaiError::InvalidTileCountForDraw(14))
+        ));
+    }
+
+    #[test]
+    fn calculate_draw_probability_err_too_many_copies() {
+        let bingpai = [8; 34];
+        let probability = calculate_draw_probability(
+            &bingpai,
+            None,
+            1,
+            &ProbabilityMethod::Exact,
+            &PlayerCount::Four,
+        );
+        assert!(matches!(
+            probability,
+            Err(BingpaiError::TooManyCopies { tile: 0, count: 8 })
+        ));
+    }
+
+    #[test]
+    fn calculate_draw_probability_sichuan() {
+        let bingpai = TileCounts::from_code("123p456p789s11s23s");
+        let config = Config {
+            void_suit: Some(Suit::Wanzi),
+            ..Default::default()
+        };
+        let probability = calculate_draw_probability_with_config(
+            &bingpai,
+            None,
+            1,
+            &ProbabilityMethod::Exact,
+            &config,
+        )
+        .unwrap();
+        // 6 winning tiles in the wall of 108 - 13 tiles without honors
+        assert_eq!(probability.tenpai, 1.0);
+        assert!((probability.win - 6.0 / 95.0).abs() < 1e-12);
+    }
+}
@@ -5,11 +5,18 @@
 use crate::bingpai::{Bingpai, Bingpai3p};
 use crate::tile::TileFlags;
 
-pub(in super::super) fn calculate_necessary_tiles(bingpai: &Bingpai) -> (u8, TileFlags) {
+pub(in super::super) fn calculate_necessary_tiles(
+    bingpai: &Bingpai,
+    quads_as_two_pairs: bool,
+) -> (u8, TileFlags) {
     if bingpai.num_required_bingpai_mianzi() < 4 {
         return (u8::MAX, 0);
     }
 
+    if quads_as_two_pairs {
+        return calculate_necessary_tiles_with_quads(bingpai.tile_counts().iter().enumerate());
+    }
+
     let (num_duizi, num_kinds, waits, wait_candidates) =
         bingpai.tile_counts().iter().enumerate().fold(
             (0, 0, 0u64, 0u64),
@@ -32,11 +39,24 @@ pub(in super::super) fn calculate_necessary_tiles(bingpai: &Bingpai) -> (u8, Til
     (replacement_number, necessary_tiles)
 }
 
-pub(in super::super) fn calculate_necessary_tiles_3p(bingpai: &Bingpai3p) -> (u8, TileFlags) {
+pub(in super::super) fn calculate_necessary_tiles_3p(
+    bingpai: &Bingpai3p,
+    quads_as_two_pairs: bool,
+) -> (u8, TileFlags) {
     if bingpai.num_required_bingpai_mianzi() < 4 {
         return (u8::MAX, 0);
     }
 
+    if quads_as_two_pairs {
+        return calculate_necessary_tiles_with_quads(
+            bingpai
+                .tile_counts()
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !matches!(i, 1..=7)),
+        );
+    }
+
     let (num_duizi, num_kinds, waits, wait_candidates) = bingpai
         .tile_counts()
         .iter()
@@ -63,6 +83,46 @@ pub(in super::super) fn calculate_necessary_tiles_3p(bingpai: &Bingpai3p) -> (u8
     (replacement_number, necessary_tiles)
 }
 
+/// Calculates the necessary tiles when four identical tiles count as two pairs.
+///
+/// Each kind makes up to two pairs, so the kinds do not need to be different.
+fn calculate_necessary_tiles_with_quads<'a>(
+    tile_counts: impl Iterator<Item = (usize, &'a u8)>,
+) -> (u8, TileFlags) {
+    let (num_duizi, num_danqi, waits, wait_candidates) = tile_counts.fold(
+        (0, 0, 0u64, 0u64),
+        |(num_duizi, num_danqi, waits, wait_candidates), (i, &count)| match count {
+            0 | 2 => (
+                num_duizi + count / 2,
+                num_danqi,
+                waits,
+                wait_candidates | (1 << i),
+            ),
+            1 | 3 => (
+                num_duizi + count / 2,
+                num_danqi + 1,
+                waits | (1 << i),
+                wait_candidates,
+            ),
+            4 => (num_duizi + 2, num_danqi, waits, wait_candidates),
+            _ => unreachable!("tile {i} count must be 4 or less but was {count}"),
+        },
+    );
+
+    let num_missing_duizi = 7u8.saturating_sub(num_duizi);
+    let replacement_number = num_missing_duizi + num_missing_duizi.saturating_sub(num_danqi);
+
+    let necessary_tiles = if num_missing_duizi == 0 {
+        0
+    } else if num_duizi + num_danqi < 7 {
+        waits | wait_candidates
+    } else {
+        waits
+    };
+
+    (replacement_number, necessary_tiles)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -73,7 +133,7 @@ mod tests {
     fn calculate_necessary_tiles_without_pair() {
         let tile_counts = TileCounts::from_code("19m19p19s1234567z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai);
+        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, 7);
         assert_eq!(necessary_tiles, TileFlags::from_code("19m19p19s1234567z"));
     }
@@ -82,7 +142,7 @@ mod tests {
     fn calculate_necessary_tiles_with_quadruple() {
         let tile_counts = TileCounts::from_code("1188m288p55s1111z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai);
+        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, 3);
         assert_eq!(
             necessary_tiles,
@@ -94,7 +154,7 @@ mod tests {
     fn calculate_necessary_tiles_with_triplet() {
         let tile_counts = TileCounts::from_code("1188m2388p55s111z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai);
+        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, 2);
         assert_eq!(necessary_tiles, TileFlags::from_code("23p"));
     }
@@ -103,7 +163,7 @@ mod tests {
     fn calculate_necessary_tiles_with_2_triplets() {
         let tile_counts = TileCounts::from_code("1188m288p555s111z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai);
+        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, 3);
         assert_eq!(
             necessary_tiles,
@@ -115,7 +175,7 @@ mod tests {
     fn calculate_necessary_tiles_tenpai() {
         let tile_counts = TileCounts::from_code("1188m288p55s1177z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai);
+        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, 1);
         assert_eq!(necessary_tiles, TileFlags::from_code("2p"));
     }
@@ -124,7 +184,7 @@ mod tests {
     fn calculate_necessary_tiles_win() {
         let tile_counts = TileCounts::from_code("1188m2288p55s1177z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai);
+        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, 0);
         assert_eq!(necessary_tiles, TileFlags::from_code(""));
     }
@@ -133,7 +193,7 @@ mod tests {
     fn calculate_necessary_tiles_incomplete_hand() {
         let tile_counts = TileCounts::from_code("1188m55s1122z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai);
+        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, u8::MAX);
         assert_eq!(necessary_tiles, TileFlags::from_code(""));
     }
@@ -142,11 +202,38 @@ mod tests {
     fn calculate_necessary_tiles_3p_with_quadruple() {
         let tile_counts = TileCounts::from_code("288p5599s111122z");
         let bingpai = Bingpai3p::new(&tile_counts).unwrap();
-        let (replacement_number, necessary_tiles) = calculate_necessary_tiles_3p(&bingpai);
+        let (replacement_number, necessary_tiles) = calculate_necessary_tiles_3p(&bingpai, false);
         assert_eq!(replacement_number, 3);
         assert_eq!(
             necessary_tiles,
             TileFlags::from_code("19m12345679p1234678s34567z")
         );
     }
+
+    #[test]
+    fn calculate_necessary_tiles_quads_as_two_pairs() {
+        let tile_counts = TileCounts::from_code("1188m288p55s1111z");
+        let bingpai = Bingpai::new(&tile_counts).unwrap();
+        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai, true);
+        assert_eq!(replacement_number, 1);
+        assert_eq!(necessary_tiles, TileFlags::from_code("2p"));
+    }
+
+    #[test]
+    fn calculate_necessary_tiles_quads_as_two_pairs_with_triplets() {
+        let tile_counts = TileCounts::from_code("111m222p333s444z5z");
+        let bingpai = Bingpai::new(&tile_counts).unwrap();
+        let (replacement_number, necessary_tiles) = calculate_necessary_tiles(&bingpai, true);
+        assert_eq!(replacement_number, 3);
+        assert_eq!(necessary_tiles, TileFlags::from_code("1m2p3s45z"));
+    }
+
+    #[test]
+    fn calculate_necessary_tiles_3p_quads_as_two_pairs() {
+        let tile_counts = TileCounts::from_code("1111m1111p1111s1z");
+        let bingpai = Bingpai3p::new(&tile_counts).unwrap();
+        let (replacement_number, necessary_tiles) = calculate_necessary_tiles_3p(&bingpai, true);
+        assert_eq!(replacement_number, 1);
+        assert_eq!(necessary_tiles, TileFlags::from_code("1z"));
+    }
 }
@@ -4,11 +4,26 @@
 
 use crate::bingpai::Bingpai;
 
-pub(in super::super) fn calculate_replacement_number(bingpai: &Bingpai) -> u8 {
+pub(in super::super) fn calculate_replacement_number(
+    bingpai: &Bingpai,
+    quads_as_two_pairs: bool,
+) -> u8 {
     if bingpai.num_required_bingpai_mianzi() < 4 {
         return u8::MAX;
     }
 
+    if quads_as_two_pairs {
+        // Each kind makes up to two pairs, so the kinds do not need to be different.
+        let (num_duizi, num_danqi) = bingpai
+            .tile_counts()
+            .iter()
+            .fold((0, 0), |(num_duizi, num_danqi), &count| {
+                (num_duizi + count / 2, num_danqi + count % 2)
+            });
+        let num_missing_duizi = 7u8.saturating_sub(num_duizi);
+        return num_missing_duizi + num_missing_duizi.saturating_sub(num_danqi);
+    }
+
     let (num_kinds, num_duizi) = bingpai
         .tile_counts()
         .iter()
@@ -30,7 +45,7 @@ mod tests {
     fn calculate_replacement_number_without_pair() {
         let tile_counts = TileCounts::from_code("19m19p19s1234567z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let replacement_number = calculate_replacement_number(&bingpai);
+        let replacement_number = calculate_replacement_number(&bingpai, false);
         assert_eq!(replacement_number, 7);
     }
 
@@ -38,7 +53,7 @@ mod tests {
     fn calculate_replacement_number_with_quadruple() {
         let tile_counts = TileCounts::from_code("1188m288p55s1111z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let replacement_number = calculate_replacement_number(&bingpai);
+        let replacement_number = calculate_replacement_number(&bingpai, false);
         assert_eq!(replacement_number, 3);
     }
 
@@ -46,7 +61,7 @@ mod tests {
     fn calculate_replacement_number_with_triplet() {
         let tile_counts = TileCounts::from_code("1188m2388p55s111z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let replacement_number = calculate_replacement_number(&bingpai);
+        let replacement_number = calculate_replacement_number(&bingpai, false);
         assert_eq!(replacement_number, 2);
     }
 
@@ -54,7 +69,7 @@ mod tests {
     fn calculate_replacement_number_with_2_triplets() {
         let tile_counts = TileCounts::from_code("1188m288p555s111z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let replacement_number = calculate_replacement_number(&bingpai);
+        let replacement_number = calculate_replacement_number(&bingpai, false);
         assert_eq!(replacement_number, 3);
     }
 
@@ -62,7 +77,7 @@ mod tests {
     fn calculate_replacement_number_tenpai() {
         let tile_counts = TileCounts::from_code("1188m288p55s1177z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let replacement_number = calculate_replacement_number(&bingpai);
+        let replacement_number = calculate_replacement_number(&bingpai, false);
         assert_eq!(replacement_number, 1);
     }
 
@@ -70,7 +85,7 @@ mod tests {
     fn calculate_replacement_number_win() {
         let tile_counts = TileCounts::from_code("1188m2288p55s1177z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let replacement_number = calculate_replacement_number(&bingpai);
+        let replacement_number = calculate_replacement_number(&bingpai, false);
         assert_eq!(replacement_number, 0);
     }
 
@@ -78,7 +93,28 @@ mod tests {
     fn calculate_replacement_number_incomplete_hand() {
         let tile_counts = TileCounts::from_code("1188m55s1122z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let replacement_number = calculate_replacement_number(&bingpai);
+        let replacement_number = calculate_replacement_number(&bingpai, false);
         assert_eq!(replacement_number, u8::MAX);
     }
+
+    #[test]
+    fn calculate_replacement_number_quads_as_two_pairs() {
+        let tile_counts = TileCounts::from_code("1188m288p55s1111z");
+        let bingpai = Bingpai::new(&tile_counts).unwrap();
+        let replacement_number = calculate_replacement_number(&bingpai, true);
+        assert_eq!(replacement_number, 1);
+
+        let tile_counts = TileCounts::from_code("1188m2288p55s1111z");
+        let bingpai = Bingpai::new(&tile_counts).unwrap();
+        let replacement_number = calculate_replacement_number(&bingpai, true);
+        assert_eq!(replacement_number, 0);
+    }
+
+    #[test]
+    fn calculate_replacement_number_quads_as_two_pairs_with_triplets() {
+        let tile_counts = TileCounts::from_code("111m222p333s444z5z");
+        let bingpai = Bingpai::new(&tile_counts).unwrap();
+        let replacement_number = calculate_replacement_number(&bingpai, true);
+        assert_eq!(replacement_number, 3);
+    }
 }
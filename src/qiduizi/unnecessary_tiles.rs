@@ -5,11 +5,18 @@
 use crate::bingpai::{Bingpai, Bingpai3p};
 use crate::tile::TileFlags;
 
-pub(in super::super) fn calculate_unnecessary_tiles(bingpai: &Bingpai) -> (u8, TileFlags) {
+pub(in super::super) fn calculate_unnecessary_tiles(
+    bingpai: &Bingpai,
+    quads_as_two_pairs: bool,
+) -> (u8, TileFlags) {
     if bingpai.num_required_bingpai_mianzi() < 4 {
         return (u8::MAX, 0);
     }
 
+    if quads_as_two_pairs {
+        return calculate_unnecessary_tiles_with_quads(bingpai.tile_counts().iter().enumerate());
+    }
+
     let (num_duizi, num_kinds, discards, discard_candidates) =
         bingpai.tile_counts().iter().enumerate().fold(
             (0, 0, 0u64, 0u64),
@@ -43,11 +50,24 @@ pub(in super::super) fn calculate_unnecessary_tiles(bingpai: &Bingpai) -> (u8, T
     (replacement_number, unnecessary_tiles)
 }
 
-pub(in super::super) fn calculate_unnecessary_tiles_3p(bingpai: &Bingpai3p) -> (u8, TileFlags) {
+pub(in super::super) fn calculate_unnecessary_tiles_3p(
+    bingpai: &Bingpai3p,
+    quads_as_two_pairs: bool,
+) -> (u8, TileFlags) {
     if bingpai.num_required_bingpai_mianzi() < 4 {
         return (u8::MAX, 0);
     }
 
+    if quads_as_two_pairs {
+        return calculate_unnecessary_tiles_with_quads(
+            bingpai
+                .tile_counts()
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| !matches!(i, 1..=7)),
+        );
+    }
+
     let (num_duizi, num_kinds, discards, discard_candidates) = bingpai
         .tile_counts()
         .iter()
@@ -85,6 +105,48 @@ pub(in super::super) fn calculate_unnecessary_tiles_3p(bingpai: &Bingpai3p) -> (
     (replacement_number, unnecessary_tiles)
 }
 
+/// Calculates the unnecessary tiles when four identical tiles count as two pairs.
+///
+/// Each kind makes up to two pairs, so the kinds do not need to be different.
+fn calculate_unnecessary_tiles_with_quads<'a>(
+    tile_counts: impl Iterator<Item = (usize, &'a u8)>,
+) -> (u8, TileFlags) {
+    let (num_duizi, num_danqi, discards, discard_candidates) = tile_counts.fold(
+        (0, 0, 0u64, 0u64),
+        |(num_duizi, num_danqi, discards, discard_candidates), (i, &count)| match count {
+            0 => (num_duizi, num_danqi, discards, discard_candidates),
+            1 | 3 => (
+                num_duizi + count / 2,
+                num_danqi + 1,
+                discards | (1 << i),
+                discard_candidates,
+            ),
+            2 | 4 => (
+                num_duizi + count / 2,
+                num_danqi,
+                discards,
+                discard_candidates | (1 << i),
+            ),
+            _ => unreachable!("tile {i} count must be 4 or less but was {count}"),
+        },
+    );
+
+    let num_missing_duizi = 7u8.saturating_sub(num_duizi);
+    let replacement_number = num_missing_duizi + num_missing_duizi.saturating_sub(num_danqi);
+
+    // The single tiles are unnecessary if the pairs are already enough,
+    // or if more of them than needed can be completed with a draw.
+    let unnecessary_tiles = if num_duizi > 7 {
+        discards | discard_candidates
+    } else if num_duizi == 7 || num_duizi + num_danqi > 7 {
+        discards
+    } else {
+        0
+    };
+
+    (replacement_number, unnecessary_tiles)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -95,7 +157,7 @@ mod tests {
     fn calculate_unnecessary_tiles_without_pair() {
         let tile_counts = TileCounts::from_code("19m19p19s1234567z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai);
+        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, 7);
         assert_eq!(unnecessary_tiles, TileFlags::from_code("19m19p19s1234567z"));
     }
@@ -104,7 +166,7 @@ mod tests {
     fn calculate_unnecessary_tiles_with_quadruple() {
         let tile_counts = TileCounts::from_code("1188m288p55s1111z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai);
+        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, 3);
         assert_eq!(unnecessary_tiles, TileFlags::from_code("1z"));
     }
@@ -113,7 +175,7 @@ mod tests {
     fn calculate_unnecessary_tiles_with_triplet() {
         let tile_counts = TileCounts::from_code("1188m2388p55s111z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai);
+        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, 2);
         assert_eq!(unnecessary_tiles, TileFlags::from_code("1z"));
     }
@@ -122,7 +184,7 @@ mod tests {
     fn calculate_unnecessary_tiles_with_2_triplets() {
         let tile_counts = TileCounts::from_code("1188m288p555s111z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai);
+        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, 3);
         assert_eq!(unnecessary_tiles, TileFlags::from_code("5s1z"));
     }
@@ -131,7 +193,7 @@ mod tests {
     fn calculate_unnecessary_tiles_tenpai() {
         let tile_counts = TileCounts::from_code("1188m288p55s1177z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai);
+        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, 1);
         assert_eq!(unnecessary_tiles, TileFlags::from_code(""));
     }
@@ -140,7 +202,7 @@ mod tests {
     fn calculate_unnecessary_tiles_win() {
         let tile_counts = TileCounts::from_code("1188m2288p55s1177z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai);
+        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, 0);
         assert_eq!(unnecessary_tiles, TileFlags::from_code(""));
     }
@@ -149,7 +211,7 @@ mod tests {
     fn calculate_unnecessary_tiles_incomplete_hand() {
         let tile_counts = TileCounts::from_code("1188m55s1122z");
         let bingpai = Bingpai::new(&tile_counts).unwrap();
-        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai);
+        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai, false);
         assert_eq!(replacement_number, u8::MAX);
         assert_eq!(unnecessary_tiles, TileFlags::from_code(""));
     }
@@ -158,8 +220,34 @@ mod tests {
     fn calculate_unnecessary_tiles_3p_with_quadruple() {
         let tile_counts = TileCounts::from_code("1199m288p55s1111z");
         let bingpai = Bingpai3p::new(&tile_counts).unwrap();
-        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles_3p(&bingpai);
+        let (replacement_number, unnecessary_tiles) =
+            calculate_unnecessary_tiles_3p(&bingpai, false);
         assert_eq!(replacement_number, 3);
         assert_eq!(unnecessary_tiles, TileFlags::from_code("1z"));
     }
+
+    #[test]
+    fn calculate_unnecessary_tiles_quads_as_two_pairs() {
+        let tile_counts = TileCounts::from_code("1188m288p55s11117z");
+        let bingpai = Bingpai::new(&tile_counts).unwrap();
+        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai, true);
+        assert_eq!(replacement_number, 1);
+        assert_eq!(unnecessary_tiles, TileFlags::from_code("2p7z"));
+
+        let tile_counts = TileCounts::from_code("1188m2288p55s1111z");
+        let bingpai = Bingpai::new(&tile_counts).unwrap();
+        let (replacement_number, unnecessary_tiles) = calculate_unnecessary_tiles(&bingpai, true);
+        assert_eq!(replacement_number, 0);
+        assert_eq!(unnecessary_tiles, TileFlags::from_code(""));
+    }
+
+    #[test]
+    fn calculate_unnecessary_tiles_3p_quads_as_two_pairs() {
+        let tile_counts = TileCounts::from_code("1111m1111p1111s12z");
+        let bingpai = Bingpai3p::new(&tile_counts).unwrap();
+        let (replacement_number, unnecessary_tiles) =
+            calculate_unnecessary_tiles_3p(&bingpai, true);
+        assert_eq!(replacement_number, 1);
+        assert_eq!(unnecessary_tiles, TileFlags::from_code("12z"));
+    }
 }
@@ -2,18 +2,22 @@
 // SPDX-License-Identifier: MIT
 // This file is part of https://github.com/Apricot-S/xiangting
 
+use super::constrained;
 use super::qiduizi;
 use super::shisanyao;
 use super::standard;
 use crate::bingpai::{Bingpai, Bingpai3p, BingpaiError};
-use crate::config::PlayerCount;
+use crate::config::{Config, PlayerCount, WinningForms};
+use crate::hand::{Hand, Meld};
 use crate::tile::TileCounts;
 
 /// Calculates the replacement number (= xiàngtīng number + 1) for a given hand.
 ///
 /// # Arguments
 ///
-/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌).
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
 /// * `player_count` - A reference to the number of players.
 ///
 /// # Errors
@@ -64,43 +68,134 @@ use crate::tile::TileCounts;
 /// ```
 #[inline]
 pub fn calculate_replacement_number(
-    bingpai: &TileCounts,
+    bingpai: &impl Hand,
     player_count: &PlayerCount,
 ) -> Result<u8, BingpaiError> {
-    match player_count {
-        PlayerCount::Four => calculate_replacement_number_4p(bingpai),
-        PlayerCount::Three => calculate_replacement_number_3p(bingpai),
+    calculate_replacement_number_with_config(bingpai, &player_count.clone().into())
+}
+
+/// Calculates the replacement number (= xiàngtīng number + 1) for a given hand
+/// under the given rules.
+///
+/// Only the winning forms selected in `config` are considered.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid or no selected winning form can be completed with the hand.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{Config, WinningForms, calculate_replacement_number_with_config};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 19m19p19s1234567z
+/// let hand: [u8; 34] = [
+///     1, 0, 0, 0, 0, 0, 0, 0, 1, // m
+///     1, 0, 0, 0, 0, 0, 0, 0, 1, // p
+///     1, 0, 0, 0, 0, 0, 0, 0, 1, // s
+///     1, 1, 1, 1, 1, 1, 1, // z
+/// ];
+///
+/// let replacement_number = calculate_replacement_number_with_config(&hand, &Config::default())?;
+/// assert_eq!(replacement_number, 1u8);
+///
+/// let config = Config {
+///     forms: WinningForms::STANDARD | WinningForms::QIDUIZI,
+///     ..Default::default()
+/// };
+/// let replacement_number = calculate_replacement_number_with_config(&hand, &config)?;
+/// assert_eq!(replacement_number, 7u8);
+/// # Ok(())
+/// # }
+/// ```
+pub fn calculate_replacement_number_with_config(
+    bingpai: &impl Hand,
+    config: &Config,
+) -> Result<u8, BingpaiError> {
+    let replacement_number = if !config.uses_tables() || bingpai.num_wildcards() > 0 {
+        let num_wildcards = bingpai.num_wildcards();
+        constrained::analyze(bingpai.bingpai(), bingpai.melds(), num_wildcards, config)
+            .map(|a| a.replacement_number)
+    } else {
+        match config.player_count {
+            PlayerCount::Four => {
+                calculate_replacement_number_4p(bingpai.bingpai(), bingpai.melds(), config)
+            }
+            PlayerCount::Three => {
+                calculate_replacement_number_3p(bingpai.bingpai(), bingpai.melds(), config)
+            }
+        }
+    }?;
+
+    match replacement_number {
+        u8::MAX => Err(BingpaiError::NoReachableForm),
+        r => Ok(r),
     }
 }
 
-fn calculate_replacement_number_4p(tile_counts: &TileCounts) -> Result<u8, BingpaiError> {
-    let bingpai = Bingpai::new(tile_counts)?;
+fn calculate_replacement_number_4p(
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    config: &Config,
+) -> Result<u8, BingpaiError> {
+    let bingpai = Bingpai::with_melds(tile_counts, melds)?;
 
-    let r0 = standard::calculate_replacement_number(&bingpai);
-    let r1 = qiduizi::calculate_replacement_number(&bingpai);
-    let r2 = shisanyao::calculate_replacement_number(&bingpai);
+    let r0 = config
+        .forms
+        .contains(WinningForms::STANDARD)
+        .then(|| standard::calculate_replacement_number(&bingpai));
+    let r1 = config.forms.contains(WinningForms::QIDUIZI).then(|| {
+        qiduizi::calculate_replacement_number(&bingpai, config.qiduizi_quads_as_two_pairs)
+    });
+    let r2 = config
+        .forms
+        .contains(WinningForms::SHISANYAO)
+        .then(|| shisanyao::calculate_replacement_number(&bingpai));
 
-    Ok([r0, r1, r2].into_iter().min().unwrap())
+    Ok([r0, r1, r2].into_iter().flatten().min().unwrap_or(u8::MAX))
 }
 
-fn calculate_replacement_number_3p(tile_counts: &TileCounts) -> Result<u8, BingpaiError> {
-    let bingpai_3p = Bingpai3p::new(tile_counts)?;
+fn calculate_replacement_number_3p(
+    tile_counts: &TileCounts,
+    melds: &[Meld],
+    config: &Config,
+) -> Result<u8, BingpaiError> {
+    let bingpai_3p = Bingpai3p::with_melds(tile_counts, melds)?;
 
-    let r0 = standard::calculate_replacement_number_3p(&bingpai_3p);
+    let r0 = config
+        .forms
+        .contains(WinningForms::STANDARD)
+        .then(|| standard::calculate_replacement_number_3p(&bingpai_3p));
 
     let bingpai = bingpai_3p.into();
 
-    let r1 = qiduizi::calculate_replacement_number(&bingpai);
-    let r2 = shisanyao::calculate_replacement_number(&bingpai);
+    let r1 = config.forms.contains(WinningForms::QIDUIZI).then(|| {
+        qiduizi::calculate_replacement_number(&bingpai, config.qiduizi_quads_as_two_pairs)
+    });
+    let r2 = config
+        .forms
+        .contains(WinningForms::SHISANYAO)
+        .then(|| shisanyao::calculate_replacement_number(&bingpai));
 
-    Ok([r0, r1, r2].into_iter().min().unwrap())
+    Ok([r0, r1, r2].into_iter().flatten().min().unwrap_or(u8::MAX))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::bingpai::BingpaiError;
+    use crate::config::Suit;
+    use crate::hand::{FullHand, HandWithWildcards, Meld, TileCountsWithRed};
     use crate::test_utils::FromTileCode;
+    use crate::tile::TileFlags;
 
     #[test]
     fn calculate_replacement_number_ok_standard_tenpai() {
@@ -123,6 +218,23 @@ mod tests {
         assert_eq!(replacement_number.unwrap(), 1);
     }
 
+    #[test]
+    fn calculate_replacement_number_ok_with_red_fives() {
+        let bingpai: TileCountsWithRed = "340m406p789s1122z".parse().unwrap();
+        let replacement_number = calculate_replacement_number(&bingpai, &PlayerCount::Four);
+        assert_eq!(replacement_number.unwrap(), 1);
+    }
+
+    #[test]
+    fn calculate_replacement_number_err_bingpai_5_same_tiles_with_red_five() {
+        let bingpai: TileCountsWithRed = "55550m".parse().unwrap();
+        let replacement_number = calculate_replacement_number(&bingpai, &PlayerCount::Four);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::TooManyCopies { tile: 4, count: 5 })
+        ));
+    }
+
     #[test]
     fn calculate_replacement_number_err_bingpai_empty() {
         let bingpai = TileCounts::from_code("");
@@ -177,6 +289,123 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn calculate_replacement_number_ok_with_melds() {
+        let bingpai = TileCounts::from_code("123m456p1122z");
+        let melds = [Meld::Chi(24)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four);
+        assert_eq!(replacement_number.unwrap(), 1);
+    }
+
+    #[test]
+    fn calculate_replacement_number_ok_with_4_melds() {
+        let bingpai = TileCounts::from_code("1z");
+        let melds = [
+            Meld::Pon(0),
+            Meld::Daiminkan(9),
+            Meld::Kakan(18),
+            Meld::Ankan(33),
+        ];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four);
+        assert_eq!(replacement_number.unwrap(), 1);
+    }
+
+    #[test]
+    fn calculate_replacement_number_ok_no_qiduizi_with_melds() {
+        // 5 pairs would be tenpai for seven pairs in a closed hand.
+        let bingpai = TileCounts::from_code("1188m288p55s1z");
+        let melds = [Meld::Pon(33)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four);
+        assert_eq!(replacement_number.unwrap(), 3);
+    }
+
+    #[test]
+    fn calculate_replacement_number_err_too_many_melds() {
+        let bingpai = TileCounts::from_code("1z");
+        let melds = [Meld::Pon(0); 5];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::TooManyMelds(5))
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_err_too_many_melds_saturates() {
+        let bingpai = TileCounts::from_code("1z");
+        let melds = [Meld::Pon(0); 300];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::TooManyMelds(255))
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_err_invalid_meld() {
+        let bingpai = TileCounts::from_code("1z");
+        let melds = [Meld::Chi(8)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::InvalidMeld(Meld::Chi(8)))
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_err_5_same_tiles_with_melds() {
+        let bingpai = TileCounts::from_code("3m");
+        let melds = [Meld::Chi(0), Meld::Chi(1), Meld::Chi(2), Meld::Pon(2)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::TooManyCopiesWithMelds { tile: 2, count: 7 })
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_err_15_tiles_with_melds() {
+        let bingpai = TileCounts::from_code("1112345678999m");
+        let melds = [Meld::Pon(27)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::TooManyTiles(16))
+        ));
+    }
+
     #[test]
     fn calculate_replacement_number_3_player_ok_standard_tenpai() {
         let bingpai = TileCounts::from_code("111m456p789s1122z");
@@ -207,4 +436,627 @@ mod tests {
             Err(BingpaiError::InvalidTileForThreePlayer(1))
         ));
     }
+
+    #[test]
+    fn calculate_replacement_number_3_player_err_chi() {
+        let bingpai = TileCounts::from_code("1z");
+        let melds = [Meld::Chi(9)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Three);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::InvalidMeldForThreePlayer(Meld::Chi(9)))
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_3_player_err_pon_2m() {
+        let bingpai = TileCounts::from_code("1z");
+        let melds = [Meld::Pon(1)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Three);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::InvalidMeldForThreePlayer(Meld::Pon(1)))
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_standard_only() {
+        let bingpai = TileCounts::from_code("1188m288p55s1177z");
+        let config = Config {
+            forms: WinningForms::STANDARD,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 4);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_without_shisanyao() {
+        let bingpai = TileCounts::from_code("19m19p19s1234567z");
+        let config = Config {
+            player_count: PlayerCount::Three,
+            forms: WinningForms::STANDARD | WinningForms::QIDUIZI,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 7);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_allowed_tiles() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        // Simples only (断么九)
+        let config = Config {
+            allowed_tiles: TileFlags::from_code("2345678m2345678p2345678s"),
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        // 234m456p678s + a meld + a pair
+        assert_eq!(replacement_number.unwrap(), 7);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_allowed_tiles_qiduizi() {
+        let bingpai = TileCounts::from_code("1188m288p55s1177z");
+        // One suit and honors (混一色)
+        let config = Config {
+            allowed_tiles: TileFlags::from_code("123456789p1234567z"),
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        // 88p11z77z + 2p + 3 pairs
+        assert_eq!(replacement_number.unwrap(), 7);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_allowed_tiles_3_player() {
+        let bingpai = TileCounts::from_code("19m19p19s1234567z");
+        // Terminals and honors only (混老頭)
+        let config = Config {
+            player_count: PlayerCount::Three,
+            allowed_tiles: TileFlags::from_code("19m19p19s1234567z"),
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 1);
+
+        let config = Config {
+            allowed_tiles: TileFlags::from_code("19m19p19s123456z"),
+            ..config
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        // 7 of 19m19p19s123456z as seven pairs
+        assert_eq!(replacement_number.unwrap(), 7);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_duiduihe() {
+        let bingpai = TileCounts::from_code("123m111p222s3344z");
+        let config = Config {
+            forms: WinningForms::DUIDUIHE,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        // 111p222s333z444z + a pair of 1m, 2m or 3m
+        assert_eq!(replacement_number.unwrap(), 3);
+
+        let config = Config {
+            forms: WinningForms::STANDARD | WinningForms::DUIDUIHE,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 1);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_duiduihe_3_player() {
+        let bingpai = TileCounts::from_code("11199m11p");
+        let melds = [Meld::Pon(27), Meld::Daiminkan(28)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let config = Config {
+            player_count: PlayerCount::Three,
+            forms: WinningForms::DUIDUIHE,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&hand, &config);
+        assert_eq!(replacement_number.unwrap(), 1);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_pinghe() {
+        let bingpai = TileCounts::from_code("234m567p345s789s11z");
+        let config = Config {
+            forms: WinningForms::PINGHE,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 0);
+
+        // Not the honors (字牌)
+        let config = Config {
+            pair_tiles: TileFlags::from_code("123456789m123456789p123456789s"),
+            ..config
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 2);
+
+        let bingpai = TileCounts::from_code("111m234p567p345s11z");
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        // 123m234p567p345s + 11m
+        assert_eq!(replacement_number.unwrap(), 2);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_pinghe_3_player() {
+        let bingpai = TileCounts::from_code("123456789p2345s");
+        let config = Config {
+            player_count: PlayerCount::Three,
+            forms: WinningForms::PINGHE,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 1);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_quandai() {
+        let bingpai = TileCounts::from_code("123789m123p99s11z");
+        let config = Config {
+            forms: WinningForms::HUNQUANDAI,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 1);
+
+        let config = Config {
+            forms: WinningForms::CHUNQUANDAI,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        // 123789m123p999s + 2 draws for the pair
+        assert_eq!(replacement_number.unwrap(), 3);
+
+        let bingpai = TileCounts::from_code("234m789m123p99s11z");
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        // 1m and 9s for 123m789m123p999s, and 2 draws for the pair
+        assert_eq!(replacement_number.unwrap(), 4);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_quandai_3_player() {
+        let bingpai = TileCounts::from_code("11199m789p123s99s");
+        let config = Config {
+            player_count: PlayerCount::Three,
+            forms: WinningForms::CHUNQUANDAI,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 1);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_qiduizi_quads() {
+        let bingpai = TileCounts::from_code("1111m2233p4455s66z");
+        let config = Config {
+            forms: WinningForms::QIDUIZI,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 2);
+
+        let config = Config {
+            qiduizi_quads_as_two_pairs: true,
+            ..config
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 0);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_qiduizi_quads_3_player() {
+        let bingpai = TileCounts::from_code("1111m2233p4455s6z");
+        let config = Config {
+            player_count: PlayerCount::Three,
+            qiduizi_quads_as_two_pairs: true,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 1);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_knitted() {
+        let forms = WinningForms::STANDARD
+            | WinningForms::QIDUIZI
+            | WinningForms::SHISANYAO
+            | WinningForms::QUANBUKAO
+            | WinningForms::QIXINGBUKAO
+            | WinningForms::ZUHELONG;
+        let config = Config {
+            forms,
+            ..Default::default()
+        };
+
+        let bingpai = TileCounts::from_code("147m258p369s12345z");
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 0);
+
+        let bingpai = TileCounts::from_code("147m25p36s1234567z");
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 0);
+
+        let bingpai = TileCounts::from_code("147m258p369s123s1z");
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 1);
+
+        let bingpai = TileCounts::from_code("147m258p369s1z");
+        let melds = [Meld::Pon(28)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number_with_config(&hand, &config);
+        assert_eq!(replacement_number.unwrap(), 1);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_sichuan() {
+        let bingpai = TileCounts::from_code("123456789m11p33s");
+        let replacement_number = calculate_replacement_number(&bingpai, &PlayerCount::Four);
+        assert_eq!(replacement_number.unwrap(), 1);
+
+        let config = Config {
+            void_suit: Some(Suit::Suozi),
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        // 33s must be discarded.
+        assert_eq!(replacement_number.unwrap(), 3);
+
+        // 龙七对
+        let bingpai = TileCounts::from_code("1111m223344p5566p");
+        let config = Config {
+            qiduizi_quads_as_two_pairs: true,
+            ..config
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 0);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_sichuan_err() {
+        let config = Config {
+            void_suit: Some(Suit::Suozi),
+            ..Default::default()
+        };
+
+        let bingpai = TileCounts::from_code("123456789m11p3s1z");
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::InvalidTileForSichuan(27))
+        ));
+
+        let bingpai = TileCounts::from_code("123456m11p");
+        let melds = [Meld::Pon(18)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number_with_config(&hand, &config);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::InvalidMeldForSichuan(Meld::Pon(18)))
+        ));
+
+        let melds = [Meld::Pon(33)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number_with_config(&hand, &config);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::InvalidMeldForSichuan(Meld::Pon(33)))
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_err_no_allowed_tiles() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let config = Config {
+            allowed_tiles: 0,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::NoReachableForm)
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_err_no_forms() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let config = Config {
+            forms: WinningForms::empty(),
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::NoReachableForm)
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_err_qiduizi_with_melds() {
+        let bingpai = TileCounts::from_code("1188m288p55s1z");
+        let melds = [Meld::Pon(33)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let config = Config {
+            forms: WinningForms::QIDUIZI,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&hand, &config);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::NoReachableForm)
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_sixteen_tiles() {
+        let config = Config {
+            sixteen_tiles: true,
+            ..Default::default()
+        };
+
+        let bingpai = TileCounts::from_code("123456789m123456p11z");
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 0);
+
+        let bingpai = TileCounts::from_code("123456789m12345p11z");
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 1);
+
+        // The 14-tile hands are still valid.
+        let bingpai = TileCounts::from_code("123m456p789s11222z");
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 0);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_sixteen_tiles_with_melds() {
+        let bingpai = TileCounts::from_code("11m");
+        let melds = [
+            Meld::Chi(0),
+            Meld::Pon(9),
+            Meld::Daiminkan(18),
+            Meld::Ankan(27),
+            Meld::Chi(3),
+        ];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let config = Config {
+            sixteen_tiles: true,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&hand, &config);
+        assert_eq!(replacement_number.unwrap(), 0);
+
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::TooManyMelds(5))
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_sixteen_tiles_no_qiduizi() {
+        let bingpai = TileCounts::from_code("11223344556677z");
+        let melds = [Meld::Pon(0)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let config = Config {
+            sixteen_tiles: true,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&hand, &config);
+        assert_eq!(replacement_number.unwrap(), 4);
+
+        let config = Config {
+            forms: WinningForms::QIDUIZI,
+            sixteen_tiles: true,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&hand, &config);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::NoReachableForm)
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_err_17_tiles() {
+        let bingpai = TileCounts::from_code("123456789m123456p11z");
+        let replacement_number =
+            calculate_replacement_number_with_config(&bingpai, &Config::default());
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::TooManyTiles(17))
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_err_sixteen_tiles() {
+        let config = Config {
+            sixteen_tiles: true,
+            ..Default::default()
+        };
+
+        let bingpai = TileCounts::from_code("123456789m123456p111z");
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::TooManyTiles(18))
+        ));
+
+        let bingpai = TileCounts::from_code("1m");
+        let melds = [Meld::Pon(27); 6];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let replacement_number = calculate_replacement_number_with_config(&hand, &config);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::TooManyMelds(6))
+        ));
+
+        // Seven pairs cannot be completed with 16 tiles.
+        let bingpai = TileCounts::from_code("1133m5577p99s1122z");
+        let config = Config {
+            forms: WinningForms::QIDUIZI,
+            ..config
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::NoReachableForm)
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_wildcards() {
+        // 123m456p789s1122z + 1 wildcard
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let hand = HandWithWildcards {
+            hand: &bingpai,
+            num_wildcards: 1,
+        };
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four);
+        assert_eq!(replacement_number.unwrap(), 0);
+
+        // 1133m5577p99s112z + 1 wildcard for 2z
+        let bingpai = TileCounts::from_code("1133m5577p99s112z");
+        let hand = HandWithWildcards {
+            hand: &bingpai,
+            num_wildcards: 1,
+        };
+        let config = Config {
+            forms: WinningForms::QIDUIZI,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&hand, &config);
+        assert_eq!(replacement_number.unwrap(), 0);
+
+        // 19m19p19s123456z + 2 wildcards for 7z and the pair
+        let bingpai = TileCounts::from_code("19m19p19s123456z");
+        let hand = HandWithWildcards {
+            hand: &bingpai,
+            num_wildcards: 2,
+        };
+        let config = Config {
+            forms: WinningForms::SHISANYAO,
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&hand, &config);
+        assert_eq!(replacement_number.unwrap(), 0);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_wildcards_with_melds() {
+        let bingpai = TileCounts::from_code("1m");
+        let melds = [Meld::Pon(27), Meld::Chi(9), Meld::Ankan(33)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let hand = HandWithWildcards {
+            hand: &hand,
+            num_wildcards: 4,
+        };
+        // 1m + 3 wildcards for 111m or 123m and 1 wildcard for the pair
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four);
+        assert_eq!(replacement_number.unwrap(), 0);
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_wildcard_tile() {
+        // 123m456p789s1122z + 7z as the wildcard
+        let bingpai = TileCounts::from_code("123m456p789s11227z");
+        let replacement_number = calculate_replacement_number(&bingpai, &PlayerCount::Four);
+        assert_eq!(replacement_number.unwrap(), 1);
+
+        let config = Config {
+            wildcard_tile: Some(33),
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 0);
+    }
+
+    #[test]
+    fn calculate_replacement_number_err_15_tiles_with_wildcards() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let hand = HandWithWildcards {
+            hand: &bingpai,
+            num_wildcards: 2,
+        };
+        let replacement_number = calculate_replacement_number(&hand, &PlayerCount::Four);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::TooManyTiles(15))
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_err_invalid_wildcard_tile() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let config = Config {
+            wildcard_tile: Some(34),
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert!(matches!(
+            replacement_number,
+            Err(BingpaiError::InvalidWildcardTile(34))
+        ));
+    }
+
+    #[test]
+    fn calculate_replacement_number_with_config_pair_tiles() {
+        let bingpai = TileCounts::from_code("123m456p789s11222z");
+        // 二五八将
+        let config = Config {
+            pair_tiles: TileFlags::from_code("258m258p258s"),
+            ..Default::default()
+        };
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 2);
+
+        // Seven pairs are not limited.
+        let bingpai = TileCounts::from_code("1133m5577p99s1122z");
+        let replacement_number = calculate_replacement_number_with_config(&bingpai, &config);
+        assert_eq!(replacement_number.unwrap(), 0);
+    }
 }
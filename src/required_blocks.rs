@@ -0,0 +1,368 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use crate::analysis::Analysis;
+use crate::bingpai::BingpaiError;
+use crate::config::{Config, PlayerCount, WinningForms};
+use crate::constrained::{self, BlockRules};
+use crate::decomposition::{Block, BlockKind};
+use crate::hand::Hand;
+use crate::tile::{NUM_TILE_INDEX, TileCounts};
+
+// The winning forms made of melds and a pair, which can contain the required blocks.
+const FORMS: [WinningForms; 5] = [
+    WinningForms::STANDARD,
+    WinningForms::DUIDUIHE,
+    WinningForms::PINGHE,
+    WinningForms::HUNQUANDAI,
+    WinningForms::CHUNQUANDAI,
+];
+
+/// The tiles of the required blocks and the numbers of melds and pairs among them.
+struct RequiredBlocks {
+    tile_counts: TileCounts,
+    num_mianzi: u8,
+    num_quetou: u8,
+}
+
+impl RequiredBlocks {
+    fn new(blocks: &[Block]) -> Result<Self, BingpaiError> {
+        let mut required = Self {
+            tile_counts: [0; NUM_TILE_INDEX],
+            num_mianzi: 0,
+            num_quetou: 0,
+        };
+
+        for &block in blocks {
+            let tile = usize::from(block.tile);
+            let is_valid = match block.kind {
+                BlockKind::Kezi | BlockKind::Quetou => tile < NUM_TILE_INDEX,
+                BlockKind::Shunzi => tile < 27 && tile % 9 < 7,
+                _ => false,
+            };
+            if !is_valid {
+                return Err(BingpaiError::InvalidRequiredBlock(block));
+            }
+
+            for &offset in block.kind.offsets() {
+                let count = &mut required.tile_counts[tile + offset];
+                *count = count.saturating_add(1);
+            }
+            match block.kind {
+                BlockKind::Quetou => required.num_quetou = required.num_quetou.saturating_add(1),
+                _ => required.num_mianzi = required.num_mianzi.saturating_add(1),
+            }
+        }
+
+        Ok(required)
+    }
+
+    /// Returns `true` if all the blocks can be used under `rules`.
+    fn is_allowed(blocks: &[Block], rules: &BlockRules) -> bool {
+        blocks.iter().all(|block| {
+            let flags = match block.kind {
+                BlockKind::Kezi => rules.kezi,
+                BlockKind::Shunzi => rules.shunzi,
+                _ => rules.quetou,
+            };
+            flags & (1 << block.tile) != 0
+        })
+    }
+}
+
+/// Calculates the replacement number (= xiàngtīng number + 1), the necessary tiles
+/// and the unnecessary tiles for a given hand whose winning hands must contain the given blocks.
+///
+/// Only the winning forms made of melds (面子) and a pair (雀頭) are considered,
+/// and the required blocks must be made of the pure hand, not of the melds (副露).
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `required_blocks` - A reference to the blocks that the winning hands must contain.
+///   Each block must be a triplet ([`BlockKind::Kezi`]), a sequence ([`BlockKind::Shunzi`])
+///   or a pair ([`BlockKind::Quetou`]).
+/// * `player_count` - A reference to the number of players.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand or a required block is invalid,
+/// or no winning hand can contain all the required blocks.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{Block, BlockKind, PlayerCount, analyze_with_required_blocks};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 123456m789p11z55z
+/// let hand: [u8; 34] = [
+///     1, 1, 1, 1, 1, 1, 0, 0, 0, // m
+///     0, 0, 0, 0, 0, 0, 1, 1, 1, // p
+///     0, 0, 0, 0, 0, 0, 0, 0, 0, // s
+///     2, 0, 0, 0, 2, 0, 0, // z
+/// ];
+///
+/// // 一気通貫: 123m 456m 789m
+/// let ittsu = [0, 3, 6].map(|tile| Block {
+///     kind: BlockKind::Shunzi,
+///     tile,
+/// });
+///
+/// let analysis = analyze_with_required_blocks(&hand, &ittsu, &PlayerCount::Four)?;
+/// assert_eq!(analysis.replacement_number, 3u8);
+/// assert_eq!(analysis.necessary_tiles, 0b111000000); // 789m
+/// # Ok(())
+/// # }
+/// ```
+#[inline]
+pub fn analyze_with_required_blocks(
+    bingpai: &impl Hand,
+    required_blocks: &[Block],
+    player_count: &PlayerCount,
+) -> Result<Analysis, BingpaiError> {
+    analyze_with_required_blocks_with_config(bingpai, required_blocks, &player_count.clone().into())
+}
+
+/// Calculates the replacement number (= xiàngtīng number + 1), the necessary tiles
+/// and the unnecessary tiles for a given hand whose winning hands must contain the given blocks
+/// under the given rules.
+///
+/// Only the selected winning forms made of melds (面子) and a pair (雀頭) are considered,
+/// and the required blocks must be made of the pure hand, not of the melds (副露).
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `required_blocks` - A reference to the blocks that the winning hands must contain.
+///   Each block must be a triplet ([`BlockKind::Kezi`]), a sequence ([`BlockKind::Shunzi`])
+///   or a pair ([`BlockKind::Quetou`]).
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand or a required block is invalid,
+/// or no winning hand of the selected forms can contain all the required blocks.
+pub fn analyze_with_required_blocks_with_config(
+    bingpai: &impl Hand,
+    required_blocks: &[Block],
+    config: &Config,
+) -> Result<Analysis, BingpaiError> {
+    let num_wildcards = bingpai.num_wildcards();
+    let num_required_mianzi =
+        constrained::validate(bingpai.bingpai(), bingpai.melds(), num_wildcards, config)?
+            .num_required_bingpai_mianzi();
+    let required = RequiredBlocks::new(required_blocks)?;
+    let (tile_counts, num_wildcards) =
+        constrained::split_wildcards(bingpai.bingpai(), num_wildcards, config);
+
+    let mut analysis = Analysis::UNREACHABLE;
+    if let (Some(num_mianzi), Some(num_quetou)) = (
+        num_required_mianzi.checked_sub(required.num_mianzi),
+        1u8.checked_sub(required.num_quetou),
+    ) {
+        for form in FORMS
+            .into_iter()
+            .filter(|&form| config.forms.contains(form))
+        {
+            let rules = BlockRules::with_form(form, config);
+            if RequiredBlocks::is_allowed(required_blocks, &rules) {
+                analysis.update_min(constrained::analyze_standard_with_base(
+                    &tile_counts,
+                    &required.tile_counts,
+                    num_mianzi,
+                    num_quetou,
+                    &rules,
+                ));
+            }
+        }
+    }
+
+    let analysis = constrained::substitute_wildcards(analysis, num_wildcards, config);
+    match analysis.replacement_number {
+        u8::MAX => Err(BingpaiError::NoReachableForm),
+        _ => Ok(analysis),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analysis::analyze_with_config;
+    use crate::hand::{FullHand, Meld};
+    use crate::test_utils::FromTileCode;
+    use crate::tile::TileFlags;
+
+    fn block(kind: BlockKind, tile: u8) -> Block {
+        Block { kind, tile }
+    }
+
+    #[test]
+    fn no_required_blocks_matches_standard() {
+        let config = Config {
+            forms: WinningForms::STANDARD,
+            ..Default::default()
+        };
+        for code in [
+            "123m456p789s11222z",
+            "11123455678999m",
+            "19m19p19s1234567z",
+            "1133m5577p99s1122z",
+            "147m258p369s1234z",
+            "5m",
+        ] {
+            let bingpai = TileCounts::from_code(code);
+            assert_eq!(
+                analyze_with_required_blocks_with_config(&bingpai, &[], &config).unwrap(),
+                analyze_with_config(&bingpai, &config).unwrap(),
+                "{code}"
+            );
+        }
+    }
+
+    #[test]
+    fn required_blocks_yiqitongguan() {
+        let bingpai = TileCounts::from_code("123456m789p1155z");
+        let required = [0, 3, 6].map(|tile| block(BlockKind::Shunzi, tile));
+        let analysis =
+            analyze_with_required_blocks(&bingpai, &required, &PlayerCount::Four).unwrap();
+        assert_eq!(analysis.replacement_number, 3);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("789m"));
+        assert_eq!(analysis.unnecessary_tiles, TileFlags::from_code("15z"));
+    }
+
+    #[test]
+    fn required_blocks_yakuhai() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let required = [block(BlockKind::Kezi, 31)];
+        let analysis =
+            analyze_with_required_blocks(&bingpai, &required, &PlayerCount::Four).unwrap();
+        assert_eq!(analysis.replacement_number, 3);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("5z"));
+        assert_eq!(analysis.unnecessary_tiles, TileFlags::from_code("12z"));
+    }
+
+    #[test]
+    fn required_blocks_sanseshuntong() {
+        let bingpai = TileCounts::from_code("234m234p23s11z");
+        let required = [1, 10, 19].map(|tile| block(BlockKind::Shunzi, tile));
+        let analysis =
+            analyze_with_required_blocks(&bingpai, &required, &PlayerCount::Four).unwrap();
+        assert_eq!(analysis.replacement_number, 1);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("4s"));
+        assert_eq!(analysis.unnecessary_tiles, 0);
+    }
+
+    #[test]
+    fn required_blocks_dasanyuan() {
+        let bingpai = TileCounts::from_code("123m456p5556667z");
+        let required = [31, 32, 33].map(|tile| block(BlockKind::Kezi, tile));
+        let analysis =
+            analyze_with_required_blocks(&bingpai, &required, &PlayerCount::Four).unwrap();
+        assert_eq!(analysis.replacement_number, 3);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("123m456p7z"));
+    }
+
+    #[test]
+    fn required_blocks_quetou() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let required = [block(BlockKind::Quetou, 27)];
+        let analysis =
+            analyze_with_required_blocks(&bingpai, &required, &PlayerCount::Four).unwrap();
+        assert_eq!(analysis.replacement_number, 1);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("2z"));
+        assert_eq!(analysis.unnecessary_tiles, 0);
+    }
+
+    #[test]
+    fn required_blocks_invalid() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        for required in [
+            block(BlockKind::Duizi, 0),
+            block(BlockKind::Kanzhang, 0),
+            block(BlockKind::Shunzi, 7),
+            block(BlockKind::Shunzi, 27),
+            block(BlockKind::Kezi, 34),
+            block(BlockKind::Quetou, 34),
+        ] {
+            let result = analyze_with_required_blocks(&bingpai, &[required], &PlayerCount::Four);
+            assert!(
+                matches!(result, Err(BingpaiError::InvalidRequiredBlock(b)) if b == required),
+                "{required:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn required_blocks_unreachable() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let too_many_mianzi = [0, 3, 6, 9, 12].map(|tile| block(BlockKind::Shunzi, tile));
+        let too_many_quetou = [block(BlockKind::Quetou, 27), block(BlockKind::Quetou, 28)];
+        let too_many_copies = [block(BlockKind::Kezi, 0), block(BlockKind::Quetou, 0)];
+        for required in [&too_many_mianzi[..], &too_many_quetou, &too_many_copies] {
+            let result = analyze_with_required_blocks(&bingpai, required, &PlayerCount::Four);
+            assert!(
+                matches!(result, Err(BingpaiError::NoReachableForm)),
+                "{required:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn required_blocks_with_melds() {
+        let bingpai = TileCounts::from_code("1122m");
+        let melds = [Meld::Pon(27), Meld::Chi(9), Meld::Ankan(33)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let kezi = [block(BlockKind::Kezi, 0)];
+        let analysis = analyze_with_required_blocks(&hand, &kezi, &PlayerCount::Four).unwrap();
+        assert_eq!(analysis.replacement_number, 1);
+        assert_eq!(analysis.necessary_tiles, TileFlags::from_code("1m"));
+
+        let two_kezi = [block(BlockKind::Kezi, 0), block(BlockKind::Kezi, 1)];
+        let result = analyze_with_required_blocks(&hand, &two_kezi, &PlayerCount::Four);
+        assert!(matches!(result, Err(BingpaiError::NoReachableForm)));
+    }
+
+    #[test]
+    fn required_blocks_forms() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let shunzi = [block(BlockKind::Shunzi, 0)];
+        for forms in [WinningForms::DUIDUIHE, WinningForms::QIDUIZI] {
+            let config = Config {
+                forms,
+                ..Default::default()
+            };
+            let result = analyze_with_required_blocks_with_config(&bingpai, &shunzi, &config);
+            assert!(matches!(result, Err(BingpaiError::NoReachableForm)));
+        }
+
+        let config = Config {
+            forms: WinningForms::PINGHE,
+            ..Default::default()
+        };
+        let analysis =
+            analyze_with_required_blocks_with_config(&bingpai, &shunzi, &config).unwrap();
+        assert_eq!(analysis.replacement_number, 3);
+    }
+
+    #[test]
+    fn required_blocks_3p() {
+        let bingpai = TileCounts::from_code("123p456p789s1122z");
+        let shunzi_m = [block(BlockKind::Shunzi, 0)];
+        let result = analyze_with_required_blocks(&bingpai, &shunzi_m, &PlayerCount::Three);
+        assert!(matches!(result, Err(BingpaiError::NoReachableForm)));
+
+        let kezi_m = [block(BlockKind::Kezi, 0)];
+        let analysis =
+            analyze_with_required_blocks(&bingpai, &kezi_m, &PlayerCount::Three).unwrap();
+        assert_eq!(analysis.replacement_number, 3);
+    }
+}
@@ -2,11 +2,15 @@
 // SPDX-License-Identifier: MIT
 // This file is part of https://github.com/Apricot-S/xiangting
 
+#[cfg(not(feature = "build-file"))]
+mod analysis;
 #[cfg(not(feature = "build-file"))]
 mod common;
 #[cfg(not(feature = "build-file"))]
 mod core;
 #[cfg(not(feature = "build-file"))]
+mod discard;
+#[cfg(not(feature = "build-file"))]
 mod hash;
 #[cfg(not(feature = "build-file"))]
 mod necessary_tiles;
@@ -29,6 +33,10 @@ mod zipai_map;
 #[cfg(not(feature = "build-file"))]
 mod zipai_table;
 
+#[cfg(not(feature = "build-file"))]
+pub(super) use analysis::{analyze, analyze_3p};
+#[cfg(not(feature = "build-file"))]
+pub(super) use discard::{calculate_discards, calculate_discards_3p};
 #[cfg(not(feature = "build-file"))]
 pub(super) use necessary_tiles::{calculate_necessary_tiles, calculate_necessary_tiles_3p};
 #[cfg(not(feature = "build-file"))]
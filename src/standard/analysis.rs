@@ -0,0 +1,282 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use super::hash::{hash_19m, hash_shupai, hash_zipai};
+use super::shupai_map::{
+    SHUPAI_NECESSARY_TILES_MAP, SHUPAI_REPLACEMENT_NUMBER_MAP, SHUPAI_UNNECESSARY_TILES_MAP,
+};
+use super::unpack::{
+    UnpackedNumbers, UnpackedTiles, unpack_necessary_tiles, unpack_replacement_number,
+    unpack_unnecessary_tiles,
+};
+use super::wanzi_19_map::{
+    WANZI_19_NECESSARY_TILES_MAP, WANZI_19_REPLACEMENT_NUMBER_MAP, WANZI_19_UNNECESSARY_TILES_MAP,
+};
+use super::zipai_map::{
+    ZIPAI_NECESSARY_TILES_MAP, ZIPAI_REPLACEMENT_NUMBER_MAP, ZIPAI_UNNECESSARY_TILES_MAP,
+};
+use crate::bingpai::{Bingpai, Bingpai3p};
+use crate::tile::TileFlags;
+use core::cmp::Ordering;
+
+// The necessary tiles of index 0 (0 pair, 0 melds) are always empty.
+// Therefore, the DP for the unnecessary tiles gives the same necessary tiles
+// as the DP for the necessary tiles, and both can share a single pass.
+struct FusedEntry {
+    numbers: UnpackedNumbers,
+    necessary_tiles: [TileFlags; 10],
+    unnecessary_tiles: [TileFlags; 10],
+}
+
+#[derive(Clone, Copy)]
+struct Candidate {
+    number: u32,
+    necessary_tiles: TileFlags,
+    unnecessary_tiles: TileFlags,
+}
+
+impl FusedEntry {
+    #[inline]
+    fn get(&self, i: usize) -> Candidate {
+        Candidate {
+            number: self.numbers[i],
+            necessary_tiles: self.necessary_tiles[i],
+            unnecessary_tiles: self.unnecessary_tiles[i],
+        }
+    }
+
+    #[inline]
+    fn set(&mut self, i: usize, candidate: Candidate) {
+        self.numbers[i] = candidate.number;
+        self.necessary_tiles[i] = candidate.necessary_tiles;
+        self.unnecessary_tiles[i] = candidate.unnecessary_tiles;
+    }
+
+    #[inline]
+    fn combine(&self, i: usize, rhs: &Self, j: usize) -> Candidate {
+        Candidate {
+            number: self.numbers[i] + rhs.numbers[j],
+            necessary_tiles: self.necessary_tiles[i] | rhs.necessary_tiles[j],
+            unnecessary_tiles: self.unnecessary_tiles[i] | rhs.unnecessary_tiles[j],
+        }
+    }
+}
+
+#[inline]
+fn update_min(lhs: &mut Candidate, rhs: Candidate) {
+    match lhs.number.cmp(&rhs.number) {
+        Ordering::Less => (),
+        Ordering::Equal => {
+            lhs.necessary_tiles |= rhs.necessary_tiles;
+            lhs.unnecessary_tiles |= rhs.unnecessary_tiles;
+        }
+        Ordering::Greater => *lhs = rhs,
+    }
+}
+
+fn update_dp(lhs: &mut FusedEntry, rhs: &FusedEntry) {
+    for i in (5..10).rev() {
+        // lhs.numbers[0] and rhs.numbers[0] are always 0.
+        let mut candidate = lhs.combine(i, rhs, 0);
+        update_min(&mut candidate, lhs.combine(0, rhs, i));
+
+        for j in 5..i {
+            update_min(&mut candidate, lhs.combine(j, rhs, i - j));
+            update_min(&mut candidate, lhs.combine(i - j, rhs, j));
+        }
+
+        lhs.set(i, candidate);
+    }
+
+    for i in (0..5).rev() {
+        let mut candidate = lhs.combine(i, rhs, 0);
+        update_min(&mut candidate, lhs.combine(0, rhs, i));
+
+        for j in 1..i {
+            update_min(&mut candidate, lhs.combine(j, rhs, i - j));
+        }
+
+        lhs.set(i, candidate);
+    }
+}
+
+fn update_dp_final(lhs: &mut FusedEntry, rhs: &FusedEntry) {
+    for i in (5..10).rev() {
+        let mut candidate = lhs.combine(i, rhs, 0);
+        update_min(&mut candidate, lhs.combine(0, rhs, i));
+
+        for j in 5..i {
+            update_min(&mut candidate, lhs.combine(j, rhs, i - j));
+            update_min(&mut candidate, lhs.combine(i - j, rhs, j));
+        }
+
+        lhs.set(i, candidate);
+    }
+}
+
+#[inline]
+fn fused_entry(
+    numbers: UnpackedNumbers,
+    necessary_tiles: UnpackedTiles,
+    unnecessary_tiles: UnpackedTiles,
+    shift: u32,
+) -> FusedEntry {
+    FusedEntry {
+        numbers,
+        necessary_tiles: necessary_tiles.map(|t| (t as TileFlags) << shift),
+        unnecessary_tiles: unnecessary_tiles.map(|t| (t as TileFlags) << shift),
+    }
+}
+
+fn finish(
+    mut entry0: FusedEntry,
+    entry1: FusedEntry,
+    entry2: FusedEntry,
+    entry3: FusedEntry,
+    num_required_bingpai_mianzi: u8,
+) -> (u8, TileFlags, TileFlags) {
+    update_dp(&mut entry0, &entry1);
+    update_dp(&mut entry0, &entry2);
+    update_dp_final(&mut entry0, &entry3);
+
+    let n = 5 + num_required_bingpai_mianzi as usize;
+    let result = entry0.get(n);
+    (
+        result.number as u8,
+        result.necessary_tiles,
+        result.unnecessary_tiles,
+    )
+}
+
+/// Returns the replacement number, the necessary tiles and the unnecessary tiles.
+pub(in super::super) fn analyze(bingpai: &Bingpai) -> (u8, TileFlags, TileFlags) {
+    let hash_m = hash_shupai(&bingpai.tile_counts()[0..9]);
+    let hash_p = hash_shupai(&bingpai.tile_counts()[9..18]);
+    let hash_s = hash_shupai(&bingpai.tile_counts()[18..27]);
+    let hash_z = hash_zipai(&bingpai.tile_counts()[27..34]);
+
+    let entry0 = fused_entry(
+        unpack_replacement_number(&SHUPAI_REPLACEMENT_NUMBER_MAP[hash_m]),
+        unpack_necessary_tiles(&SHUPAI_NECESSARY_TILES_MAP[hash_m]),
+        unpack_unnecessary_tiles(&SHUPAI_UNNECESSARY_TILES_MAP[hash_m]),
+        0,
+    );
+    let entry1 = fused_entry(
+        unpack_replacement_number(&SHUPAI_REPLACEMENT_NUMBER_MAP[hash_p]),
+        unpack_necessary_tiles(&SHUPAI_NECESSARY_TILES_MAP[hash_p]),
+        unpack_unnecessary_tiles(&SHUPAI_UNNECESSARY_TILES_MAP[hash_p]),
+        9,
+    );
+    let entry2 = fused_entry(
+        unpack_replacement_number(&SHUPAI_REPLACEMENT_NUMBER_MAP[hash_s]),
+        unpack_necessary_tiles(&SHUPAI_NECESSARY_TILES_MAP[hash_s]),
+        unpack_unnecessary_tiles(&SHUPAI_UNNECESSARY_TILES_MAP[hash_s]),
+        18,
+    );
+    let entry3 = fused_entry(
+        unpack_replacement_number(&ZIPAI_REPLACEMENT_NUMBER_MAP[hash_z]),
+        unpack_necessary_tiles(&ZIPAI_NECESSARY_TILES_MAP[hash_z]),
+        unpack_unnecessary_tiles(&ZIPAI_UNNECESSARY_TILES_MAP[hash_z]),
+        27,
+    );
+
+    finish(
+        entry0,
+        entry1,
+        entry2,
+        entry3,
+        bingpai.num_required_bingpai_mianzi(),
+    )
+}
+
+/// Returns the replacement number, the necessary tiles and the unnecessary tiles
+/// for three-player mahjong.
+pub(in super::super) fn analyze_3p(bingpai: &Bingpai3p) -> (u8, TileFlags, TileFlags) {
+    let hash_m = hash_19m(&bingpai.tile_counts()[0..9]);
+    let hash_p = hash_shupai(&bingpai.tile_counts()[9..18]);
+    let hash_s = hash_shupai(&bingpai.tile_counts()[18..27]);
+    let hash_z = hash_zipai(&bingpai.tile_counts()[27..34]);
+
+    let entry0 = fused_entry(
+        unpack_replacement_number(&WANZI_19_REPLACEMENT_NUMBER_MAP[hash_m]),
+        unpack_necessary_tiles(&WANZI_19_NECESSARY_TILES_MAP[hash_m]),
+        unpack_unnecessary_tiles(&WANZI_19_UNNECESSARY_TILES_MAP[hash_m]),
+        0,
+    );
+    let entry1 = fused_entry(
+        unpack_replacement_number(&SHUPAI_REPLACEMENT_NUMBER_MAP[hash_p]),
+        unpack_necessary_tiles(&SHUPAI_NECESSARY_TILES_MAP[hash_p]),
+        unpack_unnecessary_tiles(&SHUPAI_UNNECESSARY_TILES_MAP[hash_p]),
+        9,
+    );
+    let entry2 = fused_entry(
+        unpack_replacement_number(&SHUPAI_REPLACEMENT_NUMBER_MAP[hash_s]),
+        unpack_necessary_tiles(&SHUPAI_NECESSARY_TILES_MAP[hash_s]),
+        unpack_unnecessary_tiles(&SHUPAI_UNNECESSARY_TILES_MAP[hash_s]),
+        18,
+    );
+    let entry3 = fused_entry(
+        unpack_replacement_number(&ZIPAI_REPLACEMENT_NUMBER_MAP[hash_z]),
+        unpack_necessary_tiles(&ZIPAI_NECESSARY_TILES_MAP[hash_z]),
+        unpack_unnecessary_tiles(&ZIPAI_UNNECESSARY_TILES_MAP[hash_z]),
+        27,
+    );
+
+    finish(
+        entry0,
+        entry1,
+        entry2,
+        entry3,
+        bingpai.num_required_bingpai_mianzi(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{
+        calculate_necessary_tiles, calculate_necessary_tiles_3p, calculate_unnecessary_tiles,
+        calculate_unnecessary_tiles_3p,
+    };
+    use super::*;
+    use crate::test_utils::FromTileCode;
+    use crate::tile::TileCounts;
+
+    const HANDS: [&str; 10] = [
+        "19m19p19s1234567z",
+        "119m19p19s1234567z",
+        "123m456p789s1122z",
+        "2344456p123456z",
+        "133345568s11567z",
+        "1111m123p112233s",
+        "1111247777m1112z",
+        "11112222333444z",
+        "199m146779p12s246z",
+        "1m",
+    ];
+
+    #[test]
+    fn analyze_matches_separate_calculations() {
+        for code in HANDS {
+            let tile_counts = TileCounts::from_code(code);
+            let bingpai = Bingpai::new(&tile_counts).unwrap();
+            let (r, n, u) = analyze(&bingpai);
+            assert_eq!((r, n), calculate_necessary_tiles(&bingpai), "{code}");
+            assert_eq!((r, u), calculate_unnecessary_tiles(&bingpai), "{code}");
+        }
+    }
+
+    #[test]
+    fn analyze_3p_matches_separate_calculations() {
+        for code in HANDS {
+            let tile_counts = TileCounts::from_code(code);
+            let Ok(bingpai) = Bingpai3p::new(&tile_counts) else {
+                // Skip hands with 2m-8m.
+                continue;
+            };
+            let (r, n, u) = analyze_3p(&bingpai);
+            assert_eq!((r, n), calculate_necessary_tiles_3p(&bingpai), "{code}");
+            assert_eq!((r, u), calculate_unnecessary_tiles_3p(&bingpai), "{code}");
+        }
+    }
+}
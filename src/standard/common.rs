@@ -6,6 +6,7 @@ use super::unpack::UnpackedNumbers;
 use crate::tile::TileFlags;
 use core::cmp::Ordering;
 
+#[derive(Clone)]
 pub(super) struct Entry {
     pub(super) numbers: UnpackedNumbers,
     pub(super) tiles: [TileFlags; 10],
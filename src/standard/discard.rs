@@ -0,0 +1,170 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use super::common::Entry;
+use super::hash::{hash_19m, hash_shupai, hash_zipai};
+use super::necessary_tiles::{update_dp, update_dp_final};
+use super::shupai_map::{SHUPAI_NECESSARY_TILES_MAP, SHUPAI_REPLACEMENT_NUMBER_MAP};
+use super::unpack::{unpack_necessary_tiles, unpack_replacement_number};
+use super::wanzi_19_map::{WANZI_19_NECESSARY_TILES_MAP, WANZI_19_REPLACEMENT_NUMBER_MAP};
+use super::zipai_map::{ZIPAI_NECESSARY_TILES_MAP, ZIPAI_REPLACEMENT_NUMBER_MAP};
+use crate::bingpai::{Bingpai, Bingpai3p};
+use crate::tile::{NUM_TILE_INDEX, TileFlags};
+
+// The ranges of the tile indices of the suits.
+const SUITS: [(usize, usize); 4] = [(0, 9), (9, 18), (18, 27), (27, 34)];
+
+fn shupai_entry(single_color_bingpai: &[u8], shift: usize) -> Entry {
+    let hash = hash_shupai(single_color_bingpai);
+    Entry {
+        numbers: unpack_replacement_number(&SHUPAI_REPLACEMENT_NUMBER_MAP[hash]),
+        tiles: unpack_necessary_tiles(&SHUPAI_NECESSARY_TILES_MAP[hash])
+            .map(|t| (t as TileFlags) << shift),
+    }
+}
+
+fn wanzi_19_entry(wanzi_bingpai: &[u8]) -> Entry {
+    let hash = hash_19m(wanzi_bingpai);
+    Entry {
+        numbers: unpack_replacement_number(&WANZI_19_REPLACEMENT_NUMBER_MAP[hash]),
+        tiles: unpack_necessary_tiles(&WANZI_19_NECESSARY_TILES_MAP[hash]).map(|t| t as TileFlags),
+    }
+}
+
+fn zipai_entry(zipai_bingpai: &[u8]) -> Entry {
+    let hash = hash_zipai(zipai_bingpai);
+    Entry {
+        numbers: unpack_replacement_number(&ZIPAI_REPLACEMENT_NUMBER_MAP[hash]),
+        tiles: unpack_necessary_tiles(&ZIPAI_NECESSARY_TILES_MAP[hash])
+            .map(|t| (t as TileFlags) << 27),
+    }
+}
+
+/// Calculates the replacement number and the necessary tiles after discarding each tile.
+///
+/// `suit_entry` returns the entry of the `k`-th suit for the given tiles of the suit.
+/// Only the entry of the suit of the discarded tile is recalculated,
+/// and it is combined with the precalculated entries of the other 3 suits.
+fn calculate_discards_impl(
+    tile_counts: &[u8; NUM_TILE_INDEX],
+    num_required_bingpai_mianzi: u8,
+    suit_entry: impl Fn(usize, &[u8]) -> Entry,
+) -> [Option<(u8, TileFlags)>; NUM_TILE_INDEX] {
+    let entries: [Entry; 4] =
+        core::array::from_fn(|k| suit_entry(k, &tile_counts[SUITS[k].0..SUITS[k].1]));
+
+    // The combined entry of the suits other than the k-th suit.
+    let others: [Entry; 4] = core::array::from_fn(|k| {
+        let mut rest = (0..4).filter(|&l| l != k);
+        let mut combined = entries[rest.next().unwrap()].clone();
+        rest.for_each(|l| update_dp(&mut combined, &entries[l]));
+        combined
+    });
+
+    let n = 5 + num_required_bingpai_mianzi as usize;
+    let mut results = [None; NUM_TILE_INDEX];
+
+    for (k, &(begin, end)) in SUITS.iter().enumerate() {
+        let mut suit = [0u8; 9];
+        suit[..end - begin].copy_from_slice(&tile_counts[begin..end]);
+
+        for i in 0..(end - begin) {
+            if suit[i] == 0 {
+                continue;
+            }
+
+            suit[i] -= 1;
+            let mut entry = others[k].clone();
+            update_dp_final(&mut entry, &suit_entry(k, &suit[..end - begin]));
+            suit[i] += 1;
+
+            results[begin + i] = Some((entry.numbers[n] as u8, entry.tiles[n]));
+        }
+    }
+
+    results
+}
+
+pub(in super::super) fn calculate_discards(
+    bingpai: &Bingpai,
+) -> [Option<(u8, TileFlags)>; NUM_TILE_INDEX] {
+    calculate_discards_impl(
+        bingpai.tile_counts(),
+        bingpai.num_required_bingpai_mianzi(),
+        |k, tiles| match k {
+            3 => zipai_entry(tiles),
+            _ => shupai_entry(tiles, 9 * k),
+        },
+    )
+}
+
+pub(in super::super) fn calculate_discards_3p(
+    bingpai: &Bingpai3p,
+) -> [Option<(u8, TileFlags)>; NUM_TILE_INDEX] {
+    calculate_discards_impl(
+        bingpai.tile_counts(),
+        bingpai.num_required_bingpai_mianzi(),
+        |k, tiles| match k {
+            0 => wanzi_19_entry(tiles),
+            3 => zipai_entry(tiles),
+            _ => shupai_entry(tiles, 9 * k),
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{calculate_necessary_tiles, calculate_necessary_tiles_3p};
+    use super::*;
+    use crate::test_utils::FromTileCode;
+    use crate::tile::TileCounts;
+
+    fn verify(code: &str) {
+        let tile_counts = TileCounts::from_code(code);
+        let bingpai = Bingpai::new(&tile_counts).unwrap();
+        let discards = calculate_discards(&bingpai);
+
+        for (i, discard) in discards.iter().enumerate() {
+            if tile_counts[i] == 0 {
+                assert!(discard.is_none());
+                continue;
+            }
+            let mut discarded = tile_counts;
+            discarded[i] -= 1;
+            let bingpai = Bingpai::new(&discarded).unwrap();
+            assert_eq!(
+                discard.unwrap(),
+                calculate_necessary_tiles(&bingpai),
+                "{code} {i}"
+            );
+        }
+    }
+
+    #[test]
+    fn calculate_discards_matches_necessary_tiles() {
+        verify("123m456p789s11222z");
+        verify("199m146779p12s246z");
+        verify("1111247777m1123z");
+        verify("2234556788p1234z");
+        verify("11z");
+    }
+
+    #[test]
+    fn calculate_discards_3p_matches_necessary_tiles() {
+        let tile_counts = TileCounts::from_code("1199m288p55s11777z");
+        let bingpai = Bingpai3p::new(&tile_counts).unwrap();
+        let discards = calculate_discards_3p(&bingpai);
+
+        for (i, discard) in discards.iter().enumerate() {
+            if tile_counts[i] == 0 {
+                assert!(discard.is_none());
+                continue;
+            }
+            let mut discarded = tile_counts;
+            discarded[i] -= 1;
+            let bingpai = Bingpai3p::new(&discarded).unwrap();
+            assert_eq!(discard.unwrap(), calculate_necessary_tiles_3p(&bingpai));
+        }
+    }
+}
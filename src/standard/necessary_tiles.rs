@@ -11,7 +11,7 @@ use super::zipai_map::{ZIPAI_NECESSARY_TILES_MAP, ZIPAI_REPLACEMENT_NUMBER_MAP};
 use crate::bingpai::{Bingpai, Bingpai3p};
 use crate::tile::TileFlags;
 
-fn update_dp(lhs: &mut Entry, rhs: &Entry) {
+pub(super) fn update_dp(lhs: &mut Entry, rhs: &Entry) {
     for i in (5..10).rev() {
         // The original expression is
         // ```
@@ -80,7 +80,7 @@ fn update_dp(lhs: &mut Entry, rhs: &Entry) {
     }
 }
 
-fn update_dp_final(lhs: &mut Entry, rhs: &Entry) {
+pub(super) fn update_dp_final(lhs: &mut Entry, rhs: &Entry) {
     for i in (5..10).rev() {
         // The original expression is
         // ```
@@ -67,6 +67,7 @@ pub(super) fn unpack_unnecessary_tiles(pack: &UnnecessaryTilesMapValue) -> Unpac
 }
 
 #[cfg(test)]
+#[allow(clippy::identity_op)]
 mod tests {
     use super::*;
 
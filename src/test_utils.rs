@@ -2,87 +2,52 @@
 // SPDX-License-Identifier: MIT
 // This file is part of https://github.com/Apricot-S/xiangting
 
-use crate::tile::{TileCounts, TileFlags};
-
-fn parse_tile_indices(hand: &str) -> impl Iterator<Item = usize> + '_ {
-    let mut current_color: Option<usize> = None;
-
-    hand.chars()
-        .rev()
-        .map(move |c| match c {
-            'm' => {
-                current_color = Some(0);
-                return None;
-            }
-            'p' => {
-                current_color = Some(9);
-                return None;
-            }
-            's' => {
-                current_color = Some(18);
-                return None;
-            }
-            'z' => {
-                current_color = Some(27);
-                return None;
-            }
-            _ => {
-                let d = c.to_digit(10).expect("invalid digit") as usize;
-                let base = current_color.expect("digit without type");
-                if !(1..=9).contains(&d) {
-                    panic!("tile number must be 1-9, got {}", d);
-                }
-                if base == 27 && d > 7 {
-                    panic!("honor tile must be 1-7, got {}", d);
-                }
-                return Some(base + d - 1);
-            }
-        })
-        .filter_map(|x| x)
-}
+use crate::mpsz::{parse_tile_counts, parse_tile_flags};
+use crate::rng::SplitMix64;
+use crate::tile::{NUM_TILE_INDEX, TileCounts, TileFlags};
 
 pub trait FromTileCode: Sized {
     /// Converts a Tenhou-style tile string into `T`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the string is not in MPSZ notation.
     fn from_code(hand: &str) -> Self;
-    fn empty() -> Self;
-    fn apply(self, idx: usize) -> Self;
 }
 
 impl FromTileCode for TileCounts {
     fn from_code(hand: &str) -> Self {
-        let mut result = Self::empty();
-        for idx in parse_tile_indices(hand) {
-            result = result.apply(idx);
-        }
-        result
-    }
-
-    fn empty() -> Self {
-        [0u8; 34]
-    }
-
-    fn apply(mut self, idx: usize) -> Self {
-        self[idx] += 1;
-        self
+        parse_tile_counts(hand).unwrap()
     }
 }
 
 impl FromTileCode for TileFlags {
     fn from_code(hand: &str) -> Self {
-        let mut result = Self::empty();
-        for idx in parse_tile_indices(hand) {
-            result = result.apply(idx);
-        }
-        result
-    }
-
-    fn empty() -> Self {
-        0u64
+        parse_tile_flags(hand).unwrap()
     }
+}
 
-    fn apply(self, idx: usize) -> Self {
-        self | (1 << idx)
+/// Generates a random hand of `num_tiles` tiles, each of which is in `allowed_tiles`.
+///
+/// # Panics
+///
+/// Panics if `allowed_tiles` has fewer than `num_tiles` copies of tiles.
+pub fn random_hand(rng: &mut SplitMix64, num_tiles: usize, allowed_tiles: TileFlags) -> TileCounts {
+    let candidates = (0..NUM_TILE_INDEX).filter(|&i| allowed_tiles & (1 << i) != 0);
+    let num_candidates = candidates.clone().count() as u32;
+    assert!(num_tiles <= 4 * num_candidates as usize);
+
+    let mut tile_counts = [0; NUM_TILE_INDEX];
+    let mut n = 0;
+    while n < num_tiles {
+        let k = rng.next_below(num_candidates) as usize;
+        let i = candidates.clone().nth(k).unwrap();
+        if tile_counts[i] < 4 {
+            tile_counts[i] += 1;
+            n += 1;
+        }
     }
+    tile_counts
 }
 
 #[cfg(test)]
@@ -2,7 +2,15 @@
 // SPDX-License-Identifier: MIT
 // This file is part of https://github.com/Apricot-S/xiangting
 
-const NUM_TILE_INDEX: usize = 3 * 9 + 4 + 3;
+pub(crate) const NUM_TILE_INDEX: usize = 3 * 9 + 4 + 3;
+
+pub(crate) const ALL_TILES: TileFlags = (1 << NUM_TILE_INDEX) - 1;
+// 2m-8m, which are not used in three-player mahjong.
+pub(crate) const WANZI_2_TO_8: TileFlags = 0b1111111 << 1;
+// 19m19p19s1234567z
+pub(crate) const YAOJIU_TILES: TileFlags = 0b1111111_100000001_100000001_100000001;
+// 1234567z
+pub(crate) const HONOR_TILES: TileFlags = 0b1111111 << 27;
 
 /// 牌: Tile.
 ///
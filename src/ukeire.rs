@@ -0,0 +1,569 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use super::constrained;
+use crate::bingpai::BingpaiError;
+use crate::config::{Config, PlayerCount};
+use crate::hand::{FullHand, Hand};
+use crate::necessary_tiles::calculate_necessary_tiles_with_config;
+use crate::tile::{HONOR_TILES, NUM_TILE_INDEX, Tile, TileCounts, TileFlags, TileFlagsExt};
+use crate::unnecessary_tiles::calculate_unnecessary_tiles_with_config;
+
+pub(crate) const MAX_TILE_COPIES: u8 = 4;
+
+/// The acceptance (受け入れ) of a hand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ukeire {
+    /// The replacement number (= xiàngtīng number + 1).
+    pub replacement_number: u8,
+    /// The necessary tiles (有効牌).
+    pub necessary_tiles: TileFlags,
+    /// The number of remaining copies of each necessary tile.
+    ///
+    /// The counts of the tiles that are not necessary are 0.
+    pub remaining_tiles: TileCounts,
+    /// The total number of remaining copies of the necessary tiles.
+    pub num_remaining_tiles: u8,
+}
+
+/// The second-order acceptance of a hand (two-step ukeire).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TwoStepUkeire {
+    /// The acceptance (受け入れ) of the hand.
+    pub ukeire: Ukeire,
+    /// The improvement tiles (改良牌), i.e., the tiles that increase the acceptance
+    /// without lowering the replacement number when drawn with the best discard.
+    pub improvement_tiles: TileFlags,
+    /// The total number of remaining copies of the improvement tiles.
+    pub num_improvement_tiles: u8,
+    /// The sum of the acceptance after drawing each remaining copy of the necessary tiles
+    /// and discarding the tile that maximizes the acceptance.
+    ///
+    /// Drawing a winning tile contributes 0.
+    pub total_next_ukeire: u16,
+}
+
+impl TwoStepUkeire {
+    /// Returns the average acceptance after drawing a necessary tile.
+    ///
+    /// Returns 0 if no necessary tile remains.
+    #[must_use]
+    pub fn average_next_ukeire(&self) -> f64 {
+        match self.ukeire.num_remaining_tiles {
+            0 => 0.0,
+            n => f64::from(self.total_next_ukeire) / f64::from(n),
+        }
+    }
+}
+
+/// Returns the number of copies of each tile that cannot be drawn anymore,
+/// i.e., the tiles in the pure hand, the melds and `visible`.
+pub(crate) fn count_unavailable_tiles(
+    bingpai: &impl Hand,
+    visible: &TileCounts,
+) -> Result<TileCounts, BingpaiError> {
+    let mut unavailable = *visible;
+    for (u, &c) in unavailable.iter_mut().zip(bingpai.bingpai().iter()) {
+        *u = u.saturating_add(c);
+    }
+    bingpai
+        .melds()
+        .iter()
+        .for_each(|m| m.add_to(&mut unavailable));
+
+    unavailable
+        .iter()
+        .enumerate()
+        .find(|(_, c)| **c > MAX_TILE_COPIES)
+        .map(|(i, &c)| BingpaiError::TooManyVisibleCopies {
+            tile: i as Tile,
+            count: c,
+        })
+        .map_or(Ok(unavailable), Err)
+}
+
+/// Returns the number of remaining copies of each tile in `tiles`.
+pub(crate) fn count_remaining_tiles(
+    tiles: TileFlags,
+    unavailable: &TileCounts,
+) -> (TileCounts, u8) {
+    let mut remaining_tiles = [0; NUM_TILE_INDEX];
+    let mut num_remaining_tiles = 0;
+    for (i, is_target) in tiles.to_array().into_iter().enumerate() {
+        if is_target {
+            remaining_tiles[i] = MAX_TILE_COPIES - unavailable[i];
+            num_remaining_tiles += remaining_tiles[i];
+        }
+    }
+    (remaining_tiles, num_remaining_tiles)
+}
+
+/// Calculates the acceptance (受け入れ) of a given hand,
+/// taking into account the copies held in the hand and the visible tiles.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `visible` - A reference to the counts of the visible tiles other than the hand
+///   (e.g., discards, melds of other players and dora indicators).
+/// * `player_count` - A reference to the number of players.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid
+/// or the hand and the visible tiles contain more than 4 copies of a tile.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{PlayerCount, calculate_ukeire};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 123m456p789s1122z
+/// let hand: [u8; 34] = [
+///     1, 1, 1, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 1, 1, 1, // s
+///     2, 2, 0, 0, 0, 0, 0, // z
+/// ];
+/// // 2z
+/// let visible: [u8; 34] = [
+///     0, 0, 0, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 0, 0, 0, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 0, 0, 0, // s
+///     0, 1, 0, 0, 0, 0, 0, // z
+/// ];
+///
+/// let ukeire = calculate_ukeire(&hand, &visible, &PlayerCount::Four)?;
+/// assert_eq!(ukeire.replacement_number, 1u8);
+/// assert_eq!(ukeire.necessary_tiles, 0b0000011_000000000_000000000_000000000); // 12z
+/// assert_eq!(ukeire.remaining_tiles[27], 2); // 1z
+/// assert_eq!(ukeire.remaining_tiles[28], 1); // 2z
+/// assert_eq!(ukeire.num_remaining_tiles, 3);
+/// # Ok(())
+/// # }
+/// ```
+#[inline]
+pub fn calculate_ukeire(
+    bingpai: &impl Hand,
+    visible: &TileCounts,
+    player_count: &PlayerCount,
+) -> Result<Ukeire, BingpaiError> {
+    calculate_ukeire_with_config(bingpai, visible, &player_count.clone().into())
+}
+
+/// Calculates the acceptance (受け入れ) of a given hand under the given rules,
+/// taking into account the copies held in the hand and the visible tiles.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `visible` - A reference to the counts of the visible tiles other than the hand
+///   (e.g., discards, melds of other players and dora indicators).
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid, no selected winning form can be completed with the hand,
+/// or the hand and the visible tiles contain more than 4 copies of a tile.
+pub fn calculate_ukeire_with_config(
+    bingpai: &impl Hand,
+    visible: &TileCounts,
+    config: &Config,
+) -> Result<Ukeire, BingpaiError> {
+    constrained::check_no_wildcards(bingpai.num_wildcards(), config)?;
+    let (replacement_number, necessary_tiles) =
+        calculate_necessary_tiles_with_config(bingpai, config)?;
+    let unavailable = count_unavailable_tiles(bingpai, visible)?;
+    let (remaining_tiles, num_remaining_tiles) =
+        count_remaining_tiles(necessary_tiles, &unavailable);
+
+    Ok(Ukeire {
+        replacement_number,
+        necessary_tiles,
+        remaining_tiles,
+        num_remaining_tiles,
+    })
+}
+
+/// Calculates the second-order acceptance (two-step ukeire) of a given hand of 3n+1 tiles.
+///
+/// For each tile that can be drawn, the tile that maximizes the acceptance
+/// without raising the replacement number is assumed to be discarded.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `visible` - A reference to the counts of the visible tiles other than the hand
+///   (e.g., discards, melds of other players and dora indicators).
+///   If `None`, only the copies held in the hand are taken into account.
+/// * `player_count` - A reference to the number of players.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid, the number of tiles in the pure hand is not 3n+1,
+/// or the hand and the visible tiles contain more than 4 copies of a tile.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{PlayerCount, calculate_two_step_ukeire};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 123m456p789s1134z
+/// let hand: [u8; 34] = [
+///     1, 1, 1, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 1, 1, 1, // s
+///     2, 0, 1, 1, 0, 0, 0, // z
+/// ];
+///
+/// let two_step = calculate_two_step_ukeire(&hand, None, &PlayerCount::Four)?;
+/// assert_eq!(two_step.ukeire.replacement_number, 2u8);
+/// assert_eq!(two_step.ukeire.num_remaining_tiles, 8u8); // 1z, 3z and 4z
+/// // Any suited tile makes a new partial meld with the melds in hand.
+/// assert_eq!(two_step.num_improvement_tiles, 99u8);
+/// // 1z -> 4z (2 * 3), 3z -> 1z and 3z (3 * 4), 4z -> 1z and 4z (3 * 4)
+/// assert_eq!(two_step.total_next_ukeire, 30u16);
+/// assert_eq!(two_step.average_next_ukeire(), 3.75);
+/// # Ok(())
+/// # }
+/// ```
+#[inline]
+pub fn calculate_two_step_ukeire(
+    bingpai: &impl Hand,
+    visible: Option<&TileCounts>,
+    player_count: &PlayerCount,
+) -> Result<TwoStepUkeire, BingpaiError> {
+    calculate_two_step_ukeire_with_config(bingpai, visible, &player_count.clone().into())
+}
+
+/// Calculates the second-order acceptance (two-step ukeire) of a given hand of 3n+1 tiles
+/// under the given rules.
+///
+/// For each tile that can be drawn, the tile that maximizes the acceptance
+/// without raising the replacement number is assumed to be discarded.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `visible` - A reference to the counts of the visible tiles other than the hand
+///   (e.g., discards, melds of other players and dora indicators).
+///   If `None`, only the copies held in the hand are taken into account.
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid, the number of tiles in the pure hand is not 3n+1,
+/// no selected winning form can be completed with the hand,
+/// or the hand and the visible tiles contain more than 4 copies of a tile.
+pub fn calculate_two_step_ukeire_with_config(
+    bingpai: &impl Hand,
+    visible: Option<&TileCounts>,
+    config: &Config,
+) -> Result<TwoStepUkeire, BingpaiError> {
+    constrained::check_no_wildcards(bingpai.num_wildcards(), config)?;
+    // Validate first so that the tile count cannot overflow.
+    constrained::validate(bingpai.bingpai(), bingpai.melds(), 0, config)?;
+    let num_bingpai: u8 = bingpai.bingpai().iter().sum();
+    if num_bingpai % 3 != 1 {
+        return Err(BingpaiError::InvalidTileCountForDraw(num_bingpai));
+    }
+
+    let visible = visible.unwrap_or(&[0; NUM_TILE_INDEX]);
+    let ukeire = calculate_ukeire_with_config(bingpai, visible, config)?;
+
+    let unavailable = count_unavailable_tiles(bingpai, visible)?;
+
+    let mut improvement_tiles = 0;
+    let mut num_improvement_tiles = 0;
+    let mut total_next_ukeire = 0;
+
+    for (i, &u) in unavailable.iter().enumerate() {
+        let remaining = MAX_TILE_COPIES - u;
+        if remaining == 0 || !is_available(i, config) {
+            continue;
+        }
+
+        let mut drawn = *bingpai.bingpai();
+        drawn[i] += 1;
+        // The drawn tile is no longer in the wall.
+        let mut next_unavailable = unavailable;
+        next_unavailable[i] += 1;
+
+        let next_ukeire = calculate_max_ukeire_after_discard(
+            &FullHand {
+                bingpai: &drawn,
+                melds: bingpai.melds(),
+            },
+            &next_unavailable,
+            config,
+        )?;
+
+        if ukeire.necessary_tiles & (1 << i) != 0 {
+            total_next_ukeire += u16::from(remaining) * u16::from(next_ukeire);
+        } else if next_ukeire > ukeire.num_remaining_tiles {
+            improvement_tiles |= 1 << i;
+            num_improvement_tiles += remaining;
+        }
+    }
+
+    Ok(TwoStepUkeire {
+        ukeire,
+        improvement_tiles,
+        num_improvement_tiles,
+        total_next_ukeire,
+    })
+}
+
+/// Returns `true` if the tile is used under the given rules.
+#[inline]
+pub(crate) fn is_available(tile: usize, config: &Config) -> bool {
+    // Honors are not used in Sichuan mahjong.
+    if config.void_suit.is_some() && HONOR_TILES & (1 << tile) != 0 {
+        return false;
+    }
+    match config.player_count {
+        PlayerCount::Four => true,
+        // 2m-8m
+        PlayerCount::Three => !(1..8).contains(&tile),
+    }
+}
+
+/// Returns the maximum acceptance of a hand of 3n+2 tiles after discarding a tile
+/// that does not raise the replacement number.
+///
+/// Returns 0 if the hand is already complete.
+fn calculate_max_ukeire_after_discard(
+    hand: &FullHand,
+    unavailable: &TileCounts,
+    config: &Config,
+) -> Result<u8, BingpaiError> {
+    let (replacement_number, unnecessary_tiles) =
+        calculate_unnecessary_tiles_with_config(hand, config)?;
+    if replacement_number == 0 {
+        return Ok(0);
+    }
+
+    let mut max_ukeire = 0;
+    for (i, is_unnecessary) in unnecessary_tiles.to_array().into_iter().enumerate() {
+        if !is_unnecessary {
+            continue;
+        }
+
+        let mut discarded = *hand.bingpai;
+        discarded[i] -= 1;
+        let (_, necessary_tiles) = calculate_necessary_tiles_with_config(
+            &FullHand {
+                bingpai: &discarded,
+                melds: hand.melds,
+            },
+            config,
+        )?;
+        let (_, num_remaining_tiles) = count_remaining_tiles(necessary_tiles, unavailable);
+        max_ukeire = max_ukeire.max(num_remaining_tiles);
+    }
+
+    Ok(max_ukeire)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculate_replacement_number;
+    use crate::config::Suit;
+    use crate::hand::{FullHand, Meld};
+    use crate::test_utils::FromTileCode;
+
+    #[test]
+    fn calculate_ukeire_no_visible() {
+        let bingpai = TileCounts::from_code("123m456p789s2345z");
+        let visible = TileCounts::from_code("");
+        let ukeire = calculate_ukeire(&bingpai, &visible, &PlayerCount::Four).unwrap();
+        assert_eq!(ukeire.replacement_number, 3);
+        assert_eq!(ukeire.necessary_tiles, TileFlags::from_code("2345z"));
+        assert_eq!(
+            ukeire.remaining_tiles,
+            TileCounts::from_code("222333444555z")
+        );
+        assert_eq!(ukeire.num_remaining_tiles, 12);
+    }
+
+    #[test]
+    fn calculate_ukeire_held_copies() {
+        let bingpai = TileCounts::from_code("1112m456p789s111z");
+        let visible = TileCounts::from_code("3m");
+        let ukeire = calculate_ukeire(&bingpai, &visible, &PlayerCount::Four).unwrap();
+        assert_eq!(ukeire.replacement_number, 1);
+        assert_eq!(ukeire.necessary_tiles, TileFlags::from_code("23m"));
+        assert_eq!(ukeire.remaining_tiles, TileCounts::from_code("222333m"));
+        assert_eq!(ukeire.num_remaining_tiles, 6);
+    }
+
+    #[test]
+    fn calculate_ukeire_with_melds() {
+        let bingpai = TileCounts::from_code("1m");
+        let melds = [Meld::Pon(27), Meld::Pon(28), Meld::Pon(29), Meld::Chi(1)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let visible = TileCounts::from_code("11m");
+        let ukeire = calculate_ukeire(&hand, &visible, &PlayerCount::Four).unwrap();
+        assert_eq!(ukeire.replacement_number, 1);
+        assert_eq!(ukeire.necessary_tiles, TileFlags::from_code("1m"));
+        assert_eq!(ukeire.num_remaining_tiles, 1);
+    }
+
+    #[test]
+    fn calculate_ukeire_err_too_many_visible_copies() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let visible = TileCounts::from_code("333z");
+        let ukeire = calculate_ukeire(&bingpai, &visible, &PlayerCount::Four);
+        assert!(ukeire.is_ok());
+
+        let visible = TileCounts::from_code("333z111m");
+        let ukeire = calculate_ukeire(&bingpai, &visible, &PlayerCount::Four);
+        assert!(ukeire.is_ok());
+
+        let visible = TileCounts::from_code("1111m");
+        let ukeire = calculate_ukeire(&bingpai, &visible, &PlayerCount::Four);
+        assert!(matches!(
+            ukeire,
+            Err(BingpaiError::TooManyVisibleCopies { tile: 0, count: 5 })
+        ));
+    }
+
+    #[test]
+    fn calculate_ukeire_err_too_many_visible_copies_with_melds() {
+        let bingpai = TileCounts::from_code("1z");
+        let melds = [Meld::Ankan(0)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let visible = TileCounts::from_code("1m");
+        let ukeire = calculate_ukeire(&hand, &visible, &PlayerCount::Four);
+        assert!(matches!(
+            ukeire,
+            Err(BingpaiError::TooManyVisibleCopies { tile: 0, count: 5 })
+        ));
+    }
+
+    fn brute_force_two_step_ukeire(
+        bingpai: &TileCounts,
+        visible: &TileCounts,
+    ) -> (TileFlags, u8, u16) {
+        let ukeire = calculate_ukeire(bingpai, visible, &PlayerCount::Four).unwrap();
+        let mut improvement_tiles = 0;
+        let mut num_improvement_tiles = 0;
+        let mut total_next_ukeire = 0;
+
+        for i in 0..NUM_TILE_INDEX {
+            let remaining = MAX_TILE_COPIES - bingpai[i] - visible[i];
+            if remaining == 0 {
+                continue;
+            }
+
+            let mut drawn = *bingpai;
+            drawn[i] += 1;
+
+            // (replacement number, ukeire) of the best discard
+            let mut best = (u8::MAX, 0);
+            for j in 0..NUM_TILE_INDEX {
+                if drawn[j] == 0 {
+                    continue;
+                }
+                let mut discarded = drawn;
+                discarded[j] -= 1;
+                let mut next_visible = *visible;
+                next_visible[j] += 1;
+                let next = calculate_ukeire(&discarded, &next_visible, &PlayerCount::Four).unwrap();
+                let candidate = (next.replacement_number, next.num_remaining_tiles);
+                if candidate.0 < best.0 || (candidate.0 == best.0 && candidate.1 > best.1) {
+                    best = candidate;
+                }
+            }
+
+            if ukeire.necessary_tiles & (1 << i) != 0 {
+                // A winning draw has no next acceptance.
+                if calculate_replacement_number(&drawn, &PlayerCount::Four).unwrap() > 0 {
+                    total_next_ukeire += u16::from(remaining) * u16::from(best.1);
+                }
+            } else if best.1 > ukeire.num_remaining_tiles {
+                improvement_tiles |= 1 << i;
+                num_improvement_tiles += remaining;
+            }
+        }
+
+        (improvement_tiles, num_improvement_tiles, total_next_ukeire)
+    }
+
+    #[test]
+    fn calculate_two_step_ukeire_matches_brute_force() {
+        for (code, visible) in [
+            ("123m456p789s1134z", ""),
+            ("123m456p789s1122z", "1z"),
+            ("25m456p789s11z", "3m"),
+            ("199m14679p12s246z", ""),
+            ("2356m1178p359s35z", "444m"),
+            ("1m", ""),
+        ] {
+            let bingpai = TileCounts::from_code(code);
+            let visible = TileCounts::from_code(visible);
+            let two_step =
+                calculate_two_step_ukeire(&bingpai, Some(&visible), &PlayerCount::Four).unwrap();
+            let (improvement_tiles, num_improvement_tiles, total_next_ukeire) =
+                brute_force_two_step_ukeire(&bingpai, &visible);
+            assert_eq!(two_step.improvement_tiles, improvement_tiles, "{code}");
+            assert_eq!(
+                two_step.num_improvement_tiles, num_improvement_tiles,
+                "{code}"
+            );
+            assert_eq!(two_step.total_next_ukeire, total_next_ukeire, "{code}");
+        }
+    }
+
+    #[test]
+    fn calculate_two_step_ukeire_err_3n_plus_2() {
+        let bingpai = TileCounts::from_code("123m456p789s11223z");
+        let two_step = calculate_two_step_ukeire(&bingpai, None, &PlayerCount::Four);
+        assert!(matches!(
+            two_step,
+            Err(BingpaiError::InvalidTileCountForDraw(14))
+        ));
+    }
+
+    #[test]
+    fn calculate_two_step_ukeire_err_too_many_copies() {
+        let bingpai = [8; 34];
+        let two_step = calculate_two_step_ukeire(&bingpai, None, &PlayerCount::Four);
+        assert!(matches!(
+            two_step,
+            Err(BingpaiError::TooManyCopies { tile: 0, count: 8 })
+        ));
+    }
+
+    #[test]
+    fn calculate_two_step_ukeire_sichuan() {
+        let bingpai = TileCounts::from_code("123p456p789s11s23s");
+        let config = Config {
+            void_suit: Some(Suit::Wanzi),
+            ..Default::default()
+        };
+        let two_step = calculate_two_step_ukeire_with_config(&bingpai, None, &config).unwrap();
+        // 1s and 4s
+        assert_eq!(two_step.ukeire.necessary_tiles, TileFlags::from_code("14s"));
+        assert_eq!(two_step.ukeire.num_remaining_tiles, 6);
+        assert_eq!(two_step.improvement_tiles & HONOR_TILES, 0);
+    }
+}
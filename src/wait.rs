@@ -0,0 +1,554 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use super::constrained;
+use crate::bingpai::BingpaiError;
+use crate::config::{Config, PlayerCount, WinningForms};
+use crate::decomposition::{BlockKind, Decomposition};
+use crate::hand::{FullHand, Hand};
+use crate::necessary_tiles::calculate_necessary_tiles_with_config;
+use crate::tile::{NUM_TILE_INDEX, TileCounts, TileFlags, TileFlagsExt};
+use crate::ukeire::{count_remaining_tiles, count_unavailable_tiles};
+use crate::winning::winning_decompositions_with_config;
+use core::ops::{BitOr, BitOrAssign};
+
+/// A set of wait shapes (待ちの形).
+///
+/// The shapes can be combined with the `|` operator.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::WaitKinds;
+/// let kinds = WaitKinds::LIANGMIAN | WaitKinds::SHUANGPENG;
+/// assert!(kinds.contains(WaitKinds::SHUANGPENG));
+/// assert!(!kinds.contains(WaitKinds::KANZHANG));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct WaitKinds(u8);
+
+impl WaitKinds {
+    /// 両面: An open wait (e.g., 23m waiting for 1m and 4m).
+    pub const LIANGMIAN: Self = Self(1 << 0);
+    /// 嵌張: A closed wait (e.g., 13m waiting for 2m).
+    pub const KANZHANG: Self = Self(1 << 1);
+    /// 辺張: An edge wait (12m waiting for 3m, or 89m waiting for 7m).
+    pub const BIANZHANG: Self = Self(1 << 2);
+    /// 双碰: A dual pon wait (e.g., 11m22m waiting for 1m and 2m).
+    pub const SHUANGPENG: Self = Self(1 << 3);
+    /// 単騎: A single wait (e.g., 1m waiting for 1m).
+    pub const DANQI: Self = Self(1 << 4);
+    /// 延べ単: A single wait on either end of 4 consecutive tiles
+    /// (e.g., 1234m waiting for 1m and 4m).
+    pub const YANDAN: Self = Self(1 << 5);
+    /// 十三面: The 13-sided wait of thirteen orphans (国士無双).
+    pub const SHISANMIAN: Self = Self(1 << 6);
+
+    /// Returns an empty set of wait shapes.
+    #[inline]
+    #[must_use]
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// Returns `true` if no wait shape is included.
+    #[inline]
+    #[must_use]
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all shapes in `other` are included.
+    #[inline]
+    #[must_use]
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for WaitKinds {
+    type Output = Self;
+
+    #[inline]
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for WaitKinds {
+    #[inline]
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+/// The waits (待ち) of a tenpai (聴牌) hand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Waits {
+    /// The winning tiles (和了牌).
+    pub tiles: TileFlags,
+    /// The wait shapes completed by each winning tile.
+    ///
+    /// The shapes of the tiles that are not winning tiles are empty.
+    pub kinds: [WaitKinds; NUM_TILE_INDEX],
+}
+
+/// The furiten (振聴) and karaten (空聴) status of a tenpai (聴牌) hand.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaitStatus {
+    /// The winning tiles (和了牌).
+    pub necessary_tiles: TileFlags,
+    /// The winning tiles in the player's own discards.
+    pub furiten_tiles: TileFlags,
+    /// The total number of remaining copies of the winning tiles.
+    pub num_remaining_tiles: u8,
+    /// Whether the hand is furiten (振聴),
+    /// i.e., any winning tile is in the player's own discards.
+    pub is_furiten: bool,
+    /// Whether the hand is karaten (空聴),
+    /// i.e., every copy of every winning tile is held or visible.
+    pub is_karaten: bool,
+}
+
+/// Calculates the furiten (振聴) and karaten (空聴) status of a given tenpai (聴牌) hand.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `discards` - A reference to the counts of the player's own discards (捨て牌),
+///   including the ones called by other players.
+/// * `visible` - A reference to the counts of the visible tiles other than the hand
+///   and the player's own discards (e.g., discards and melds of other players, dora indicators).
+///   If `None`, only the hand and the player's own discards are taken into account.
+/// * `player_count` - A reference to the number of players.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid, the number of tiles in the pure hand is not 3n+1,
+/// the hand is not tenpai, or the hand, the discards and the visible tiles contain
+/// more than 4 copies of a tile.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{PlayerCount, calculate_wait_status};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 23m456p789s11122z
+/// let hand: [u8; 34] = [
+///     0, 1, 1, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 1, 1, 1, // s
+///     3, 2, 0, 0, 0, 0, 0, // z
+/// ];
+/// // 4m
+/// let discards: [u8; 34] = [
+///     0, 0, 0, 1, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 0, 0, 0, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 0, 0, 0, // s
+///     0, 0, 0, 0, 0, 0, 0, // z
+/// ];
+///
+/// let status = calculate_wait_status(&hand, &discards, None, &PlayerCount::Four)?;
+/// assert_eq!(status.furiten_tiles, 0b1000); // 4m
+/// assert!(status.is_furiten);
+/// assert_eq!(status.num_remaining_tiles, 7); // 1m and 4m
+/// assert!(!status.is_karaten);
+/// # Ok(())
+/// # }
+/// ```
+#[inline]
+pub fn calculate_wait_status(
+    bingpai: &impl Hand,
+    discards: &TileCounts,
+    visible: Option<&TileCounts>,
+    player_count: &PlayerCount,
+) -> Result<WaitStatus, BingpaiError> {
+    calculate_wait_status_with_config(bingpai, discards, visible, &player_count.clone().into())
+}
+
+/// Calculates the furiten (振聴) and karaten (空聴) status of a given tenpai (聴牌) hand
+/// under the given rules.
+///
+/// Only the winning forms selected in `config` are considered.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `discards` - A reference to the counts of the player's own discards (捨て牌),
+///   including the ones called by other players.
+/// * `visible` - A reference to the counts of the visible tiles other than the hand
+///   and the player's own discards (e.g., discards and melds of other players, dora indicators).
+///   If `None`, only the hand and the player's own discards are taken into account.
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid, the number of tiles in the pure hand is not 3n+1,
+/// no selected winning form can be completed with the hand, the hand is not tenpai,
+/// or the hand, the discards and the visible tiles contain more than 4 copies of a tile.
+pub fn calculate_wait_status_with_config(
+    bingpai: &impl Hand,
+    discards: &TileCounts,
+    visible: Option<&TileCounts>,
+    config: &Config,
+) -> Result<WaitStatus, BingpaiError> {
+    constrained::check_no_wildcards(bingpai.num_wildcards(), config)?;
+    // Validate first so that the tile count cannot overflow.
+    constrained::validate(bingpai.bingpai(), bingpai.melds(), 0, config)?;
+    let num_bingpai: u8 = bingpai.bingpai().iter().sum();
+    if num_bingpai % 3 != 1 {
+        return Err(BingpaiError::InvalidTileCountForDraw(num_bingpai));
+    }
+
+    let (replacement_number, necessary_tiles) =
+        calculate_necessary_tiles_with_config(bingpai, config)?;
+    if replacement_number != 1 {
+        return Err(BingpaiError::NotTenpai(replacement_number));
+    }
+
+    let mut seen = *discards;
+    if let Some(visible) = visible {
+        for (s, &v) in seen.iter_mut().zip(visible.iter()) {
+            *s = s.saturating_add(v);
+        }
+    }
+    let unavailable = count_unavailable_tiles(bingpai, &seen)?;
+    let (_, num_remaining_tiles) = count_remaining_tiles(necessary_tiles, &unavailable);
+
+    let discarded_tiles = discards
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| **c > 0)
+        .fold(0, |flags: TileFlags, (i, _)| flags | (1 << i));
+    let furiten_tiles = necessary_tiles & discarded_tiles;
+
+    Ok(WaitStatus {
+        necessary_tiles,
+        furiten_tiles,
+        num_remaining_tiles,
+        is_furiten: furiten_tiles != 0,
+        is_karaten: num_remaining_tiles == 0,
+    })
+}
+
+/// Calculates the winning tiles (和了牌) of a given tenpai (聴牌) hand
+/// and the wait shapes (待ちの形) each of them completes.
+///
+/// A winning tile completes a shape for each decomposition of the completed hand
+/// in which it belongs to a block, so it may complete several shapes
+/// (e.g., 2m of 1223m completes both a closed wait and a single wait).
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `player_count` - A reference to the number of players.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid, the number of tiles in the pure hand is not 3n+1,
+/// or the hand is not tenpai.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{PlayerCount, WaitKinds, calculate_waits};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 23m456p789s11122z
+/// let hand: [u8; 34] = [
+///     0, 1, 1, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 1, 1, 1, // s
+///     3, 2, 0, 0, 0, 0, 0, // z
+/// ];
+///
+/// let waits = calculate_waits(&hand, &PlayerCount::Four)?;
+/// assert_eq!(waits.tiles, 0b1001); // 1m and 4m
+/// assert_eq!(waits.kinds[0], WaitKinds::LIANGMIAN); // 1m
+/// assert_eq!(waits.kinds[3], WaitKinds::LIANGMIAN); // 4m
+/// # Ok(())
+/// # }
+/// ```
+#[inline]
+pub fn calculate_waits(
+    bingpai: &impl Hand,
+    player_count: &PlayerCount,
+) -> Result<Waits, BingpaiError> {
+    calculate_waits_with_config(bingpai, &player_count.clone().into())
+}
+
+/// Calculates the winning tiles (和了牌) of a given tenpai (聴牌) hand
+/// and the wait shapes (待ちの形) each of them completes under the given rules.
+///
+/// Only the winning forms selected in `config` are considered.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid, the number of tiles in the pure hand is not 3n+1,
+/// no selected winning form can be completed with the hand, or the hand is not tenpai.
+pub fn calculate_waits_with_config(
+    bingpai: &impl Hand,
+    config: &Config,
+) -> Result<Waits, BingpaiError> {
+    constrained::check_no_wildcards(bingpai.num_wildcards(), config)?;
+    // Validate first so that the tile count cannot overflow.
+    constrained::validate(bingpai.bingpai(), bingpai.melds(), 0, config)?;
+    let num_bingpai: u8 = bingpai.bingpai().iter().sum();
+    if num_bingpai % 3 != 1 {
+        return Err(BingpaiError::InvalidTileCountForDraw(num_bingpai));
+    }
+
+    let (replacement_number, tiles) = calculate_necessary_tiles_with_config(bingpai, config)?;
+    if replacement_number != 1 {
+        return Err(BingpaiError::NotTenpai(replacement_number));
+    }
+
+    let mut kinds = [WaitKinds::empty(); NUM_TILE_INDEX];
+    for (i, is_winning_tile) in tiles.to_array().into_iter().enumerate() {
+        if !is_winning_tile {
+            continue;
+        }
+
+        let mut completed = *bingpai.bingpai();
+        completed[i] += 1;
+        let hand = FullHand {
+            bingpai: &completed,
+            melds: bingpai.melds(),
+        };
+        for decomposition in winning_decompositions_with_config(&hand, config)? {
+            kinds[i] |= classify_wait(&decomposition, i, bingpai.bingpai());
+        }
+    }
+
+    Ok(Waits { tiles, kinds })
+}
+
+/// Returns the wait shapes that the winning tile completes in a decomposition of the completed hand.
+fn classify_wait(decomposition: &Decomposition, tile: usize, bingpai: &TileCounts) -> WaitKinds {
+    let form = decomposition.form();
+    if form == WinningForms::QIDUIZI {
+        return WaitKinds::DANQI;
+    }
+    if form == WinningForms::SHISANYAO {
+        // The hand before the win has all 13 kinds of terminal and honor tiles.
+        return match bingpai.iter().filter(|&&c| c > 0).count() {
+            13 => WaitKinds::SHISANMIAN,
+            _ => WaitKinds::DANQI,
+        };
+    }
+
+    let blocks = decomposition.blocks();
+    let mut kinds = WaitKinds::empty();
+    for block in blocks {
+        let first = block.tile as usize;
+        if !block.kind.offsets().iter().any(|&o| first + o == tile) {
+            continue;
+        }
+
+        kinds |= match block.kind {
+            BlockKind::Kezi => WaitKinds::SHUANGPENG,
+            BlockKind::Shunzi => match (tile - first, first % 9) {
+                (1, _) => WaitKinds::KANZHANG,
+                // 12m waiting for 3m, or 89m waiting for 7m.
+                (2, 0) | (0, 6) => WaitKinds::BIANZHANG,
+                _ => WaitKinds::LIANGMIAN,
+            },
+            BlockKind::Quetou => {
+                // A sequence next to the pair, e.g., 1m + 234m or 123m + 4m.
+                let is_yandan = blocks.iter().any(|b| {
+                    b.kind == BlockKind::Shunzi
+                        && tile < 27
+                        && ((tile % 9 < 6 && b.tile as usize == tile + 1)
+                            || (tile % 9 >= 3 && b.tile as usize + 3 == tile))
+                });
+                if is_yandan {
+                    WaitKinds::YANDAN
+                } else {
+                    WaitKinds::DANQI
+                }
+            }
+            _ => WaitKinds::empty(),
+        };
+    }
+    kinds
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hand::Meld;
+    use crate::test_utils::FromTileCode;
+
+    fn calculate(code: &str) -> Waits {
+        let bingpai = TileCounts::from_code(code);
+        calculate_waits(&bingpai, &PlayerCount::Four).unwrap()
+    }
+
+    #[test]
+    fn calculate_waits_liangmian() {
+        let waits = calculate("23m456p789s11122z");
+        assert_eq!(waits.tiles, TileFlags::from_code("14m"));
+        assert_eq!(waits.kinds[0], WaitKinds::LIANGMIAN);
+        assert_eq!(waits.kinds[3], WaitKinds::LIANGMIAN);
+    }
+
+    #[test]
+    fn calculate_waits_kanzhang_and_bianzhang() {
+        let waits = calculate("13m456p789s11122z");
+        assert_eq!(waits.tiles, TileFlags::from_code("2m"));
+        assert_eq!(waits.kinds[1], WaitKinds::KANZHANG);
+
+        let waits = calculate("89m456p789s11122z");
+        assert_eq!(waits.tiles, TileFlags::from_code("7m"));
+        assert_eq!(waits.kinds[6], WaitKinds::BIANZHANG);
+
+        let waits = calculate("12m456p789s11122z");
+        assert_eq!(waits.kinds[2], WaitKinds::BIANZHANG);
+    }
+
+    #[test]
+    fn calculate_waits_shuangpeng_and_danqi() {
+        let waits = calculate("123m456p789s1122z");
+        assert_eq!(waits.kinds[27], WaitKinds::SHUANGPENG);
+        assert_eq!(waits.kinds[28], WaitKinds::SHUANGPENG);
+
+        let waits = calculate("123m456p789s1113z");
+        assert_eq!(waits.tiles, TileFlags::from_code("3z"));
+        assert_eq!(waits.kinds[29], WaitKinds::DANQI);
+    }
+
+    #[test]
+    fn calculate_waits_yandan() {
+        let waits = calculate("1234m456p789s111z");
+        assert_eq!(waits.tiles, TileFlags::from_code("14m"));
+        assert_eq!(waits.kinds[0], WaitKinds::YANDAN);
+        assert_eq!(waits.kinds[3], WaitKinds::YANDAN);
+    }
+
+    #[test]
+    fn calculate_waits_multiple_kinds() {
+        // 2m: 123m + 22m completes both 13m + 22m (closed) and 123m + 2m (single).
+        let waits = calculate("1223m456p789s111z");
+        assert_eq!(waits.tiles, TileFlags::from_code("2m"));
+        assert_eq!(waits.kinds[1], WaitKinds::KANZHANG | WaitKinds::DANQI);
+    }
+
+    #[test]
+    fn calculate_waits_qiduizi_and_shisanyao() {
+        let waits = calculate("1122m3344p5566s7z");
+        assert_eq!(waits.kinds[33], WaitKinds::DANQI);
+
+        let waits = calculate("19m19p19s1234567z");
+        assert_eq!(waits.tiles.count_ones(), 13);
+        assert_eq!(waits.kinds[0], WaitKinds::SHISANMIAN);
+
+        let waits = calculate("119m19p19s123456z");
+        assert_eq!(waits.tiles, TileFlags::from_code("7z"));
+        assert_eq!(waits.kinds[33], WaitKinds::DANQI);
+    }
+
+    #[test]
+    fn calculate_waits_with_melds() {
+        let bingpai = TileCounts::from_code("1m");
+        let melds = [Meld::Pon(27), Meld::Pon(28), Meld::Pon(29), Meld::Chi(9)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        let waits = calculate_waits(&hand, &PlayerCount::Four).unwrap();
+        assert_eq!(waits.kinds[0], WaitKinds::DANQI);
+    }
+
+    #[test]
+    fn calculate_wait_status_furiten() {
+        let bingpai = TileCounts::from_code("23m456p789s11122z");
+        let discards = TileCounts::from_code("59m4z");
+        let status = calculate_wait_status(&bingpai, &discards, None, &PlayerCount::Four).unwrap();
+        assert_eq!(status.furiten_tiles, 0);
+        assert!(!status.is_furiten);
+
+        let discards = TileCounts::from_code("1m");
+        let status = calculate_wait_status(&bingpai, &discards, None, &PlayerCount::Four).unwrap();
+        assert_eq!(status.furiten_tiles, TileFlags::from_code("1m"));
+        assert!(status.is_furiten);
+        assert_eq!(status.num_remaining_tiles, 7);
+    }
+
+    #[test]
+    fn calculate_wait_status_karaten() {
+        let bingpai = TileCounts::from_code("123m456p789s1113z");
+        let discards = TileCounts::from_code("3z");
+        let visible = TileCounts::from_code("33z");
+        let status =
+            calculate_wait_status(&bingpai, &discards, Some(&visible), &PlayerCount::Four).unwrap();
+        assert_eq!(status.num_remaining_tiles, 0);
+        assert!(status.is_furiten);
+        assert!(status.is_karaten);
+
+        let status = calculate_wait_status(
+            &bingpai,
+            &[0; NUM_TILE_INDEX],
+            Some(&visible),
+            &PlayerCount::Four,
+        )
+        .unwrap();
+        assert_eq!(status.num_remaining_tiles, 1);
+        assert!(!status.is_furiten);
+        assert!(!status.is_karaten);
+    }
+
+    #[test]
+    fn calculate_wait_status_err_too_many_visible_copies() {
+        let bingpai = TileCounts::from_code("123m456p789s1113z");
+        let discards = TileCounts::from_code("33z");
+        let visible = TileCounts::from_code("33z");
+        let status = calculate_wait_status(&bingpai, &discards, Some(&visible), &PlayerCount::Four);
+        assert!(matches!(
+            status,
+            Err(BingpaiError::TooManyVisibleCopies { tile: 29, count: 5 })
+        ));
+    }
+
+    #[test]
+    fn calculate_waits_err_not_tenpai() {
+        let bingpai = TileCounts::from_code("123m456p789s1234z");
+        let waits = calculate_waits(&bingpai, &PlayerCount::Four);
+        assert!(matches!(waits, Err(BingpaiError::NotTenpai(3))));
+    }
+
+    #[test]
+    fn calculate_waits_err_too_many_copies() {
+        let bingpai = [8; 34];
+        let waits = calculate_waits(&bingpai, &PlayerCount::Four);
+        assert!(matches!(
+            waits,
+            Err(BingpaiError::TooManyCopies { tile: 0, count: 8 })
+        ));
+    }
+
+    #[test]
+    fn calculate_wait_status_err_too_many_copies() {
+        let bingpai = [8; 34];
+        let discards = [0; 34];
+        let status = calculate_wait_status(&bingpai, &discards, None, &PlayerCount::Four);
+        assert!(matches!(
+            status,
+            Err(BingpaiError::TooManyCopies { tile: 0, count: 8 })
+        ));
+    }
+}
@@ -0,0 +1,286 @@
+// SPDX-FileCopyrightText: 2025 Apricot S.
+// SPDX-License-Identifier: MIT
+// This file is part of https://github.com/Apricot-S/xiangting
+
+use crate::bingpai::BingpaiError;
+use crate::config::{Config, PlayerCount};
+use crate::decomposition::{Decompositions, decompose_with_config};
+use crate::hand::Hand;
+use crate::replacement_number::calculate_replacement_number_with_config;
+
+/// Returns `true` if a given hand is a winning hand (和了形).
+///
+/// This is equivalent to checking if the replacement number is 0,
+/// and a hand of 3n+1 tiles is never a winning hand.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `player_count` - A reference to the number of players.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{PlayerCount, is_winning};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 123m456p789s11222z
+/// let hand: [u8; 34] = [
+///     1, 1, 1, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 1, 1, 1, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 1, 1, 1, // s
+///     2, 3, 0, 0, 0, 0, 0, // z
+/// ];
+///
+/// assert!(is_winning(&hand, &PlayerCount::Four)?);
+/// # Ok(())
+/// # }
+/// ```
+#[inline]
+pub fn is_winning(bingpai: &impl Hand, player_count: &PlayerCount) -> Result<bool, BingpaiError> {
+    is_winning_with_config(bingpai, &player_count.clone().into())
+}
+
+/// Returns `true` if a given hand is a winning hand (和了形) under the given rules.
+///
+/// Only the winning forms selected in `config` are considered.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid or no selected winning form can be completed with the hand.
+#[inline]
+pub fn is_winning_with_config(bingpai: &impl Hand, config: &Config) -> Result<bool, BingpaiError> {
+    calculate_replacement_number_with_config(bingpai, config).map(|r| r == 0)
+}
+
+/// Enumerates all decompositions of a given winning hand (和了形)
+/// into melds (面子) and a pair (雀頭), seven pairs (七対子) or thirteen orphans (国士無双).
+///
+/// The iterator yields nothing if the hand is not a winning hand.
+/// See [`decompose`](crate::decompose) for the blocks of each form.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `player_count` - A reference to the number of players.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid.
+///
+/// # Examples
+///
+/// ```
+/// # use xiangting::{PlayerCount, WinningForms, winning_decompositions};
+/// # use xiangting::BingpaiError;
+/// # fn main() -> Result<(), BingpaiError> {
+/// // 112233m445566p77z
+/// let hand: [u8; 34] = [
+///     2, 2, 2, 0, 0, 0, 0, 0, 0, // m
+///     0, 0, 0, 2, 2, 2, 0, 0, 0, // p
+///     0, 0, 0, 0, 0, 0, 0, 0, 0, // s
+///     0, 0, 0, 0, 0, 0, 2, // z
+/// ];
+///
+/// let mut decompositions = winning_decompositions(&hand, &PlayerCount::Four)?;
+/// // 123m 123m 456p 456p 77z
+/// assert_eq!(decompositions.next().unwrap().form(), WinningForms::STANDARD);
+/// // 11m 22m 33m 44p 55p 66p 77z
+/// assert_eq!(decompositions.next().unwrap().form(), WinningForms::QIDUIZI);
+/// assert!(decompositions.next().is_none());
+/// # Ok(())
+/// # }
+/// ```
+#[inline]
+pub fn winning_decompositions(
+    bingpai: &impl Hand,
+    player_count: &PlayerCount,
+) -> Result<Decompositions, BingpaiError> {
+    winning_decompositions_with_config(bingpai, &player_count.clone().into())
+}
+
+/// Enumerates all decompositions of a given winning hand (和了形) under the given rules.
+///
+/// Only the winning forms selected in `config` are considered.
+/// The iterator yields nothing if the hand is not a winning hand.
+///
+/// # Arguments
+///
+/// * `bingpai` - 兵牌: A reference to a hand excluding melds (a.k.a. pure hand, 純手牌),
+///   such as [`TileCounts`](crate::TileCounts) or [`TileCountsWithRed`](crate::TileCountsWithRed),
+///   or a [`FullHand`](crate::FullHand) with melds (副露).
+/// * `config` - A reference to the rules.
+///
+/// # Errors
+///
+/// Returns [`Err`] if the hand is invalid or no selected winning form can be completed with the hand.
+pub fn winning_decompositions_with_config(
+    bingpai: &impl Hand,
+    config: &Config,
+) -> Result<Decompositions, BingpaiError> {
+    let decompositions = decompose_with_config(bingpai, config)?;
+    match decompositions.replacement_number() {
+        0 => Ok(decompositions),
+        r => Ok(Decompositions::empty(r)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::WinningForms;
+    use crate::decomposition::BlockKind;
+    use crate::hand::{FullHand, Meld};
+    use crate::replacement_number::calculate_replacement_number;
+    use crate::test_utils::FromTileCode;
+    use crate::tile::TileCounts;
+
+    const HANDS: [&str; 12] = [
+        "123m456p789s11222z",
+        "11123455678999m",
+        "112233m445566p77z",
+        "19m19p19s12345677z",
+        "22334455667788s",
+        "11112222333344z",
+        "123m456p789s1122z",
+        "123m456p789s11223z",
+        "11m",
+        "1m",
+        "111999m111999p11z",
+        "19m19p19s1234567z",
+    ];
+
+    #[test]
+    fn is_winning_matches_replacement_number() {
+        for player_count in [PlayerCount::Four, PlayerCount::Three] {
+            for code in HANDS {
+                let bingpai = TileCounts::from_code(code);
+                let Ok(replacement_number) = calculate_replacement_number(&bingpai, &player_count)
+                else {
+                    // Skip hands with 2m-8m in three-player mahjong.
+                    continue;
+                };
+                assert_eq!(
+                    is_winning(&bingpai, &player_count).unwrap(),
+                    replacement_number == 0,
+                    "{code}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn is_winning_forms() {
+        let standard = TileCounts::from_code("123m456p789s11222z");
+        let qiduizi = TileCounts::from_code("1133m5577p99s1122z");
+        let shisanyao = TileCounts::from_code("19m19p19s12345677z");
+        let tenpai = TileCounts::from_code("123m456p789s1122z");
+        assert!(is_winning(&standard, &PlayerCount::Four).unwrap());
+        assert!(is_winning(&qiduizi, &PlayerCount::Four).unwrap());
+        assert!(is_winning(&shisanyao, &PlayerCount::Four).unwrap());
+        assert!(!is_winning(&tenpai, &PlayerCount::Four).unwrap());
+
+        let config = Config {
+            forms: WinningForms::STANDARD,
+            ..Default::default()
+        };
+        assert!(!is_winning_with_config(&qiduizi, &config).unwrap());
+    }
+
+    #[test]
+    fn winning_decompositions_are_complete() {
+        for player_count in [PlayerCount::Four, PlayerCount::Three] {
+            for code in HANDS {
+                let bingpai = TileCounts::from_code(code);
+                let Ok(is_winning) = is_winning(&bingpai, &player_count) else {
+                    continue;
+                };
+
+                let mut num_decompositions = 0;
+                for decomposition in winning_decompositions(&bingpai, &player_count).unwrap() {
+                    let is_complete = decomposition.blocks().iter().all(|b| {
+                        matches!(
+                            (decomposition.form(), b.kind),
+                            (
+                                WinningForms::STANDARD,
+                                BlockKind::Kezi | BlockKind::Shunzi | BlockKind::Quetou
+                            ) | (WinningForms::QIDUIZI, BlockKind::Duizi)
+                                | (
+                                    WinningForms::SHISANYAO,
+                                    BlockKind::Yaojiu | BlockKind::Quetou
+                                )
+                        )
+                    });
+                    assert!(is_complete, "{code}");
+                    num_decompositions += 1;
+                }
+                assert_eq!(num_decompositions > 0, is_winning, "{code}");
+            }
+        }
+    }
+
+    #[test]
+    fn winning_decompositions_chuuren() {
+        // 1112345678999m + 5m
+        let bingpai = TileCounts::from_code("11123455678999m");
+        let decompositions = winning_decompositions(&bingpai, &PlayerCount::Four).unwrap();
+        // 111m 234m 55m 678m 999m
+        assert_eq!(decompositions.count(), 1);
+    }
+
+    #[test]
+    fn winning_decompositions_with_melds() {
+        let bingpai = TileCounts::from_code("11122m");
+        let melds = [Meld::Pon(27), Meld::Chi(9), Meld::Ankan(33)];
+        let hand = FullHand {
+            bingpai: &bingpai,
+            melds: &melds,
+        };
+        assert!(is_winning(&hand, &PlayerCount::Four).unwrap());
+        // 111m 22m
+        assert_eq!(
+            winning_decompositions(&hand, &PlayerCount::Four)
+                .unwrap()
+                .count(),
+            1
+        );
+    }
+
+    #[test]
+    fn winning_decompositions_sixteen_tiles() {
+        let bingpai = TileCounts::from_code("123456789m123456p11z");
+        let config = Config {
+            sixteen_tiles: true,
+            ..Default::default()
+        };
+        let mut decompositions = winning_decompositions_with_config(&bingpai, &config).unwrap();
+        // 123m 456m 789m 123p 456p 11z
+        let decomposition = decompositions.next().unwrap();
+        assert_eq!(decomposition.form(), WinningForms::STANDARD);
+        assert_eq!(decomposition.blocks().len(), 6);
+        assert!(decompositions.next().is_none());
+    }
+
+    #[test]
+    fn winning_decompositions_not_winning() {
+        let bingpai = TileCounts::from_code("123m456p789s1122z");
+        let mut decompositions = winning_decompositions(&bingpai, &PlayerCount::Four).unwrap();
+        assert_eq!(decompositions.replacement_number(), 1);
+        assert!(decompositions.next().is_none());
+    }
+}